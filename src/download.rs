@@ -0,0 +1,1953 @@
+//! Downloading archives.
+
+use anyhow::anyhow;
+use anyhow::{Context, Result};
+use reqwest::{header, Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use std::io::Read;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicU64, Ordering as AtomicOrdering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use std::{fs, io};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tokio_util::sync::CancellationToken;
+
+use crate::extract::check_archive;
+use crate::network::{http_error, proxy_rejected, HttpError};
+use crate::progress::{Phase, ProgressReporter};
+use crate::version_list::templates_version;
+use crate::{godot, is_cancelled, CachedArchive, Cancelled, MirrorSpeed};
+
+/// Checks the downloaded archive at `path` against the SHA-512 digest from
+/// the version list. A mismatching download is deleted so the next attempt
+/// starts over. Without a digest this only warns, unless `require` is set.
+pub(crate) fn verify_checksum(
+    path: &Path,
+    actual: &str,
+    expected: Option<&str>,
+    require: bool,
+    progress: &dyn ProgressReporter,
+) -> Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let Some(expected) = expected else {
+        if require {
+            return Err(anyhow!(
+                "the version list has no checksum for {} and require_checksums is set",
+                name
+            ));
+        }
+        progress.warning(&format!("no checksum for {}, skipping verification", name));
+        return Ok(());
+    };
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        fs::remove_file(path)?;
+        return Err(ChecksumMismatch {
+            name: name.into_owned(),
+            expected: expected.trim().to_string(),
+            actual: actual.to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// A download that doesn't match the checksum it should have.
+#[derive(Debug)]
+pub(crate) struct ChecksumMismatch {
+    name: String,
+    expected: String,
+    actual: String,
+}
+
+impl std::fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "checksum mismatch for {}: expected SHA-512 {}, got {}",
+            self.name, self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for ChecksumMismatch {}
+
+/// The digest of `filename` from the `SHA512-SUMS.txt` upstream publishes
+/// next to the archive at `url`. Any failure to get it is only a warning to
+/// `progress`.
+pub(crate) async fn upstream_checksum(
+    client: &Client,
+    url: &str,
+    filename: &str,
+    progress: &dyn ProgressReporter,
+) -> Option<String> {
+    let sums_url = format!("{}/SHA512-SUMS.txt", url.rsplit_once('/')?.0);
+    let result: Result<Option<String>> = async {
+        let text = send_traced(client.get(&sums_url))
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        Ok(parse_checksums(&text).remove(filename))
+    }
+    .await;
+    match result {
+        Ok(Some(sha512)) => Some(sha512),
+        Ok(None) => {
+            progress.warning(&format!("{} has no checksum for {}", sums_url, filename));
+            None
+        }
+        Err(err) => {
+            progress.warning(&format!("could not fetch {}: {:#}", sums_url, err));
+            None
+        }
+    }
+}
+
+/// Reads `sha512sum` output into a map from file name to digest. Both the
+/// text (`<digest>  <name>`) and binary (`<digest> *<name>`) forms are
+/// accepted; anything else is skipped.
+fn parse_checksums(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .filter_map(|line| {
+            let (digest, name) = line.trim().split_once(' ')?;
+            let name = name.trim_start_matches(' ').trim_start_matches('*');
+            let valid = digest.len() == 128 && digest.chars().all(|c| c.is_ascii_hexdigit());
+            (valid && !name.is_empty()).then(|| (name.to_string(), digest.to_ascii_lowercase()))
+        })
+        .collect()
+}
+
+/// Lowercase hex form of a digest.
+pub(crate) fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The hasher state after the first `len` bytes of `path`, read on a
+/// blocking thread.
+pub(crate) async fn hash_prefix(
+    path: &Path,
+    len: u64,
+    progress: &Arc<dyn ProgressReporter>,
+) -> Result<Sha512> {
+    let path = path.to_path_buf();
+    let progress = progress.clone();
+    tokio::task::spawn_blocking(move || {
+        progress.phase_changed(Phase::Checksumming);
+        progress.started(Some(len));
+        let mut hasher = Sha512::new();
+        let mut file = fs::File::open(path)?.take(len);
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let read = file.read(&mut buf)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buf[..read]);
+            progress.advanced(read as u64);
+        }
+        progress.finished();
+        Ok(hasher)
+    })
+    .await?
+}
+
+/// Downloads `url` to `path`, resuming a partial download left there by an
+/// earlier attempt. Resuming is conditional on the ETag or Last-Modified date
+/// recorded alongside it, so a file that changed on the server is downloaded
+/// from scratch, as is everything from a server that ignores the range.
+/// Fails with [`Stalled`] once nothing arrives for `stall_timeout`.
+///
+/// Returns the SHA-512 of the whole file, hashed as it is written.
+pub(crate) async fn download_from_url(
+    client: &Client,
+    url: &str,
+    path: &Path,
+    stall_timeout: Duration,
+    limit: &RateLimit,
+    cancel: &CancellationToken,
+    progress: &Arc<dyn ProgressReporter>,
+) -> Result<String> {
+    progress.message(&format!("Downloading {} to {:?}...", url, path));
+    let etag_path = etag_path(path);
+    let existing = tokio::fs::metadata(path)
+        .await
+        .map(|m| m.len())
+        .unwrap_or(0);
+    let mut request = client.get(url);
+    if existing > 0 {
+        request = request.header(header::RANGE, format!("bytes={}-", existing));
+        if let Ok(validator) = tokio::fs::read_to_string(&etag_path).await {
+            request = request.header(header::IF_RANGE, validator);
+        }
+    }
+    let mut source = send_download(request, url, stall_timeout, cancel).await?;
+    if source.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        source = send_download(client.get(url), url, stall_timeout, cancel).await?;
+    }
+    if source.status().is_client_error() || source.status().is_server_error() {
+        return Err(http_error(url, &source));
+    }
+    let resume = source.status() == StatusCode::PARTIAL_CONTENT;
+    match range_validator(&source) {
+        Some(validator) => tokio::fs::write(&etag_path, validator).await?,
+        None => {
+            let _ = tokio::fs::remove_file(&etag_path).await;
+        }
+    }
+
+    let offset = if resume { existing } else { 0 };
+    let mut hasher = if resume {
+        hash_prefix(path, offset, progress).await?
+    } else {
+        Sha512::new()
+    };
+    let expected = source.content_length().map(|len| offset + len);
+    progress.phase_changed(Phase::Downloading);
+    progress.started(expected);
+    progress.resumed(offset);
+    let mut dest = tokio::io::BufWriter::new(
+        tokio::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resume)
+            .truncate(!resume)
+            .open(path)
+            .await?,
+    );
+    let mut written = offset;
+    let received: Result<()> = async {
+        while let Some(chunk) = next_chunk(&mut source, url, stall_timeout, cancel).await? {
+            dest.write_all(&chunk).await?;
+            limit.take(chunk.len()).await;
+            hasher.update(&chunk);
+            written += chunk.len() as u64;
+            progress.advanced(chunk.len() as u64);
+        }
+        Ok(())
+    }
+    .await;
+    // The partial file is kept either way, so the next attempt resumes.
+    dest.flush().await?;
+    dest.get_ref().sync_data().await?;
+    match expected {
+        Some(expected) if written != expected => {
+            let err = CutShort {
+                url: url.to_string(),
+                written,
+                expected,
+            };
+            return Err(match received {
+                Err(cause) => cause.context(err),
+                Ok(()) => err.into(),
+            });
+        }
+        Some(_) => received?,
+        None => {
+            received?;
+            check_archive(path, None)?;
+        }
+    }
+    progress.finished();
+    progress.message("Completed!");
+    Ok(hex(&hasher.finalize()))
+}
+
+/// Where to download the archive `filename`. Each archive gets its own
+/// directory, keyed by its checksum when known so another run can resume it,
+/// and by this process otherwise so concurrent runs never share a file.
+pub(crate) fn download_path(dir: &Path, filename: &str, sha512: Option<&str>) -> PathBuf {
+    let key = match sha512 {
+        Some(sha512) => sha512[..sha512.len().min(16)].to_ascii_lowercase(),
+        None => format!("pid-{}", process::id()),
+    };
+    dir.join(key).join(filename)
+}
+
+/// Where a download to `path` is written until it is complete and verified.
+pub(crate) fn part_path(path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.part", path.display()))
+}
+
+/// Moves a complete, verified download from `part` to `path`, only after it
+/// is safely on disk, so a file at `path` is never partial.
+pub(crate) fn finish_download(part: &Path, path: &Path) -> Result<()> {
+    fs::File::open(part)?.sync_all()?;
+    fs::rename(part, path)?;
+    let _ = fs::remove_file(etag_path(part));
+    Ok(())
+}
+
+/// The `.part` files of Godot and export template archives in the
+/// subdirectories of `dir`, see [`download_path`].
+pub(crate) fn partial_downloads_in(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut parts: Vec<_> = cached_archives_in(dir)?
+        .into_iter()
+        .filter(|archive| archive.partial)
+        .map(|archive| archive.path)
+        .collect();
+    parts.sort();
+    Ok(parts)
+}
+
+/// The complete and partial Godot and export template archives in the
+/// subdirectories of `dir`, oldest first.
+pub(crate) fn cached_archives_in(dir: &Path) -> Result<Vec<CachedArchive>> {
+    let mut archives = Vec::new();
+    if !dir.exists() {
+        return Ok(archives);
+    }
+    for entry in fs::read_dir(dir)? {
+        let sub = entry?.path();
+        if !sub.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&sub)? {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let (name, partial) = match name.strip_suffix(".part") {
+                Some(name) => (name, true),
+                None => (name, false),
+            };
+            let is_archive =
+                godot::Build::from_filename(name).is_some() || templates_version(name).is_some();
+            let metadata = path.metadata()?;
+            if is_archive && metadata.is_file() {
+                archives.push(CachedArchive {
+                    size: metadata.len(),
+                    partial,
+                    modified: metadata.modified()?,
+                    path,
+                });
+            }
+        }
+    }
+    archives.sort_by(|a, b| (a.modified, &a.path).cmp(&(b.modified, &b.path)));
+    Ok(archives)
+}
+
+/// Removes the cached archive at `path` along with what a download keeps
+/// next to it, and its directory once empty.
+pub(crate) fn remove_cached_archive(path: &Path) -> Result<()> {
+    fs::remove_file(path)?;
+    let _ = fs::remove_file(etag_path(path));
+    let _ = fs::remove_file(ranges_path(path));
+    if let Some(dir) = path.parent() {
+        let _ = fs::remove_dir(dir);
+    }
+    Ok(())
+}
+
+/// A token bucket shared by the connections of a download, holding up to a
+/// second's worth of bytes.
+pub(crate) struct RateLimit {
+    /// Bytes per second, 0 for no limit.
+    rate: u64,
+    /// Tokens left, which go negative to make later takers wait, and when.
+    bucket: std::sync::Mutex<Option<(f64, tokio::time::Instant)>>,
+}
+
+impl RateLimit {
+    pub(crate) const fn new(rate: u64) -> Self {
+        Self {
+            rate,
+            bucket: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Waits until `bytes` more fit within the rate.
+    async fn take(&self, bytes: usize) {
+        if self.rate == 0 {
+            return;
+        }
+        let rate = self.rate as f64;
+        let now = tokio::time::Instant::now();
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let (tokens, last) = bucket.unwrap_or((rate, now));
+            let tokens = (tokens + (now - last).as_secs_f64() * rate).min(rate) - bytes as f64;
+            *bucket = Some((tokens, now));
+            Duration::from_secs_f64((-tokens).max(0.0) / rate)
+        };
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Parses a rate like `500K` or `2M` (binary multiples, an optional `B` or
+/// `/s` after the unit) into bytes per second.
+pub fn parse_rate(rate: &str) -> Result<u64> {
+    let lower = rate.trim().to_ascii_lowercase();
+    let trimmed = lower.trim_end_matches("/s").trim_end_matches('b');
+    let (number, multiplier) = match trimmed.char_indices().last() {
+        Some((i, 'k')) => (&trimmed[..i], 1 << 10),
+        Some((i, 'm')) => (&trimmed[..i], 1 << 20),
+        Some((i, 'g')) => (&trimmed[..i], 1 << 30),
+        _ => (trimmed, 1),
+    };
+    let number: f64 = number
+        .trim()
+        .parse()
+        .ok()
+        .filter(|n: &f64| n.is_finite() && *n >= 0.0)
+        .with_context(|| format!("Invalid rate `{}`, expected e.g. 500K or 2M", rate))?;
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Progress of a download split over several connections, kept next to the
+/// file so an interrupted download resumes each range where it stopped.
+#[derive(Serialize, Deserialize)]
+struct RangeState {
+    total: u64,
+    etag: Option<String>,
+    /// Start and (exclusive) end of each range.
+    ranges: Vec<(u64, u64)>,
+    /// Bytes downloaded of each range.
+    done: Vec<u64>,
+}
+
+pub(crate) fn ranges_path(path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.ranges", path.display()))
+}
+
+/// Splits `total` bytes into at most `parts` ranges of about the same size.
+fn split_ranges(total: u64, parts: u32) -> Vec<(u64, u64)> {
+    let size = total.div_ceil(u64::from(parts.max(1))).max(1);
+    (0..total)
+        .step_by(size as usize)
+        .map(|start| (start, (start + size).min(total)))
+        .collect()
+}
+
+/// The full size from a `Content-Range: bytes 0-0/<size>` header.
+fn content_range_total(response: &reqwest::Response) -> Option<u64> {
+    let range = response
+        .headers()
+        .get(header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?;
+    range
+        .strip_prefix("bytes ")?
+        .rsplit_once('/')?
+        .1
+        .parse()
+        .ok()
+}
+
+/// Downloads `url` to `path` over `connections` connections at once, each
+/// fetching its own range into place in a preallocated file. Falls back to
+/// [`download_from_url`] when the server doesn't support ranges.
+///
+/// Returns the SHA-512 of the file, which has to be hashed once complete
+/// since the ranges arrive out of order.
+#[allow(clippy::too_many_arguments)]
+async fn download_in_ranges(
+    client: &Client,
+    url: &str,
+    path: &Path,
+    connections: u32,
+    stall_timeout: Duration,
+    limit: Arc<RateLimit>,
+    cancel: &CancellationToken,
+    progress: &Arc<dyn ProgressReporter>,
+) -> Result<String> {
+    let probe = client.get(url).header(header::RANGE, "bytes=0-0");
+    let probe = send_download(probe, url, stall_timeout, cancel).await?;
+    let total = match content_range_total(&probe) {
+        Some(total) if probe.status() == StatusCode::PARTIAL_CONTENT && total > 0 => total,
+        _ => {
+            return download_from_url(client, url, path, stall_timeout, &limit, cancel, progress)
+                .await
+        }
+    };
+    let etag = range_validator(&probe);
+    drop(probe);
+    progress.message(&format!(
+        "Downloading {} to {:?} over {} connections...",
+        url, path, connections
+    ));
+
+    let state_path = ranges_path(path);
+    let resumable = |state: &RangeState| {
+        state.total == total
+            && (state.etag.is_none() || state.etag == etag)
+            && path.metadata().is_ok_and(|m| m.len() == total)
+    };
+    let state = match fs::read(&state_path)
+        .ok()
+        .and_then(|state| serde_json::from_slice::<RangeState>(&state).ok())
+    {
+        Some(state) if resumable(&state) => RangeState { etag, ..state },
+        _ => {
+            let file = tokio::fs::File::create(path).await?;
+            file.set_len(total).await?;
+            let ranges = split_ranges(total, connections);
+            RangeState {
+                total,
+                etag,
+                done: vec![0; ranges.len()],
+                ranges,
+            }
+        }
+    };
+
+    progress.phase_changed(Phase::Downloading);
+    progress.started(Some(total));
+    progress.resumed(state.done.iter().sum());
+    let done: Arc<Vec<AtomicU64>> =
+        Arc::new(state.done.iter().map(|&d| AtomicU64::new(d)).collect());
+    let tasks: Vec<_> = state
+        .ranges
+        .iter()
+        .enumerate()
+        .filter(|&(i, &(start, end))| start + state.done[i] < end)
+        .map(|(i, &range)| {
+            let client = client.clone();
+            let url = url.to_string();
+            let path = path.to_path_buf();
+            let etag = state.etag.clone();
+            let done = done.clone();
+            let progress = progress.clone();
+            let limit = limit.clone();
+            let cancel = cancel.clone();
+            tokio::spawn(async move {
+                download_range(
+                    &client,
+                    &url,
+                    &path,
+                    range,
+                    etag,
+                    stall_timeout,
+                    &done[i],
+                    &*progress,
+                    &limit,
+                    &cancel,
+                )
+                .await
+            })
+        })
+        .collect();
+    // Wait for all of them, so the saved progress is final.
+    let mut result = Ok(());
+    for task in tasks {
+        let range_result = task.await?;
+        if result.is_ok() {
+            result = range_result;
+        }
+    }
+    if let Err(err) = result {
+        let state = RangeState {
+            done: done
+                .iter()
+                .map(|d| d.load(AtomicOrdering::SeqCst))
+                .collect(),
+            ..state
+        };
+        fs::write(&state_path, serde_json::to_vec(&state)?)?;
+        return Err(err);
+    }
+    let _ = fs::remove_file(&state_path);
+    progress.finished();
+    let hasher = hash_prefix(path, total, progress).await?;
+    progress.message("Completed!");
+    Ok(hex(&hasher.finalize()))
+}
+
+/// Downloads what is missing of the range `start..end` of `url` into place
+/// in `path`, counting the bytes written in `done`.
+#[allow(clippy::too_many_arguments)]
+async fn download_range(
+    client: &Client,
+    url: &str,
+    path: &Path,
+    (start, end): (u64, u64),
+    etag: Option<String>,
+    stall_timeout: Duration,
+    done: &AtomicU64,
+    progress: &dyn ProgressReporter,
+    limit: &RateLimit,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let from = start + done.load(AtomicOrdering::SeqCst);
+    let mut request = client
+        .get(url)
+        .header(header::RANGE, format!("bytes={}-{}", from, end - 1));
+    if let Some(etag) = etag {
+        request = request.header(header::IF_RANGE, etag);
+    }
+    let mut response = send_download(request, url, stall_timeout, cancel).await?;
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        if response.status().is_success() {
+            return Err(anyhow!(
+                "{} changed on the server during the download; try again to start over",
+                url
+            ));
+        }
+        return Err(http_error(url, &response));
+    }
+    let mut file = tokio::fs::OpenOptions::new().write(true).open(path).await?;
+    file.seek(io::SeekFrom::Start(from)).await?;
+    let mut file = tokio::io::BufWriter::new(file);
+    let mut pos = from;
+    let received: Result<()> = async {
+        while pos < end {
+            let Some(chunk) = next_chunk(&mut response, url, stall_timeout, cancel).await? else {
+                break;
+            };
+            let chunk = &chunk[..chunk.len().min((end - pos) as usize)];
+            limit.take(chunk.len()).await;
+            file.write_all(chunk).await?;
+            pos += chunk.len() as u64;
+            progress.advanced(chunk.len() as u64);
+        }
+        Ok(())
+    }
+    .await;
+    // Only what made it to disk counts when resuming.
+    file.flush().await?;
+    file.get_ref().sync_data().await?;
+    done.fetch_add(pos - from, AtomicOrdering::SeqCst);
+    received?;
+    if pos < end {
+        return Err(CutShort {
+            url: url.to_string(),
+            written: pos - start,
+            expected: end - start,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// The next chunk of a download's body, failing if none arrives within
+/// `stall_timeout` or `cancel` is cancelled meanwhile.
+async fn next_chunk(
+    response: &mut reqwest::Response,
+    url: &str,
+    stall_timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<Option<bytes::Bytes>> {
+    tokio::select! {
+        biased;
+        _ = cancel.cancelled() => Err(Cancelled.into()),
+        chunk = tokio::time::timeout(stall_timeout, response.chunk()) => {
+            Ok(chunk.map_err(|_| stalled(url, stall_timeout))??)
+        }
+    }
+}
+
+/// Where [`download_from_url`] keeps the [`range_validator`] of a partial
+/// download, so resuming it in another process is just as safe.
+pub(crate) fn etag_path(path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.etag", path.display()))
+}
+
+/// What an `If-Range` can make sure the rest of `response` will match: its
+/// strong ETag, or else its `Last-Modified` date.
+fn range_validator(response: &reqwest::Response) -> Option<String> {
+    let headers = response.headers();
+    let value = |name| Some(headers.get(name)?.to_str().ok()?.to_string());
+    value(header::ETAG)
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| value(header::LAST_MODIFIED))
+}
+
+/// Sends `request`, logging it and the response at debug level. Only
+/// headers that help debugging downloads are logged, never credentials.
+pub(crate) async fn send_traced(
+    request: reqwest::RequestBuilder,
+) -> reqwest::Result<reqwest::Response> {
+    let (client, request) = request.build_split();
+    let request = request?;
+    let method = request.method().clone();
+    let url = request.url().clone();
+    let range = request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|range| range.to_str().ok())
+        .map(String::from);
+    tracing::debug!(%method, %url, range, "request");
+    let started = Instant::now();
+    let result = client.execute(request).await;
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+    match &result {
+        Ok(response) => {
+            let headers = response.headers();
+            let value = |name| headers.get(name).and_then(|value| value.to_str().ok());
+            tracing::debug!(
+                %method,
+                url = %response.url(),
+                status = response.status().as_u16(),
+                content_length = value(header::CONTENT_LENGTH),
+                content_range = value(header::CONTENT_RANGE),
+                etag = value(header::ETAG),
+                elapsed_ms,
+                "response"
+            );
+        }
+        Err(err) => tracing::debug!(%method, %url, error = %err, elapsed_ms, "request failed"),
+    }
+    result
+}
+
+/// Sends a download request, telling a server that can't be reached apart
+/// from one that accepts the connection and then never answers.
+async fn send_download(
+    request: reqwest::RequestBuilder,
+    url: &str,
+    stall_timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<reqwest::Response> {
+    let sent = tokio::select! {
+        biased;
+        _ = cancel.cancelled() => return Err(Cancelled.into()),
+        sent = tokio::time::timeout(stall_timeout, send_traced(request)) => sent,
+    };
+    match sent {
+        Err(_) => Err(stalled(url, stall_timeout)),
+        Ok(Err(err)) if err.is_connect() => {
+            Err(anyhow::Error::new(err).context(format!("Couldn't connect to {}", url)))
+        }
+        Ok(response) => Ok(response?),
+    }
+}
+
+pub(crate) fn stalled(url: &str, timeout: Duration) -> anyhow::Error {
+    Stalled {
+        url: url.to_string(),
+        timeout,
+    }
+    .into()
+}
+
+/// Downloads of one install that run at the same time.
+pub(crate) const MAX_CONCURRENT_DOWNLOADS: usize = 2;
+
+/// Delay before the second download attempt; it doubles for each one after.
+pub(crate) const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// How [`download_with_retries`] downloads.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DownloadSettings {
+    pub(crate) attempts: u32,
+    /// Delay before the first retry.
+    pub(crate) retry_delay: Duration,
+    pub(crate) stall_timeout: Duration,
+    /// More than one downloads with [`download_in_ranges`].
+    pub(crate) connections: u32,
+    /// Bytes per second across all connections, 0 for no limit.
+    pub(crate) max_rate: u64,
+}
+
+/// [`download_from_url`] with up to `attempts` tries. Connection problems,
+/// timeouts, stalls, cut-off bodies, 5xx and 429 responses are retried after an
+/// exponential backoff with jitter, or the server's `Retry-After`. Each
+/// retry resumes from what the previous one wrote. Returns the SHA-512 of
+/// the download.
+pub(crate) async fn download_with_retries(
+    client: &Client,
+    url: &str,
+    path: &Path,
+    settings: DownloadSettings,
+    cancel: &CancellationToken,
+    progress: &Arc<dyn ProgressReporter>,
+) -> Result<String> {
+    let DownloadSettings {
+        attempts,
+        retry_delay: base_delay,
+        stall_timeout,
+        connections,
+        max_rate,
+    } = settings;
+    let limit = Arc::new(RateLimit::new(max_rate));
+    let mut attempt = 1;
+    loop {
+        let result = if connections > 1 {
+            download_in_ranges(
+                client,
+                url,
+                path,
+                connections,
+                stall_timeout,
+                limit.clone(),
+                cancel,
+                progress,
+            )
+            .await
+        } else {
+            download_from_url(client, url, path, stall_timeout, &limit, cancel, progress).await
+        };
+        let err = match result {
+            Ok(sha512) => return Ok(sha512),
+            Err(err) => err,
+        };
+        let delay = match retry_delay(&err) {
+            Some(retry_after) if attempt < attempts => {
+                retry_after.unwrap_or_else(|| backoff(base_delay, attempt))
+            }
+            _ => return Err(err),
+        };
+        attempt += 1;
+        tracing::debug!(
+            url,
+            attempt,
+            attempts,
+            delay_ms = delay.as_millis() as u64,
+            error = %format!("{:#}", err),
+            "retrying download"
+        );
+        progress.message(&format!("{:#}", err));
+        progress.phase_changed(Phase::Retrying { attempt, attempts });
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = cancel.cancelled() => return Err(Cancelled.into()),
+        }
+    }
+}
+
+/// The URLs `url` can be downloaded from, in the order of `mirrors`. A
+/// relative URL is resolved against every mirror and a URL on one of them is
+/// rewritten for the others; any other URL has no alternatives.
+pub(crate) fn mirror_urls(url: &str, mirrors: &[String]) -> Vec<String> {
+    let path = if url.contains("://") {
+        match mirrors
+            .iter()
+            .find_map(|mirror| url.strip_prefix(mirror.trim_end_matches('/')))
+        {
+            Some(path) => path,
+            None => return vec![url.to_string()],
+        }
+    } else {
+        url
+    };
+    mirrors
+        .iter()
+        .map(|mirror| {
+            format!(
+                "{}/{}",
+                mirror.trim_end_matches('/'),
+                path.trim_start_matches('/')
+            )
+        })
+        .collect()
+}
+
+/// Downloads from the first of `urls` that works, moving on to the next
+/// once [`download_with_retries`] gives up on one. The next mirror resumes
+/// from whatever the previous ones fetched; the archives are the same
+/// everywhere, and the checksum catches it if they aren't. Returns the URL
+/// that finished the download and the download's SHA-512.
+pub(crate) async fn download_from_mirrors(
+    client: &Client,
+    urls: &[String],
+    path: &Path,
+    settings: DownloadSettings,
+    cancel: &CancellationToken,
+    progress: &Arc<dyn ProgressReporter>,
+) -> Result<(String, String)> {
+    let mut urls = urls.iter().peekable();
+    while let Some(url) = urls.next() {
+        let err = match download_with_retries(client, url, path, settings, cancel, progress).await {
+            Ok(sha512) => return Ok((url.clone(), sha512)),
+            Err(err) => err,
+        };
+        match urls.peek() {
+            Some(next) if !is_cancelled(&err) => {
+                tracing::debug!(from = %url, to = %next, "switching mirror");
+                progress.message(&format!("{:#}\nSwitching to {}", err, next));
+                // ETags differ between servers, so resume without one.
+                let _ = fs::remove_file(etag_path(path));
+                let ranges = ranges_path(path);
+                if let Some(state) = fs::read(&ranges)
+                    .ok()
+                    .and_then(|state| serde_json::from_slice::<RangeState>(&state).ok())
+                {
+                    let state = RangeState {
+                        etag: None,
+                        ..state
+                    };
+                    fs::write(&ranges, serde_json::to_vec(&state)?)?;
+                }
+            }
+            _ => return Err(err),
+        }
+    }
+    Err(anyhow!("No URL to download from"))
+}
+
+/// How much of an artifact [`probe_mirror`] downloads.
+const MIRROR_PROBE_BYTES: u64 = 1024 * 1024;
+
+/// Times a ranged download of the first [`MIRROR_PROBE_BYTES`] of `url`.
+pub(crate) async fn probe_mirror(
+    client: &Client,
+    url: &str,
+    stall_timeout: Duration,
+) -> Result<MirrorSpeed> {
+    let start = Instant::now();
+    let request = client
+        .get(url)
+        .header(header::RANGE, format!("bytes=0-{}", MIRROR_PROBE_BYTES - 1));
+    let never = CancellationToken::new();
+    let mut response = send_download(request, url, stall_timeout, &never)
+        .await?
+        .error_for_status()?;
+    let latency = start.elapsed();
+    let mut bytes = 0;
+    while bytes < MIRROR_PROBE_BYTES {
+        match next_chunk(&mut response, url, stall_timeout, &never).await? {
+            Some(chunk) => bytes += chunk.len() as u64,
+            None => break,
+        }
+    }
+    Ok(MirrorSpeed {
+        latency,
+        bytes,
+        elapsed: start.elapsed(),
+    })
+}
+
+/// Whether a failed download is worth another try, and if so, how long the
+/// server asked us to wait.
+pub(crate) fn retry_delay(err: &anyhow::Error) -> Option<Option<Duration>> {
+    if proxy_rejected(err) || is_cancelled(err) {
+        return None;
+    }
+    if let Some(err) = err.downcast_ref::<HttpError>() {
+        let transient = err.status.is_server_error() || err.status == StatusCode::TOO_MANY_REQUESTS;
+        return transient.then_some(err.retry_after);
+    }
+    let transient = err.chain().any(|cause| {
+        cause.is::<CutShort>()
+            || cause.is::<Stalled>()
+            || cause
+                .downcast_ref::<reqwest::Error>()
+                .is_some_and(|e| e.is_connect() || e.is_timeout() || e.is_body() || e.is_request())
+    });
+    transient.then_some(None)
+}
+
+/// `base` doubled for every attempt after the first, capped at a minute,
+/// plus up to half of that again as jitter.
+fn backoff(base: Duration, attempt: u32) -> Duration {
+    let delay = base
+        .saturating_mul(1 << (attempt - 1).min(16))
+        .min(Duration::from_secs(60));
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    delay + delay.mul_f64(f64::from(nanos % 1000) / 2000.0)
+}
+
+/// A download that received nothing for longer than the stall timeout.
+#[derive(Debug)]
+pub(crate) struct Stalled {
+    pub(crate) url: String,
+    pub(crate) timeout: Duration,
+}
+
+impl std::fmt::Display for Stalled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "download of {} stalled: no data received for {:?}; if IPv6 is broken on this network, try --ipv4",
+            self.url, self.timeout
+        )
+    }
+}
+
+impl std::error::Error for Stalled {}
+
+/// A download whose body ended before its `Content-Length`.
+#[derive(Debug)]
+struct CutShort {
+    url: String,
+    written: u64,
+    expected: u64,
+}
+
+impl std::fmt::Display for CutShort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "download of {} stopped after {} of {} bytes; try again to resume it",
+            self.url, self.written, self.expected
+        )
+    }
+}
+
+impl std::error::Error for CutShort {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    use crate::extract::unzip;
+    use crate::network::explain_error;
+    use crate::progress::NoProgress;
+    use crate::test_util::{
+        no_progress, serve, serve_ranges, serve_with, synthetic_tar, synthetic_zip, test_client,
+        test_dir, RecordedProgress, STALL, UNLIMITED,
+    };
+
+    /// Download settings that give up after one try on one connection.
+    const SINGLE_TRY: DownloadSettings = DownloadSettings {
+        attempts: 1,
+        retry_delay: Duration::ZERO,
+        stall_timeout: STALL,
+        connections: 1,
+        max_rate: 0,
+    };
+
+    #[tokio::test]
+    async fn test_upstream_checksum() {
+        let a = "a".repeat(128);
+        let b = "B".repeat(128);
+        let sums = format!(
+            "{}  Godot_v4.2.1-stable_linux.x86_64.zip\n{} *Godot_v4.2.1-stable_win64.exe.zip\nnot a checksum line\n{} \n",
+            a, b, a
+        );
+        let parsed = parse_checksums(&sums);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed["Godot_v4.2.1-stable_linux.x86_64.zip"], a);
+        assert_eq!(
+            parsed["Godot_v4.2.1-stable_win64.exe.zip"],
+            b.to_lowercase()
+        );
+
+        let sums: &'static str = sums.leak();
+        let url = serve_with(move |head| {
+            let (status, body) = if head.starts_with("GET /SHA512-SUMS.txt ") {
+                ("200 OK", sums)
+            } else {
+                ("404 Not Found", "")
+            };
+            format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+        });
+        let archive = url.replace("versions.yml", "Godot_v4.2.1-stable_linux.x86_64.zip");
+        let progress = RecordedProgress::default();
+        assert_eq!(
+            upstream_checksum(
+                &test_client(),
+                &archive,
+                "Godot_v4.2.1-stable_linux.x86_64.zip",
+                &progress
+            )
+            .await,
+            Some(a)
+        );
+        assert!(progress.take().is_empty());
+        assert_eq!(
+            upstream_checksum(
+                &test_client(),
+                &archive,
+                "Godot_v4.2.1-stable_macos.universal.zip",
+                &progress
+            )
+            .await,
+            None
+        );
+        let warnings = progress.take();
+        assert_eq!(warnings.len(), 1);
+        assert!(
+            warnings[0].ends_with("has no checksum for Godot_v4.2.1-stable_macos.universal.zip")
+        );
+        let missing = serve("404 Not Found", "");
+        assert_eq!(
+            upstream_checksum(&test_client(), &missing, "versions.yml", &progress).await,
+            None
+        );
+        assert!(progress.take()[0].starts_with("warning could not fetch"));
+    }
+
+    #[tokio::test]
+    async fn test_download_resume() {
+        const BODY: &str = "0123456789";
+        // Serves `body` tagged `etag`, honouring ranges the way a real server
+        // would, or ignoring them if `ranges` is off.
+        let server = |etag: &'static str, body: &'static str, ranges: bool| {
+            serve_with(move |head| {
+                let head = head.to_ascii_lowercase();
+                let header = |name: &str| {
+                    head.lines()
+                        .find_map(|l| l.strip_prefix(name))
+                        .map(|v| v.trim().to_string())
+                };
+                let range = header("range: bytes=")
+                    .and_then(|r| r.trim_end_matches('-').parse::<usize>().ok());
+                let fresh = header("if-range:").is_none_or(|v| v == etag.to_ascii_lowercase());
+                let (status, body) = match range {
+                    Some(start) if ranges && fresh && start >= body.len() => {
+                        ("416 Range Not Satisfiable", "")
+                    }
+                    Some(start) if ranges && fresh => ("206 Partial Content", &body[start..]),
+                    _ => ("200 OK", body),
+                };
+                format!(
+                    "HTTP/1.1 {}\r\nETag: {}\r\nContent-Length: {}\r\n\r\n{}",
+                    status,
+                    etag,
+                    body.len(),
+                    body
+                )
+            })
+        };
+        let tmp = test_dir("download-resume");
+        let dir = tmp.path();
+        let path = dir.join("godot.zip");
+        let etag = dir.join("godot.zip.etag");
+
+        // Fresh download records the ETag.
+        download_from_url(
+            &test_client(),
+            &server("\"v1\"", BODY, true),
+            &path,
+            STALL,
+            &UNLIMITED,
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), BODY);
+        assert_eq!(fs::read_to_string(&etag).unwrap(), "\"v1\"");
+
+        // Resumed with a 206: the rest is appended exactly once.
+        fs::write(&path, "ABCD").unwrap();
+        let sha512 = download_from_url(
+            &test_client(),
+            &server("\"v1\"", BODY, true),
+            &path,
+            STALL,
+            &UNLIMITED,
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "ABCD456789");
+        assert_eq!(sha512, hex(&Sha512::digest("ABCD456789")));
+
+        // Server ignores the range: start over instead of appending.
+        fs::write(&path, "ABCD").unwrap();
+        download_from_url(
+            &test_client(),
+            &server("\"v1\"", BODY, false),
+            &path,
+            STALL,
+            &UNLIMITED,
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), BODY);
+
+        // The file changed on the server: If-Range makes it send all of it.
+        fs::write(&path, "ABCD").unwrap();
+        download_from_url(
+            &test_client(),
+            &server("\"v2\"", "abcdefghijkl", true),
+            &path,
+            STALL,
+            &UNLIMITED,
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "abcdefghijkl");
+        assert_eq!(fs::read_to_string(&etag).unwrap(), "\"v2\"");
+
+        // Already complete: the range can't be satisfied, fetch it again.
+        download_from_url(
+            &test_client(),
+            &server("\"v2\"", "abcdefghijkl", true),
+            &path,
+            STALL,
+            &UNLIMITED,
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "abcdefghijkl");
+
+        let err = download_from_url(
+            &test_client(),
+            &serve("404 Not Found", ""),
+            &path,
+            STALL,
+            &UNLIMITED,
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().starts_with("Couldn't download URL"));
+    }
+
+    #[tokio::test]
+    async fn test_resume_hashes_prefix() {
+        let tmp = test_dir("resume-hash");
+        let dir = tmp.path();
+        let path = dir.join("godot.zip");
+        // Only on disk: the server sends nothing but the rest.
+        fs::write(&path, "ABCD").unwrap();
+        let url = serve_with(|head| {
+            let (status, body) = match head.to_ascii_lowercase().contains("range: bytes=4-") {
+                true => ("206 Partial Content", "456789"),
+                false => ("200 OK", "0123456789"),
+            };
+            format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+        });
+        let sha512 = download_from_url(
+            &test_client(),
+            &url,
+            &path,
+            STALL,
+            &UNLIMITED,
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "ABCD456789");
+        assert_eq!(sha512, hex(&Sha512::digest("ABCD456789")));
+    }
+
+    #[tokio::test]
+    async fn test_download_retries() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        const BODY: &str = "0123456789";
+        let tmp = test_dir("download-retries");
+        let dir = tmp.path();
+        let path = dir.join("godot.zip");
+        let settings = |attempts| DownloadSettings {
+            attempts,
+            retry_delay: Duration::from_millis(1),
+            stall_timeout: STALL,
+            connections: 1,
+            max_rate: 0,
+        };
+        // Answers the first requests with `failures` in turn, then with BODY.
+        let server = |failures: &'static [&'static str]| {
+            let requests = Arc::new(AtomicUsize::new(0));
+            let count = requests.clone();
+            let url =
+                serve_with(
+                    move |_| match failures.get(count.fetch_add(1, Ordering::SeqCst)) {
+                        Some(failure) => failure.to_string(),
+                        None => format!(
+                            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                            BODY.len(),
+                            BODY
+                        ),
+                    },
+                );
+            (url, requests)
+        };
+        const UNAVAILABLE: &str = "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n";
+        const TOO_MANY: &str =
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 0\r\nContent-Length: 0\r\n\r\n";
+        const CUT_SHORT: &str = "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n0123";
+        const NOT_FOUND: &str = "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n";
+
+        let (url, requests) = server(&[UNAVAILABLE, TOO_MANY, CUT_SHORT]);
+        download_with_retries(
+            &test_client(),
+            &url,
+            &path,
+            settings(5),
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), BODY);
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+
+        // Out of attempts.
+        fs::remove_file(&path).unwrap();
+        let (url, requests) = server(&[UNAVAILABLE, UNAVAILABLE, UNAVAILABLE]);
+        let err = download_with_retries(
+            &test_client(),
+            &url,
+            &path,
+            settings(2),
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            err.to_string().ends_with("503 Service Unavailable"),
+            "{}",
+            err
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        // Not worth retrying.
+        let (url, requests) = server(&[NOT_FOUND]);
+        let err = download_with_retries(
+            &test_client(),
+            &url,
+            &path,
+            settings(5),
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().ends_with("404 Not Found"), "{}", err);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // Nothing listening.
+        let err = download_with_retries(
+            &test_client(),
+            "http://127.0.0.1:1/godot.zip",
+            &path,
+            settings(2),
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap_err();
+        assert!(retry_delay(&err).is_some(), "{:#}", err);
+
+        let second = backoff(Duration::from_secs(1), 2);
+        assert!(second >= Duration::from_secs(2) && second <= Duration::from_secs(3));
+        assert!(backoff(Duration::from_secs(1), 30) <= Duration::from_secs(90));
+    }
+
+    #[test]
+    fn test_mirror_urls() {
+        let mirrors = [
+            String::from("https://downloads.tuxfamily.org/godotengine/"),
+            String::from("https://mirror.example.org/godot"),
+        ];
+        let file = "4.2.1/Godot_v4.2.1-stable_linux.x86_64.zip";
+        let expected = [
+            format!("https://downloads.tuxfamily.org/godotengine/{}", file),
+            format!("https://mirror.example.org/godot/{}", file),
+        ];
+        assert_eq!(mirror_urls(file, &mirrors), expected);
+        assert_eq!(mirror_urls(&expected[1], &mirrors), expected);
+        let github =
+            "https://github.com/godotengine/godot/releases/download/4.2.1-stable/godot.zip";
+        assert_eq!(mirror_urls(github, &mirrors), [github]);
+    }
+
+    #[tokio::test]
+    async fn test_download_from_mirrors() {
+        let tmp = test_dir("download-mirrors");
+        let dir = tmp.path();
+        let path = dir.join("godot.zip");
+        let down = serve("404 Not Found", "");
+        let cut_short = serve_with(|_| {
+            String::from("HTTP/1.1 200 OK\r\nETag: \"a\"\r\nContent-Length: 10\r\n\r\n0123")
+        });
+        // Resumes from the bytes the previous mirror sent, without its ETag.
+        let rest = serve_with(|head| {
+            assert!(head.contains("ange: bytes=4-"), "{}", head);
+            assert!(!head.to_ascii_lowercase().contains("if-range"), "{}", head);
+            String::from("HTTP/1.1 206 Partial Content\r\nContent-Length: 6\r\n\r\n456789")
+        });
+
+        let urls = [down, cut_short, rest];
+        let (url, sha512) = download_from_mirrors(
+            &test_client(),
+            &urls,
+            &path,
+            SINGLE_TRY,
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(url, urls[2]);
+        // The digest covers the bytes from both mirrors.
+        assert_eq!(sha512, hex(&Sha512::digest("0123456789")));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "0123456789");
+
+        fs::remove_file(&path).unwrap();
+        let err = download_from_mirrors(
+            &test_client(),
+            &urls[..1],
+            &path,
+            SINGLE_TRY,
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().ends_with("404 Not Found"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_probe_mirror() {
+        let url = serve_with(|head| {
+            assert!(head.contains("ange: bytes=0-1048575"), "{}", head);
+            String::from("HTTP/1.1 206 Partial Content\r\nContent-Length: 10\r\n\r\n0123456789")
+        });
+        let speed = probe_mirror(&test_client(), &url, STALL).await.unwrap();
+        assert_eq!(speed.bytes, 10);
+        assert!(speed.latency <= speed.elapsed);
+        assert!(speed.throughput() > 0);
+
+        let err = probe_mirror(&test_client(), &serve("404 Not Found", ""), STALL)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("404"), "{}", err);
+        let err = probe_mirror(&test_client(), "http://127.0.0.1:1/godot.zip", STALL)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().starts_with("Couldn't connect"), "{}", err);
+    }
+
+    #[test]
+    fn test_part_files() {
+        let tmp = test_dir("part-files");
+        let dir = tmp.path();
+        fs::create_dir(dir.join("0123456789abcdef")).unwrap();
+        let path = dir
+            .join("0123456789abcdef")
+            .join("Godot_v4.2.1-stable_linux.x86_64.zip");
+        let part = part_path(&path);
+        fs::write(&part, "zip").unwrap();
+        fs::write(etag_path(&part), "\"v1\"").unwrap();
+        fs::write(dir.join("unrelated.part"), "").unwrap();
+        fs::write(dir.join("Godot_v4.2.1-stable_linux.x86_64.zip.part"), "").unwrap();
+        assert_eq!(partial_downloads_in(dir).unwrap(), vec![part.clone()]);
+
+        finish_download(&part, &path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "zip");
+        assert!(!part.exists() && !etag_path(&part).exists());
+        assert!(partial_downloads_in(dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_download_path() {
+        let build = godot::Build {
+            version: godot::Version::parse("4.2.1").unwrap(),
+            platform: godot::Platform::Linux64,
+        };
+        let dir = Path::new("cache");
+        let keyed = download_path(dir, &build.to_filename(), Some("0123456789ABCDEF0123"));
+        assert_eq!(
+            keyed,
+            dir.join("0123456789abcdef/Godot_v4.2.1-stable_linux.x86_64.zip")
+        );
+        let unkeyed = download_path(dir, &build.to_filename(), None);
+        assert_ne!(unkeyed, keyed);
+        assert_eq!(unkeyed.file_name(), keyed.file_name());
+    }
+
+    #[tokio::test]
+    async fn test_download_stale_oversized() {
+        const BODY: &str = "0123456789";
+        let tmp = test_dir("download-oversized");
+        let dir = tmp.path();
+        let path = dir.join("godot.zip");
+        // Left over by an unrelated, larger download: the range starts past the
+        // end, so it has to be replaced instead of appended to.
+        fs::write(&path, "a much longer file from another run").unwrap();
+        let url = serve_with(|head| {
+            if head.to_ascii_lowercase().contains("range: bytes=") {
+                String::from("HTTP/1.1 416 Range Not Satisfiable\r\nContent-Length: 0\r\n\r\n")
+            } else {
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                    BODY.len(),
+                    BODY
+                )
+            }
+        });
+        download_from_url(
+            &test_client(),
+            &url,
+            &path,
+            STALL,
+            &UNLIMITED,
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), BODY);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limit() {
+        use tokio::time::Instant;
+
+        let limit = RateLimit::new(1000);
+        let start = Instant::now();
+        // A second's worth goes through at once, the rest at the rate.
+        limit.take(1000).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        limit.take(500).await;
+        limit.take(500).await;
+        assert_eq!(start.elapsed().as_millis(), 1000);
+
+        let start = Instant::now();
+        RateLimit::new(0).take(usize::MAX).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_parse_rate() {
+        assert_eq!(parse_rate("2M").unwrap(), 2 * 1024 * 1024);
+        assert_eq!(parse_rate("500k").unwrap(), 500 * 1024);
+        assert_eq!(parse_rate("1.5MB/s").unwrap(), 3 * 512 * 1024);
+        assert_eq!(parse_rate("300").unwrap(), 300);
+        assert_eq!(parse_rate("0").unwrap(), 0);
+        assert!(parse_rate("fast").is_err());
+        assert!(parse_rate("-1M").is_err());
+    }
+
+    #[test]
+    fn test_split_ranges() {
+        assert_eq!(split_ranges(10, 4), [(0, 3), (3, 6), (6, 9), (9, 10)]);
+        assert_eq!(split_ranges(3, 4), [(0, 1), (1, 2), (2, 3)]);
+        assert_eq!(split_ranges(10, 1), [(0, 10)]);
+    }
+
+    #[tokio::test]
+    async fn test_download_in_ranges() {
+        use std::sync::Mutex;
+
+        const BODY: &str = "The quick brown fox jumps over the lazy dog, 0123456789";
+        let tmp = test_dir("download-ranges");
+        let dir = tmp.path();
+        let path = dir.join("godot.zip");
+        let sha512 = hex(&Sha512::digest(BODY));
+        let unlimited = Arc::new(RateLimit::new(0));
+
+        let requested = Arc::new(Mutex::new(Vec::new()));
+        let url = serve_ranges(BODY, true, None, requested.clone());
+        assert_eq!(
+            download_in_ranges(
+                &test_client(),
+                &url,
+                &path,
+                4,
+                STALL,
+                unlimited.clone(),
+                &CancellationToken::new(),
+                &no_progress(),
+            )
+            .await
+            .unwrap(),
+            sha512
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), BODY);
+        let mut requested = requested.lock().unwrap().clone();
+        requested.sort();
+        assert_eq!(requested, ["0-0", "0-13", "14-27", "28-41", "42-54"]);
+
+        // One range fails; trying again only fetches that one.
+        let requested = Arc::new(Mutex::new(Vec::new()));
+        let url = serve_ranges(BODY, true, Some(42), requested.clone());
+        let err = download_in_ranges(
+            &test_client(),
+            &url,
+            &path,
+            4,
+            STALL,
+            unlimited.clone(),
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            err.to_string().ends_with("503 Service Unavailable"),
+            "{}",
+            err
+        );
+        assert!(ranges_path(&path).exists());
+        let requested = Arc::new(Mutex::new(Vec::new()));
+        let url = serve_ranges(BODY, true, None, requested.clone());
+        assert_eq!(
+            download_in_ranges(
+                &test_client(),
+                &url,
+                &path,
+                4,
+                STALL,
+                unlimited.clone(),
+                &CancellationToken::new(),
+                &no_progress(),
+            )
+            .await
+            .unwrap(),
+            sha512
+        );
+        assert_eq!(*requested.lock().unwrap(), ["0-0", "42-54"]);
+        assert_eq!(fs::read_to_string(&path).unwrap(), BODY);
+        assert!(!ranges_path(&path).exists());
+
+        // No range support: a single plain download.
+        fs::remove_file(&path).unwrap();
+        let requested = Arc::new(Mutex::new(Vec::new()));
+        let url = serve_ranges(BODY, false, None, requested.clone());
+        assert_eq!(
+            download_in_ranges(
+                &test_client(),
+                &url,
+                &path,
+                4,
+                STALL,
+                unlimited.clone(),
+                &CancellationToken::new(),
+                &no_progress(),
+            )
+            .await
+            .unwrap(),
+            sha512
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), BODY);
+        assert_eq!(requested.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_download_stalled() {
+        use std::net::TcpListener;
+
+        let tmp = test_dir("download-stalled");
+        let dir = tmp.path();
+        let path = dir.join("godot.zip");
+        let timeout = Duration::from_millis(200);
+
+        // No response at all.
+        let url = serve_with(|_| {
+            std::thread::sleep(Duration::from_secs(1));
+            String::from("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+        });
+        let err = download_from_url(
+            &test_client(),
+            &url,
+            &path,
+            timeout,
+            &UNLIMITED,
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("stalled"), "{:#}", err);
+        assert!(retry_delay(&err).is_some());
+
+        // Goes quiet halfway through the body.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/godot.zip", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut head = [0; 1024];
+            let _ = stream.read(&mut head).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n0123")
+                .unwrap();
+            std::thread::sleep(Duration::from_secs(1));
+        });
+        let err = download_from_url(
+            &test_client(),
+            &url,
+            &path,
+            timeout,
+            &UNLIMITED,
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("no data received for 200ms"),
+            "{:#}",
+            err
+        );
+        assert!(retry_delay(&err).is_some());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "0123");
+
+        // A refused connection is reported as such.
+        let err = download_from_url(
+            &test_client(),
+            "http://127.0.0.1:1/godot.zip",
+            &path,
+            timeout,
+            &UNLIMITED,
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().starts_with("Couldn't connect"), "{:#}", err);
+        assert!(!format!("{:#}", err).contains("stalled"));
+    }
+
+    #[tokio::test]
+    async fn test_download_truncated() {
+        let tmp = test_dir("download-truncated");
+        let dir = tmp.path();
+        let path = dir.join("godot.zip");
+
+        // The connection closes after 4 of the 10 promised bytes.
+        let url = serve_with(|_| String::from("HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n0123"));
+        let err = download_from_url(
+            &test_client(),
+            &url,
+            &path,
+            STALL,
+            &UNLIMITED,
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("stopped after 4 of 10 bytes"),
+            "{:#}",
+            err
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "0123");
+
+        // Without a length, the archive itself has to be complete.
+        let url = serve_with(|_| String::from("HTTP/1.1 200 OK\r\n\r\nnot a zip"));
+        let err = download_from_url(
+            &test_client(),
+            &url,
+            &path,
+            STALL,
+            &UNLIMITED,
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("not a complete archive"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_chunked_tarball() {
+        let tmp = test_dir("download-chunked");
+        let dir = tmp.path();
+        let path = dir.join("godot.tar");
+        let (_tmp, tar) = synthetic_tar("download-chunked-tar", ".tar", &["Godot/", "Godot/godot"]);
+        let tar = fs::read_to_string(tar).unwrap();
+
+        // Without a length, only the archive can tell it arrived whole.
+        let body = tar.clone();
+        let url = serve_with(move |_| {
+            format!(
+                "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n0\r\n\r\n",
+                body.len(),
+                body
+            )
+        });
+        let sha512 = download_from_url(
+            &test_client(),
+            &url,
+            &path,
+            STALL,
+            &UNLIMITED,
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(sha512, hex(&Sha512::digest(&tar)));
+        assert_eq!(fs::read_to_string(&path).unwrap(), tar);
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        const GODOT_SHA512: &str = "6e096a822dcb43922a4479f5144a60255b7bdf3cceca1bcdb740773c5927efa553adb0195634797d41d95c13dbded4a124a2e0d742560d2fd65a5bac52852767";
+        let tmp = test_dir("checksum");
+        let dir = tmp.path();
+        let archive = dir.join("Godot_v4.2.1-stable_linux.x86_64.zip");
+        fs::write(&archive, "godot").unwrap();
+        assert_eq!(hex(&Sha512::digest("godot")), GODOT_SHA512);
+
+        let progress = RecordedProgress::default();
+        verify_checksum(&archive, GODOT_SHA512, Some(GODOT_SHA512), true, &progress).unwrap();
+        verify_checksum(
+            &archive,
+            GODOT_SHA512,
+            Some(&GODOT_SHA512.to_uppercase()),
+            true,
+            &progress,
+        )
+        .unwrap();
+        assert!(progress.take().is_empty());
+
+        // No checksum: a warning, or an error if checksums are required.
+        verify_checksum(&archive, GODOT_SHA512, None, false, &progress).unwrap();
+        assert_eq!(
+            progress.take(),
+            ["warning no checksum for Godot_v4.2.1-stable_linux.x86_64.zip, skipping verification"]
+        );
+        let err = verify_checksum(&archive, GODOT_SHA512, None, true, &progress).unwrap_err();
+        assert!(err.to_string().contains("require_checksums"));
+        assert!(archive.exists());
+
+        // A mismatch is an error and the download is discarded.
+        let tampered = hex(&Sha512::digest("godoT"));
+        let err =
+            verify_checksum(&archive, &tampered, Some(GODOT_SHA512), false, &progress).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("checksum mismatch for Godot_v4.2.1-stable_linux.x86_64.zip"));
+        assert!(!archive.exists());
+        assert!(explain_error(err).to_string().contains("--force-download"));
+    }
+
+    #[test]
+    fn cancelled_downloads_are_not_retried() {
+        let err = anyhow::Error::new(Cancelled).context(CutShort {
+            url: String::new(),
+            written: 1,
+            expected: 2,
+        });
+        assert!(is_cancelled(&err));
+        assert!(retry_delay(&err).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_download_cancelled() {
+        use std::net::TcpListener;
+
+        let tmp = test_dir("download-cancelled");
+        let dir = tmp.path();
+        let path = dir.join("godot.zip");
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/godot.zip", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut head = [0; 1024];
+            let _ = stream.read(&mut head).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n0123")
+                .unwrap();
+            std::thread::sleep(Duration::from_secs(2));
+        });
+        let cancel = CancellationToken::new();
+        let later = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            later.cancel();
+        });
+        let err = download_from_url(
+            &test_client(),
+            &url,
+            &path,
+            STALL,
+            &UNLIMITED,
+            &cancel,
+            &no_progress(),
+        )
+        .await
+        .unwrap_err();
+        assert!(is_cancelled(&err), "{:#}", err);
+        // What arrived is kept for resuming.
+        assert_eq!(fs::read_to_string(&path).unwrap(), "0123");
+
+        let (_tmp, zip) = synthetic_zip("cancelled", &["Godot_v4.2.1-stable_linux.x86_64"]);
+        let to = zip.with_file_name("out");
+        let err = unzip(&zip, &to, true, 1, &cancel, &NoProgress).unwrap_err();
+        assert!(is_cancelled(&err));
+        assert!(!to.join("Godot_v4.2.1-stable_linux.x86_64").exists());
+    }
+
+    #[tokio::test]
+    async fn test_download_progress() {
+        let tmp = test_dir("download-progress");
+        let dir = tmp.path();
+        let path = dir.join("godot.zip");
+        let recorded = Arc::new(RecordedProgress::default());
+        let progress: Arc<dyn ProgressReporter> = recorded.clone();
+        let download = |url: String| {
+            let path = path.clone();
+            let progress = progress.clone();
+            async move {
+                let cancel = CancellationToken::new();
+                download_from_url(
+                    &test_client(),
+                    &url,
+                    &path,
+                    STALL,
+                    &UNLIMITED,
+                    &cancel,
+                    &progress,
+                )
+                .await
+            }
+        };
+
+        let url =
+            serve_with(|_| String::from("HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n0123456789"));
+        download(url).await.unwrap();
+        assert_eq!(
+            recorded.take(),
+            ["Downloading", "started Some(10)", "advanced 10", "finished"]
+        );
+
+        // Resuming hashes what's there first, and counts it as downloaded.
+        fs::write(&path, "0123").unwrap();
+        let url = serve_with(|_| {
+            String::from("HTTP/1.1 206 Partial Content\r\nContent-Length: 6\r\n\r\n456789")
+        });
+        download(url).await.unwrap();
+        assert_eq!(
+            recorded.take(),
+            [
+                "Checksumming",
+                "started Some(4)",
+                "advanced 4",
+                "finished",
+                "Downloading",
+                "started Some(10)",
+                "advanced 10",
+                "finished"
+            ]
+        );
+
+        // A failed download isn't finished.
+        download(serve("404 Not Found", "")).await.unwrap_err();
+        assert_eq!(recorded.take(), Vec::<String>::new());
+
+        let (_tmp, zip) = synthetic_zip("progress", &["Godot_v4.2.1-stable_linux.x86_64"]);
+        let to = zip.with_file_name("out");
+        unzip(&zip, &to, true, 1, &CancellationToken::new(), &*recorded).unwrap();
+        assert_eq!(
+            recorded.take(),
+            ["Extracting", "started Some(32)", "advanced 32", "finished"]
+        );
+    }
+}
@@ -0,0 +1,1821 @@
+//! Unpacking archives into an install directory.
+
+use anyhow::anyhow;
+use anyhow::{Context, Result};
+use indicatif::HumanBytes;
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::io::Write;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering as AtomicOrdering, Arc},
+    time::{Duration, Instant, SystemTime},
+};
+use std::{fs, io};
+use tokio_util::sync::CancellationToken;
+
+use crate::download::hex;
+use crate::install::{aside, free_space, manifest_path, sha256_file, ManifestFile};
+use crate::progress::{NoProgress, Phase, ProgressReporter};
+use crate::Cancelled;
+
+/// Extracts `from` into `to`. Mono archives wrap everything in a single
+/// top-level directory (the binary plus `GodotSharp`); with `flatten`, that
+/// directory is stripped so `to` always holds the executable at its top
+/// level. macOS `.app` bundles are kept intact. Files are written by up to `workers`
+/// threads at once. Stops before the next entry once `cancel` is
+/// cancelled.
+///
+/// Zip64 archives are fine. As a guard against decompression bombs, the
+/// uncompressed sizes the archive declares have to fit in the free space
+/// of `to`, and no entry may turn out larger than it declared.
+pub(crate) fn unzip(
+    from: &Path,
+    to: &Path,
+    flatten: bool,
+    workers: usize,
+    cancel: &CancellationToken,
+    progress: &dyn ProgressReporter,
+) -> Result<Vec<ManifestFile>> {
+    let mut archive = open_archive(from)?;
+    let root = flatten.then(|| wrapping_dir(&mut archive)).flatten();
+    progress.phase_changed(Phase::Extracting);
+    let mut total: u64 = 0;
+    for i in 0..archive.len() {
+        let size = archive
+            .by_index_raw(i)
+            .with_context(|| format!("Couldn't read entry {} of {}", i, from.display()))?
+            .size();
+        total = total.saturating_add(size);
+    }
+    if let Some((_, free)) = free_space(to).filter(|&(_, free)| total > free) {
+        return Err(anyhow!(
+            "{} unpacks to {}, but only {} is free in {}",
+            from.display(),
+            HumanBytes(total),
+            HumanBytes(free),
+            to.display()
+        ));
+    }
+    progress.started(Some(total));
+    tracing::debug!(
+        archive = %from.display(),
+        to = %to.display(),
+        entries = archive.len(),
+        bytes = total,
+        workers,
+        "extracting"
+    );
+    let started = Instant::now();
+    fs::create_dir_all(to).with_context(|| format!("Couldn't create {}", to.display()))?;
+    // Whatever the entries say, nothing is written outside of this.
+    let within = to
+        .canonicalize()
+        .with_context(|| format!("Couldn't resolve {}", to.display()))?;
+
+    // Directories and symlinks are made first, in archive order, so the
+    // files can then be written in any order.
+    let mut files: Vec<Option<(usize, PathBuf)>> = Vec::new();
+    let mut file_at: HashMap<PathBuf, usize> = HashMap::new();
+    let mut links: HashMap<PathBuf, String> = HashMap::new();
+    let mut dirs = Vec::new();
+    for i in 0..archive.len() {
+        if cancel.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        let mut file = archive
+            .by_index(i)
+            .with_context(|| format!("Couldn't read entry {} of {}", i, from.display()))?;
+        let name = entry_name(&file);
+        let Some(path) = name.as_deref().and_then(enclosed_path) else {
+            let reason = match &name {
+                Some(_) => format!("which would be extracted outside {}", to.display()),
+                None => "whose name isn't valid UTF-8".to_string(),
+            };
+            let name = name.unwrap_or_else(|| file.name().to_string());
+            tracing::warn!(entry = i, name = %name, reason = %reason, "skipped entry");
+            if looks_executable(&file, &name) {
+                return Err(anyhow!(
+                    "Refusing to skip {} in {}, {}, as it looks like an executable",
+                    name,
+                    from.display(),
+                    reason
+                ));
+            }
+            progress.message(&format!("Skipped {}, {}", name, reason));
+            continue;
+        };
+        let outpath = match &root {
+            Some(root) => match path.strip_prefix(root) {
+                Ok(path) if path.as_os_str().is_empty() => continue,
+                Ok(path) => to.join(path),
+                Err(_) => continue,
+            },
+            None => to.join(path),
+        };
+
+        if !file.comment().is_empty() {
+            tracing::debug!(entry = i, comment = file.comment(), "entry comment");
+        }
+
+        if file
+            .unix_mode()
+            .is_some_and(|mode| mode & S_IFMT == S_IFLNK)
+        {
+            let mut target = String::new();
+            file.read_to_string(&mut target)
+                .with_context(|| format!("Couldn't read entry {} of {}", i, from.display()))?;
+            // Entries later in the archive replace earlier ones.
+            if let Some(earlier) = file_at.remove(&outpath) {
+                files[earlier] = None;
+            }
+            extract_symlink(&outpath, Path::new(&target), &within, progress)?;
+            if outpath.symlink_metadata().is_ok_and(|m| m.is_symlink()) {
+                links.insert(outpath, target);
+            }
+            progress.advanced(file.size());
+            continue;
+        }
+
+        if (*file.name()).ends_with('/') {
+            tracing::trace!(path = %outpath.display(), "extracted directory");
+            fs::create_dir_all(&outpath)
+                .with_context(|| format!("Couldn't create {}", outpath.display()))?;
+            resolve_within(&outpath, &within)?;
+            dirs.push((outpath, file.unix_mode(), entry_modified(&file)));
+        } else {
+            make_room(&outpath, &within)?;
+            if let Some(earlier) = file_at.insert(outpath.clone(), files.len()) {
+                files[earlier] = None;
+            }
+            links.remove(&outpath);
+            files.push(Some((i, outpath)));
+        }
+    }
+    drop(archive);
+
+    // Each worker reads its own handle on the archive.
+    let files: Vec<_> = files.into_iter().flatten().collect();
+    let next = std::sync::atomic::AtomicUsize::new(0);
+    let failed = std::sync::atomic::AtomicBool::new(false);
+    let work = || -> Result<Vec<ManifestFile>> {
+        let mut archive = open_archive(from)?;
+        let mut extracted = Vec::new();
+        while !failed.load(AtomicOrdering::SeqCst) {
+            if cancel.is_cancelled() {
+                return Err(Cancelled.into());
+            }
+            let Some((index, outpath)) = files.get(next.fetch_add(1, AtomicOrdering::SeqCst))
+            else {
+                break;
+            };
+            extracted.push(extract_file(
+                &mut archive,
+                *index,
+                to,
+                outpath,
+                from,
+                progress,
+            )?);
+        }
+        Ok(extracted)
+    };
+    let mut extracted = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..workers.clamp(1, files.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| work().inspect_err(|_| failed.store(true, AtomicOrdering::SeqCst)))
+            })
+            .collect();
+        workers
+            .into_iter()
+            .try_fold(Vec::new(), |mut extracted, worker| {
+                extracted.extend(worker.join().expect("extraction worker panicked")?);
+                Ok::<_, anyhow::Error>(extracted)
+            })
+    })?;
+    extracted.extend(links.into_iter().map(|(path, target)| ManifestFile {
+        path: manifest_path(to, &path),
+        size: target.len() as u64,
+        mode: 0o777,
+        sha256: None,
+        link: Some(target),
+    }));
+
+    finish_dirs(dirs)?;
+    tracing::debug!(
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        "extraction complete"
+    );
+    progress.finished();
+    Ok(extracted)
+}
+
+/// Readies `outpath` for a file entry: its directory is made, and has to be
+/// within `within`, and a link an earlier entry made there is removed
+/// rather than written through.
+fn make_room(outpath: &Path, within: &Path) -> Result<()> {
+    if let Some(p) = outpath.parent() {
+        if !p.exists() {
+            fs::create_dir_all(p).with_context(|| format!("Couldn't create {}", p.display()))?;
+        }
+        resolve_within(p, within)?;
+    }
+    if outpath.symlink_metadata().is_ok_and(|m| m.is_symlink()) {
+        fs::remove_file(outpath)
+            .with_context(|| format!("Couldn't replace {}", outpath.display()))?;
+    }
+    Ok(())
+}
+
+/// Gives the extracted directories `dirs` their modes and modification
+/// times. Only once everything inside is written, deepest first.
+fn finish_dirs(mut dirs: Vec<(PathBuf, Option<u32>, Option<SystemTime>)>) -> Result<()> {
+    dirs.sort_by_key(|(path, _, _)| std::cmp::Reverse(path.components().count()));
+    for (path, mode, modified) in dirs {
+        if let Some(modified) = modified {
+            set_dir_modified(&path, modified).with_context(|| {
+                format!("Couldn't set the modification time of {}", path.display())
+            })?;
+        }
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            use std::os::unix::fs::PermissionsExt;
+
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode & 0o777))
+                .with_context(|| format!("Couldn't set the permissions of {}", path.display()))?;
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+    }
+    Ok(())
+}
+
+/// When `entry` was last modified, unless the archive has no valid time for
+/// it. Zip times have no time zone, so they are taken as UTC.
+fn entry_modified(entry: &zip::read::ZipFile) -> Option<SystemTime> {
+    let time = entry.last_modified().to_time().ok()?;
+    let secs = u64::try_from(time.unix_timestamp()).ok()?;
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Sets the modification time of the directory `path`.
+fn set_dir_modified(path: &Path, modified: SystemTime) -> io::Result<()> {
+    // Windows only opens directories with backup semantics.
+    #[cfg(windows)]
+    let dir = {
+        use std::os::windows::fs::OpenOptionsExt;
+
+        const FILE_WRITE_ATTRIBUTES: u32 = 0x100;
+        const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+        fs::OpenOptions::new()
+            .access_mode(FILE_WRITE_ATTRIBUTES)
+            .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+            .open(path)?
+    };
+    #[cfg(not(windows))]
+    let dir = fs::File::open(path)?;
+    dir.set_modified(modified)
+}
+
+/// The kinds of archive godotup extracts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+    TarXz,
+}
+
+impl ArchiveFormat {
+    /// The kind of archive that begins with `start`, given at least its
+    /// first 512 bytes if it has them.
+    fn detect(start: &[u8]) -> Option<Self> {
+        if start.starts_with(b"PK\x03\x04") || start.starts_with(b"PK\x05\x06") {
+            Some(ArchiveFormat::Zip)
+        } else if start.starts_with(&[0x1f, 0x8b]) {
+            Some(ArchiveFormat::TarGz)
+        } else if start.starts_with(b"\xfd7zXZ\x00") {
+            Some(ArchiveFormat::TarXz)
+        } else if start.get(257..262) == Some(b"ustar") {
+            Some(ArchiveFormat::Tar)
+        } else {
+            None
+        }
+    }
+
+    /// The kind of archive `path` is, by its contents.
+    fn of(path: &Path) -> Result<Option<Self>> {
+        let mut start = Vec::with_capacity(512);
+        fs::File::open(path)
+            .and_then(|file| file.take(512).read_to_end(&mut start))
+            .with_context(|| format!("Couldn't read {}", path.display()))?;
+        Ok(Self::detect(&start))
+    }
+}
+
+/// Checks `path` looks like a whole archive of `size` bytes, if known,
+/// before it is opened: it starts like an archive godotup extracts and, for
+/// zips, still ends with the end of central directory record.
+pub(crate) fn check_archive(path: &Path, size: Option<u64>) -> Result<()> {
+    // The record is 22 bytes and may be followed by a comment of up to 64 KiB.
+    const EOCD_LEN: u64 = 22;
+    const EOCD_SEARCH: u64 = EOCD_LEN + u16::MAX as u64;
+    let corrupt = |reason: String| {
+        anyhow!(
+            "{} is not a complete archive, {}; the download is corrupt, use --force-download to download it again",
+            path.display(),
+            reason
+        )
+    };
+    let mut file =
+        fs::File::open(path).with_context(|| format!("Couldn't open {}", path.display()))?;
+    let len = file.metadata()?.len();
+    let mut start = Vec::with_capacity(512);
+    (&mut file).take(512).read_to_end(&mut start)?;
+    let Some(format) = ArchiveFormat::detect(&start) else {
+        let text = String::from_utf8_lossy(&start[..start.len().min(16)])
+            .trim_start()
+            .to_ascii_lowercase();
+        return Err(corrupt(match len {
+            0 => "it is empty".to_string(),
+            _ if text.starts_with("<!doctype")
+                || text.starts_with("<html")
+                || text.starts_with("<?xml") =>
+            {
+                "it looks like a web page, likely an error from the server".to_string()
+            }
+            _ => "it is neither a zip nor a tarball".to_string(),
+        }));
+    };
+    if let Some(size) = size.filter(|&size| size != len) {
+        return Err(corrupt(format!(
+            "it is {} where {} was expected",
+            HumanBytes(len),
+            HumanBytes(size)
+        )));
+    }
+    if format != ArchiveFormat::Zip {
+        // Tarballs have no index to look for; one cut short fails to extract.
+        return Ok(());
+    }
+    let tail = len.min(EOCD_SEARCH);
+    io::Seek::seek(&mut file, io::SeekFrom::Start(len - tail))?;
+    let mut end = Vec::with_capacity(tail as usize);
+    file.read_to_end(&mut end)?;
+    let found = end
+        .windows(4)
+        .rev()
+        .skip(EOCD_LEN as usize - 4)
+        .any(|window| window == b"PK\x05\x06");
+    if !found {
+        return Err(corrupt(
+            "its end of central directory record is missing, so it was cut short".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn open_archive(path: &Path) -> Result<zip::ZipArchive<fs::File>> {
+    let file = fs::File::open(path).with_context(|| format!("Couldn't open {}", path.display()))?;
+    zip::ZipArchive::new(file)
+        .with_context(|| format!("{} is not a valid zip archive", path.display()))
+}
+
+/// Writes the file entry `index` of the archive `from` to `outpath` and
+/// gives it the entry's modification time and permissions.
+fn extract_file(
+    archive: &mut zip::ZipArchive<fs::File>,
+    index: usize,
+    to: &Path,
+    outpath: &Path,
+    from: &Path,
+    progress: &dyn ProgressReporter,
+) -> Result<ManifestFile> {
+    let mut file = archive
+        .by_index(index)
+        .with_context(|| format!("Couldn't read entry {} of {}", index, from.display()))?;
+    let declared = file.size();
+    let extracted = fs::File::create(outpath).and_then(|outfile| {
+        let mut outfile = ReportingWriter {
+            inner: outfile,
+            progress,
+            hasher: Sha256::new(),
+        };
+        io::copy(&mut (&mut file).take(declared), &mut outfile)?;
+        Ok((outfile.inner, outfile.hasher.finalize()))
+    });
+    let (outfile, sha256) = extracted
+        .with_context(|| format!("Couldn't extract {} to {}", file.name(), outpath.display()))?;
+    if file.read(&mut [0])? > 0 {
+        return Err(anyhow!(
+            "{} in {} is larger than the {} it declares",
+            file.name(),
+            from.display(),
+            HumanBytes(declared)
+        ));
+    }
+    if let Some(modified) = entry_modified(&file) {
+        outfile.set_modified(modified).with_context(|| {
+            format!(
+                "Couldn't set the modification time of {}",
+                outpath.display()
+            )
+        })?;
+    }
+    tracing::trace!(path = %outpath.display(), bytes = declared, "extracted");
+    // Archives made on Windows may not say; the binary is made executable
+    // by `ensure_executable` once everything is extracted. Only the
+    // permission bits are kept: setuid, setgid and sticky bits from an
+    // archive aren't trusted.
+    let mode = file.unix_mode().map_or(0o644, |mode| mode & 0o777);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(outpath, fs::Permissions::from_mode(mode))
+            .with_context(|| format!("Couldn't set the permissions of {}", outpath.display()))?;
+    }
+    Ok(ManifestFile {
+        path: manifest_path(to, outpath),
+        size: declared,
+        mode,
+        sha256: Some(hex(&sha256)),
+        link: None,
+    })
+}
+
+/// Reports the bytes written through it as progress, and hashes them.
+struct ReportingWriter<'a, W> {
+    inner: W,
+    progress: &'a dyn ProgressReporter,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for ReportingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.progress.advanced(written as u64);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reports the bytes read through it as progress.
+struct ReportingReader<'a, R> {
+    inner: R,
+    progress: &'a dyn ProgressReporter,
+}
+
+impl<R: Read> Read for ReportingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.progress.advanced(read as u64);
+        Ok(read)
+    }
+}
+
+/// The file type bits of a unix mode, and their value for symlinks.
+const S_IFMT: u32 = 0o170000;
+
+const S_IFLNK: u32 = 0o120000;
+
+/// `path` with every symlink resolved, which has to be within the resolved
+/// `within`, so extraction never writes through a link to elsewhere.
+fn resolve_within(path: &Path, within: &Path) -> Result<PathBuf> {
+    let resolved = path
+        .canonicalize()
+        .with_context(|| format!("Couldn't resolve {}", path.display()))?;
+    if !resolved.starts_with(within) {
+        return Err(anyhow!(
+            "Refusing to extract to {}, which leads outside {} to {}",
+            path.display(),
+            within.display(),
+            resolved.display()
+        ));
+    }
+    Ok(resolved)
+}
+
+/// Whether a symlink to `target` in the resolved directory `dir` points
+/// within `within`. Only relative targets whose `..` all come first
+/// qualify, so that following the rest never climbs through another link.
+fn link_within(dir: &Path, target: &Path, within: &Path) -> bool {
+    let mut resolved = dir.to_path_buf();
+    let mut climbing = true;
+    for component in target.components() {
+        match component {
+            std::path::Component::ParentDir if climbing => {
+                if !resolved.pop() {
+                    return false;
+                }
+            }
+            std::path::Component::CurDir => {}
+            std::path::Component::Normal(name) => {
+                climbing = false;
+                resolved.push(name);
+            }
+            _ => return false,
+        }
+    }
+    !target.as_os_str().is_empty() && resolved.starts_with(within)
+}
+
+/// Recreates a symlink entry of an archive at `link`, or skips it with a
+/// message if it would point outside `within` or symlinks aren't supported.
+fn extract_symlink(
+    link: &Path,
+    target: &Path,
+    within: &Path,
+    progress: &dyn ProgressReporter,
+) -> Result<()> {
+    let dir = link.parent().context("Symlink entry without a directory")?;
+    fs::create_dir_all(dir).with_context(|| format!("Couldn't create {}", dir.display()))?;
+    let dir = resolve_within(dir, within)?;
+    if !link_within(&dir, target, within) {
+        progress.message(&format!(
+            "Skipped the symlink {} to {}, which points outside {}",
+            link.display(),
+            target.display(),
+            within.display()
+        ));
+        return Ok(());
+    }
+    if link.symlink_metadata().is_ok() {
+        fs::remove_file(link).with_context(|| format!("Couldn't replace {}", link.display()))?;
+    }
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, link)
+        .with_context(|| format!("Couldn't create the symlink {}", link.display()))?;
+    #[cfg(not(unix))]
+    progress.message(&format!(
+        "Skipped the symlink {} to {}, symlinks aren't supported here",
+        link.display(),
+        target.display()
+    ));
+    Ok(())
+}
+
+/// Extracts the archive `from` to `to`, with [`unzip`] or [`untar`] by what
+/// its contents say it is.
+fn unpack(
+    from: &Path,
+    to: &Path,
+    flatten: bool,
+    workers: usize,
+    cancel: &CancellationToken,
+    progress: &dyn ProgressReporter,
+) -> Result<Vec<ManifestFile>> {
+    match ArchiveFormat::of(from)? {
+        Some(ArchiveFormat::Zip) => unzip(from, to, flatten, workers, cancel, progress),
+        Some(format) => untar(from, to, format, flatten, cancel, progress),
+        None => Err(anyhow!(
+            "{} is neither a zip archive nor a tarball",
+            from.display()
+        )),
+    }
+}
+
+/// [`unpack`], removing `to` again if it didn't exist before and extraction
+/// fails, so a half-extracted version doesn't look installed.
+fn unpack_new(
+    from: &Path,
+    to: &Path,
+    flatten: bool,
+    workers: usize,
+    cancel: &CancellationToken,
+    progress: &dyn ProgressReporter,
+) -> Result<Vec<ManifestFile>> {
+    let existed = to.exists();
+    let result = unpack(from, to, flatten, workers, cancel, progress);
+    if result.is_err() && !existed && fs::remove_dir_all(to).is_ok() {
+        progress.message(&format!("Removed the partially extracted {}", to.display()));
+    }
+    result
+}
+
+/// [`unpack_new`] on a blocking thread, so progress and ctrl-c are still
+/// handled while it runs.
+pub(crate) async fn extract(
+    from: &Path,
+    to: &Path,
+    flatten: bool,
+    workers: usize,
+    cancel: &CancellationToken,
+    progress: &Arc<dyn ProgressReporter>,
+) -> Result<Vec<ManifestFile>> {
+    let (from, to) = (from.to_path_buf(), to.to_path_buf());
+    let (cancel, progress) = (cancel.clone(), progress.clone());
+    tokio::task::spawn_blocking(move || {
+        unpack_new(&from, &to, flatten, workers, &cancel, &*progress)
+    })
+    .await?
+}
+
+/// The name of `entry`, if it can be told. Names not flagged as UTF-8 are
+/// meant to be cp437, but plenty of tools write UTF-8 without saying so, so
+/// that is tried first.
+fn entry_name(entry: &zip::read::ZipFile) -> Option<String> {
+    match std::str::from_utf8(entry.name_raw()) {
+        Ok(name) => Some(name.to_string()),
+        // Decoded as cp437 unless flagged as UTF-8, when what isn't was
+        // replaced instead.
+        Err(_) => Some(entry.name().to_string())
+            .filter(|name| !name.contains(char::REPLACEMENT_CHARACTER)),
+    }
+}
+
+/// `name` as a relative path, unless it could point outside the directory
+/// it is extracted to; what [`zip::read::ZipFile::enclosed_name`] checks.
+pub(crate) fn enclosed_path(name: &str) -> Option<PathBuf> {
+    if name.contains('\0') {
+        return None;
+    }
+    let mut depth = 0usize;
+    for component in Path::new(name).components() {
+        match component {
+            std::path::Component::Prefix(_) | std::path::Component::RootDir => return None,
+            std::path::Component::ParentDir => depth = depth.checked_sub(1)?,
+            std::path::Component::Normal(_) => depth += 1,
+            std::path::Component::CurDir => {}
+        }
+    }
+    Some(PathBuf::from(name))
+}
+
+/// Whether `entry`, named `name`, looks like something to run, so an
+/// install without it would be broken.
+fn looks_executable(entry: &zip::read::ZipFile, name: &str) -> bool {
+    let file_name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    !name.ends_with('/')
+        && (file_name.starts_with("Godot")
+            || file_name.ends_with(".exe")
+            || entry
+                .unix_mode()
+                .is_some_and(|mode| mode & S_IFMT != S_IFLNK && mode & 0o111 != 0))
+}
+
+/// Extracts the tarball `from`, compressed as `format` says, to `to` as
+/// [`unzip`] does zips, one entry after another as it is decompressed. With
+/// `flatten`, a directory wrapping everything can only be left out once all
+/// is extracted, so only if `to` held nothing before.
+fn untar(
+    from: &Path,
+    to: &Path,
+    format: ArchiveFormat,
+    flatten: bool,
+    cancel: &CancellationToken,
+    progress: &dyn ProgressReporter,
+) -> Result<Vec<ManifestFile>> {
+    let file = fs::File::open(from).with_context(|| format!("Couldn't open {}", from.display()))?;
+    progress.phase_changed(Phase::Extracting);
+    // Compressed tarballs don't say how much they unpack to, so progress is
+    // how much of the archive has been read.
+    progress.started(Some(file.metadata()?.len()));
+    let file = io::BufReader::new(ReportingReader {
+        inner: file,
+        progress,
+    });
+    let mut reader: Box<dyn Read> = match format {
+        ArchiveFormat::TarGz => Box::new(flate2::bufread::MultiGzDecoder::new(file)),
+        ArchiveFormat::TarXz => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(file)),
+        _ => Box::new(file),
+    };
+    let files = read_tar(&mut reader, from, to, flatten, cancel, progress)?;
+    // Padding after the end of the tar stream, and the compressed format's
+    // checksum, so all of the archive is read and checked.
+    io::copy(&mut reader, &mut io::sink())
+        .with_context(|| format!("{} is corrupt or was cut short", from.display()))?;
+    progress.finished();
+    Ok(files)
+}
+
+/// Extracts the tar stream `reader` of the archive `from` to `to`, see
+/// [`untar`].
+fn read_tar(
+    reader: &mut dyn Read,
+    from: &Path,
+    to: &Path,
+    flatten: bool,
+    cancel: &CancellationToken,
+    progress: &dyn ProgressReporter,
+) -> Result<Vec<ManifestFile>> {
+    tracing::debug!(archive = %from.display(), to = %to.display(), "extracting");
+    let started = Instant::now();
+    let was_empty = fs::read_dir(to).map_or(true, |mut entries| entries.next().is_none());
+    fs::create_dir_all(to).with_context(|| format!("Couldn't create {}", to.display()))?;
+    // Whatever the entries say, nothing is written outside of this.
+    let within = to
+        .canonicalize()
+        .with_context(|| format!("Couldn't resolve {}", to.display()))?;
+    let corrupt = || format!("{} is corrupt or was cut short", from.display());
+
+    // Later entries replace earlier ones, in the manifest too.
+    let mut files: std::collections::BTreeMap<String, ManifestFile> = Default::default();
+    let mut dirs = Vec::new();
+    let mut roots = HashSet::new();
+    let mut top_level_file = false;
+    let mut archive = tar::Archive::new(reader);
+    for (index, entry) in archive.entries().with_context(corrupt)?.enumerate() {
+        if cancel.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        let mut entry = entry.with_context(corrupt)?;
+        let kind = entry.header().entry_type();
+        if kind == tar::EntryType::XGlobalHeader {
+            continue;
+        }
+        let name = String::from_utf8(entry.path_bytes().into_owned()).ok();
+        let link = entry
+            .link_name_bytes()
+            .map(|link| String::from_utf8_lossy(&link).into_owned())
+            .unwrap_or_default();
+        let modified = entry
+            .header()
+            .mtime()
+            .ok()
+            .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+        // Only the permission bits: setuid, setgid and sticky bits from an
+        // archive aren't trusted.
+        let mode = entry.header().mode().map_or(0o644, |mode| mode & 0o777);
+
+        let Some(path) = name.as_deref().and_then(enclosed_path) else {
+            let reason = match &name {
+                Some(_) => format!("which would be extracted outside {}", to.display()),
+                None => "whose name isn't valid UTF-8".to_string(),
+            };
+            let name = name.unwrap_or_else(|| format!("entry {}", index));
+            tracing::warn!(entry = index, name = %name, reason = %reason, "skipped entry");
+            progress.message(&format!("Skipped {}, {}", name, reason));
+            continue;
+        };
+        let name = name.unwrap_or_default();
+        let is_dir = kind.is_dir() || (kind.is_file() && name.ends_with('/'));
+        let mut components = path.components().filter_map(|component| match component {
+            std::path::Component::Normal(component) => Some(component),
+            _ => None,
+        });
+        // `./` itself, which is `to`.
+        let Some(root) = components.next() else {
+            continue;
+        };
+        top_level_file |= components.next().is_none() && !is_dir;
+        roots.insert(PathBuf::from(root));
+        let outpath = to.join(&path);
+
+        match kind {
+            _ if is_dir => {
+                tracing::trace!(path = %outpath.display(), "extracted directory");
+                fs::create_dir_all(&outpath)
+                    .with_context(|| format!("Couldn't create {}", outpath.display()))?;
+                resolve_within(&outpath, &within)?;
+                dirs.push((outpath, Some(mode), modified));
+            }
+            tar::EntryType::Symlink => {
+                extract_symlink(&outpath, Path::new(&link), &within, progress)?;
+                let path = manifest_path(to, &outpath);
+                files.remove(&path);
+                if outpath.symlink_metadata().is_ok_and(|m| m.is_symlink()) {
+                    files.insert(
+                        path.clone(),
+                        ManifestFile {
+                            path,
+                            size: link.len() as u64,
+                            mode: 0o777,
+                            sha256: None,
+                            link: Some(link),
+                        },
+                    );
+                }
+            }
+            tar::EntryType::Link => {
+                let Some(source) = enclosed_path(&link).map(|source| to.join(source)) else {
+                    progress.message(&format!(
+                        "Skipped {}, a link to {} outside {}",
+                        name,
+                        link,
+                        to.display()
+                    ));
+                    continue;
+                };
+                make_room(&outpath, &within)?;
+                resolve_within(&source, &within)?;
+                fs::copy(&source, &outpath).with_context(|| {
+                    format!(
+                        "Couldn't copy {} to {}",
+                        source.display(),
+                        outpath.display()
+                    )
+                })?;
+                let path = manifest_path(to, &outpath);
+                let mode = files
+                    .get(&manifest_path(to, &source))
+                    .map_or(mode, |source| source.mode);
+                let file = ManifestFile {
+                    path: path.clone(),
+                    size: outpath.metadata()?.len(),
+                    mode,
+                    sha256: Some(sha256_file(&outpath)?),
+                    link: None,
+                };
+                files.insert(path, file);
+            }
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                make_room(&outpath, &within)?;
+                let size = entry.size();
+                let (outfile, sha256) = fs::File::create(&outpath)
+                    .and_then(|outfile| {
+                        // Progress is the archive read, not what it unpacks to.
+                        let mut outfile = ReportingWriter {
+                            inner: outfile,
+                            progress: &NoProgress,
+                            hasher: Sha256::new(),
+                        };
+                        if io::copy(&mut entry, &mut outfile)? < size {
+                            return Err(io::ErrorKind::UnexpectedEof.into());
+                        }
+                        Ok((outfile.inner, outfile.hasher.finalize()))
+                    })
+                    .with_context(|| {
+                        format!("Couldn't extract {} to {}", name, outpath.display())
+                    })?;
+                if let Some(modified) = modified {
+                    outfile.set_modified(modified).with_context(|| {
+                        format!(
+                            "Couldn't set the modification time of {}",
+                            outpath.display()
+                        )
+                    })?;
+                }
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+
+                    fs::set_permissions(&outpath, fs::Permissions::from_mode(mode)).with_context(
+                        || format!("Couldn't set the permissions of {}", outpath.display()),
+                    )?;
+                }
+                tracing::trace!(path = %outpath.display(), bytes = size, "extracted");
+                let path = manifest_path(to, &outpath);
+                let file = ManifestFile {
+                    path: path.clone(),
+                    size,
+                    mode,
+                    sha256: Some(hex(&sha256)),
+                    link: None,
+                };
+                files.insert(path, file);
+            }
+            _ => {
+                progress.message(&format!(
+                    "Skipped {}, which is neither a file, a directory nor a link",
+                    name
+                ));
+            }
+        }
+    }
+
+    let mut files: Vec<_> = files.into_values().collect();
+    let root = match roots.len() {
+        1 if flatten && was_empty && !top_level_file => roots.into_iter().next(),
+        _ => None,
+    };
+    if let Some(root) = root.filter(|root| root.extension().is_none_or(|ext| ext != "app")) {
+        let wrapper = to.join(&root);
+        unwrap_dir(to, &wrapper)?;
+        for file in &mut files {
+            file.path = manifest_path(&root, Path::new(&file.path));
+        }
+        dirs = dirs
+            .into_iter()
+            .filter_map(|(path, mode, modified)| {
+                let path = path.strip_prefix(&wrapper).ok()?;
+                (!path.as_os_str().is_empty()).then(|| (to.join(path), mode, modified))
+            })
+            .collect();
+    }
+    finish_dirs(dirs)?;
+    tracing::debug!(
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        "extraction complete"
+    );
+    Ok(files)
+}
+
+/// Moves everything in the directory `wrapper` up into `to`, leaving out
+/// `wrapper` itself.
+fn unwrap_dir(to: &Path, wrapper: &Path) -> Result<()> {
+    // Out of the way of an entry in it of the same name.
+    let moved = aside(wrapper, ".unwrapping-");
+    fs::rename(wrapper, &moved)
+        .with_context(|| format!("Couldn't move {} aside", wrapper.display()))?;
+    for entry in
+        fs::read_dir(&moved).with_context(|| format!("Couldn't read {}", moved.display()))?
+    {
+        let entry = entry?;
+        fs::rename(entry.path(), to.join(entry.file_name()))
+            .with_context(|| format!("Couldn't move {} up", entry.path().display()))?;
+    }
+    fs::remove_dir(&moved).with_context(|| format!("Couldn't remove {}", moved.display()))
+}
+
+/// The directory every entry of `archive` lives under, if there is exactly
+/// one and it is not a macOS `.app` bundle.
+fn wrapping_dir<R: Read + io::Seek>(archive: &mut zip::ZipArchive<R>) -> Option<PathBuf> {
+    let mut root: Option<PathBuf> = None;
+    for i in 0..archive.len() {
+        let name = entry_name(&archive.by_index_raw(i).ok()?)?;
+        let mut components = Path::new(&name).components();
+        let first = PathBuf::from(components.next()?.as_os_str());
+        if components.next().is_none() && !name.ends_with('/') {
+            return None;
+        }
+        match &root {
+            Some(root) if *root != first => return None,
+            Some(_) => {}
+            None => root = Some(first),
+        }
+    }
+    root.filter(|root| root.extension().is_none_or(|ext| ext != "app"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sha2::Sha512;
+
+    use crate::godot;
+    #[cfg(unix)]
+    use crate::install::ensure_executable;
+    use crate::install::find_executable;
+    use crate::test_util::{synthetic_tar, synthetic_zip, test_dir, RecordedProgress};
+
+    /// Hashes of every file under `dir` by their path relative to it, along
+    /// with their permissions.
+    fn tree_hashes(dir: &Path) -> std::collections::BTreeMap<PathBuf, (String, u32)> {
+        let mut hashes = std::collections::BTreeMap::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(next) = pending.pop() {
+            for entry in fs::read_dir(&next).unwrap() {
+                let path = entry.unwrap().path();
+                let meta = fs::symlink_metadata(&path).unwrap();
+                #[cfg(unix)]
+                let mode = std::os::unix::fs::PermissionsExt::mode(&meta.permissions());
+                #[cfg(not(unix))]
+                let mode = 0;
+                let hash = if meta.is_dir() {
+                    pending.push(path.clone());
+                    String::new()
+                } else {
+                    format!("{:x}", Sha512::digest(fs::read(&path).unwrap()))
+                };
+                hashes.insert(path.strip_prefix(dir).unwrap().to_path_buf(), (hash, mode));
+            }
+        }
+        hashes
+    }
+
+    #[test]
+    fn test_unzip_parallel_matches_serial() {
+        let tmp = test_dir("parallel");
+        let dir = tmp.path();
+        let zip_path = dir.join("archive.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+        zip.add_directory("Godot/", Default::default()).unwrap();
+        for i in 0..400 {
+            let name = format!("Godot/{}/{}/file{}.bin", i % 7, i % 3, i);
+            let options = zip::write::FileOptions::default()
+                .compression_method(zip::CompressionMethod::Deflated)
+                .unix_permissions(if i % 5 == 0 { 0o755 } else { 0o644 });
+            zip.start_file(name, options).unwrap();
+            let content: Vec<u8> = (0..i * 97).map(|j| (j * i % 251) as u8).collect();
+            zip.write_all(&content).unwrap();
+        }
+        // A later entry replaces an earlier one wherever it is extracted.
+        zip.start_file("Godot/0/0/file0.bin", Default::default())
+            .unwrap();
+        zip.write_all(b"replaced").unwrap();
+        zip.finish().unwrap();
+
+        let serial = dir.join("serial");
+        unzip(
+            &zip_path,
+            &serial,
+            true,
+            1,
+            &CancellationToken::new(),
+            &NoProgress,
+        )
+        .unwrap();
+        let parallel = dir.join("parallel");
+        unzip(
+            &zip_path,
+            &parallel,
+            true,
+            8,
+            &CancellationToken::new(),
+            &NoProgress,
+        )
+        .unwrap();
+        let hashes = tree_hashes(&serial);
+        assert_eq!(hashes.len(), 400 + 7 + 7 * 3);
+        assert_eq!(hashes, tree_hashes(&parallel));
+        assert_eq!(
+            fs::read(parallel.join("0/0/file0.bin")).unwrap(),
+            b"replaced"
+        );
+    }
+
+    /// Holds up extraction until the runtime it was started from lets it go.
+    struct Gate(std::sync::Mutex<std::sync::mpsc::Receiver<()>>);
+
+    impl ProgressReporter for Gate {
+        fn phase_changed(&self, _phase: Phase) {}
+        fn started(&self, _total: Option<u64>) {
+            let released = self.0.lock().unwrap().recv_timeout(Duration::from_secs(10));
+            released.expect("extraction blocked the runtime");
+        }
+        fn advanced(&self, _bytes: u64) {}
+        fn finished(&self) {}
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_extract_leaves_runtime_responsive() {
+        let (_tmp, zip) = synthetic_zip("responsive", &["Godot/", "Godot/Godot.x86_64"]);
+        let to = zip.with_file_name("out");
+        let (release, released) = std::sync::mpsc::channel();
+        let progress: Arc<dyn ProgressReporter> = Arc::new(Gate(std::sync::Mutex::new(released)));
+        let cancel = CancellationToken::new();
+        // Runs only while extraction is waiting, on the same single thread.
+        let interrupt = tokio::spawn({
+            let cancel = cancel.clone();
+            async move {
+                cancel.cancel();
+                release.send(()).unwrap();
+            }
+        });
+        let err = extract(&zip, &to, true, 1, &cancel, &progress)
+            .await
+            .unwrap_err();
+        interrupt.await.unwrap();
+        assert!(err.is::<Cancelled>(), "{}", err);
+        assert!(!to.exists());
+    }
+
+    #[test]
+    fn test_unzip_truncated_archive() {
+        let (_tmp, zip) = synthetic_zip("truncated", &["Godot_v4.2.1-stable_linux.x86_64"]);
+        let bytes = fs::read(&zip).unwrap();
+        fs::write(&zip, &bytes[..bytes.len() / 2]).unwrap();
+        let to = zip.with_file_name("out");
+        let err =
+            unpack_new(&zip, &to, true, 1, &CancellationToken::new(), &NoProgress).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("{} is not a valid zip archive", zip.display())
+        );
+        assert!(!to.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unzip_read_only_destination() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (_tmp, zip) = synthetic_zip(
+            "read-only",
+            &["Godot_mono/", "Godot_mono/GodotSharp/Api/GodotSharp.dll"],
+        );
+        let to = zip.with_file_name("out");
+        let locked = to.join("GodotSharp");
+        fs::create_dir_all(&locked).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o555)).unwrap();
+        // Permissions don't hold back root, so there is nothing to test then.
+        let unchecked = fs::write(locked.join("probe"), "").is_ok();
+        let result = unpack_new(&zip, &to, true, 1, &CancellationToken::new(), &NoProgress);
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        if unchecked {
+            return;
+        }
+        let err = result.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("Couldn't create {}", locked.join("Api").display())
+        );
+        // Left alone, as it was there before.
+        assert!(locked.exists());
+
+        let fresh = locked.join("new");
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o555)).unwrap();
+        let err = unpack_new(
+            &zip,
+            &fresh,
+            true,
+            1,
+            &CancellationToken::new(),
+            &NoProgress,
+        )
+        .unwrap_err();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(err.to_string().starts_with("Couldn't create"), "{}", err);
+        assert!(!fresh.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unzip_hostile_archive() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (_tmp, zip) = synthetic_zip(
+            "hostile",
+            &[
+                "Godot_v4.2.1-stable_linux.x86_64",
+                "../evil",
+                "Godot/../../evil",
+                "/godotup-test-evil",
+                "absolute -> /etc",
+                "up -> ../evil",
+                "sub/inside -> ../Godot_v4.2.1-stable_linux.x86_64",
+                "sub/outside -> ../../evil",
+                "sub/sneaky -> sub/../../../evil",
+                "sub/nested/current -> ../../sub",
+                "sub/outside/payload",
+            ],
+        );
+        let dir = zip.parent().unwrap();
+        let to = dir.join("out");
+        unzip(&zip, &to, true, 1, &CancellationToken::new(), &NoProgress).unwrap();
+        assert!(to.join("Godot_v4.2.1-stable_linux.x86_64").is_file());
+        assert!(!dir.join("evil").exists());
+        assert!(!Path::new("/godotup-test-evil").exists());
+        for skipped in ["absolute", "up", "sub/outside", "sub/sneaky"] {
+            assert!(
+                !to.join(skipped).is_symlink(),
+                "{} shouldn't be a symlink",
+                skipped
+            );
+        }
+        // Written as a plain directory where the link was skipped.
+        assert!(to.join("sub/outside/payload").is_file());
+        assert_eq!(
+            fs::read_link(to.join("sub/inside")).unwrap(),
+            Path::new("../Godot_v4.2.1-stable_linux.x86_64")
+        );
+        assert!(to.join("sub/nested/current/inside").exists());
+
+        // Nor does it write through a link that was already there.
+        let outside = dir.join("elsewhere");
+        fs::create_dir_all(&outside).unwrap();
+        let to = dir.join("linked");
+        fs::create_dir_all(&to).unwrap();
+        std::os::unix::fs::symlink(&outside, to.join("sub")).unwrap();
+        let (_tmp, zip) = synthetic_zip("hostile-linked", &["Godot", "sub/payload"]);
+        let err = unzip(&zip, &to, true, 1, &CancellationToken::new(), &NoProgress).unwrap_err();
+        assert!(
+            err.to_string().starts_with("Refusing to extract"),
+            "{}",
+            err
+        );
+        assert!(!outside.join("payload").exists());
+
+        // Nor does it keep setuid, setgid or sticky bits.
+        let (_tmp, zip) = synthetic_zip("hostile-setuid", &["Godot"]);
+        let mut bytes = fs::read(&zip).unwrap();
+        let central = bytes.windows(4).rposition(|b| b == b"PK\x01\x02").unwrap();
+        bytes[central + 38..central + 42].copy_from_slice(&(0o107755u32 << 16).to_le_bytes());
+        fs::write(&zip, bytes).unwrap();
+        let to = dir.join("setuid");
+        unzip(&zip, &to, true, 1, &CancellationToken::new(), &NoProgress).unwrap();
+        let mode = fs::metadata(to.join("Godot")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o7777, 0o755);
+    }
+
+    #[test]
+    fn test_unzip_progress() {
+        let (_tmp, zip) = synthetic_zip(
+            "unzip-progress",
+            &[
+                "Godot/",
+                "Godot/Godot_v4.2.1-stable_mono_linux.x86_64",
+                "Godot/GodotSharp/Api/GodotSharp.dll",
+            ],
+        );
+        let mut archive = zip::ZipArchive::new(fs::File::open(&zip).unwrap()).unwrap();
+        let total: u64 = (0..archive.len())
+            .map(|i| archive.by_index_raw(i).unwrap().size())
+            .sum();
+        let progress = RecordedProgress::default();
+        let to = zip.parent().unwrap().join("out");
+        unzip(&zip, &to, true, 1, &CancellationToken::new(), &progress).unwrap();
+        // The bar fills up to exactly the sizes in the central directory.
+        assert_eq!(
+            progress.take(),
+            [
+                String::from("Extracting"),
+                format!("started Some({})", total),
+                format!("advanced {}", total),
+                String::from("finished"),
+            ]
+        );
+    }
+
+    /// A zip64 archive of one stored entry, written by hand since the zip crate
+    /// only writes zip64 records for archives too large for a test. The entry
+    /// declares `size` as its uncompressed size.
+    fn zip64_archive(
+        name: &str,
+        entry: &str,
+        content: &[u8],
+        size: u64,
+    ) -> (tempfile::TempDir, PathBuf) {
+        fn crc32(data: &[u8]) -> u32 {
+            let mut crc = !0u32;
+            for &byte in data {
+                crc ^= u32::from(byte);
+                for _ in 0..8 {
+                    crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+                }
+            }
+            !crc
+        }
+        let mut zip = Vec::new();
+        let put = |zip: &mut Vec<u8>, fields: &[&[u8]]| {
+            for field in fields {
+                zip.extend_from_slice(field);
+            }
+        };
+        let crc = crc32(content).to_le_bytes();
+        let name_len = (entry.len() as u16).to_le_bytes();
+        let compressed = content.len() as u64;
+        put(
+            &mut zip,
+            &[
+                &0x0403_4b50u32.to_le_bytes(),
+                &45u16.to_le_bytes(),
+                &[0; 8], // flags, stored, time, date
+                &crc,
+                &u32::MAX.to_le_bytes(),
+                &u32::MAX.to_le_bytes(),
+                &name_len,
+                &20u16.to_le_bytes(),
+                entry.as_bytes(),
+                &1u16.to_le_bytes(),
+                &16u16.to_le_bytes(),
+                &size.to_le_bytes(),
+                &compressed.to_le_bytes(),
+                content,
+            ],
+        );
+        let central = zip.len() as u64;
+        put(
+            &mut zip,
+            &[
+                &0x0201_4b50u32.to_le_bytes(),
+                &45u16.to_le_bytes(),
+                &45u16.to_le_bytes(),
+                &[0; 8], // flags, stored, time, date
+                &crc,
+                &u32::MAX.to_le_bytes(),
+                &u32::MAX.to_le_bytes(),
+                &name_len,
+                &28u16.to_le_bytes(),
+                &[0; 6], // comment, disk, internal attributes
+                &[0; 4], // external attributes
+                &u32::MAX.to_le_bytes(),
+                entry.as_bytes(),
+                &1u16.to_le_bytes(),
+                &24u16.to_le_bytes(),
+                &size.to_le_bytes(),
+                &compressed.to_le_bytes(),
+                &0u64.to_le_bytes(),
+            ],
+        );
+        let end64 = zip.len() as u64;
+        put(
+            &mut zip,
+            &[
+                &0x0606_4b50u32.to_le_bytes(),
+                &44u64.to_le_bytes(),
+                &45u16.to_le_bytes(),
+                &45u16.to_le_bytes(),
+                &[0; 8], // disks
+                &1u64.to_le_bytes(),
+                &1u64.to_le_bytes(),
+                &(end64 - central).to_le_bytes(),
+                &central.to_le_bytes(),
+                &0x0706_4b50u32.to_le_bytes(),
+                &0u32.to_le_bytes(),
+                &end64.to_le_bytes(),
+                &1u32.to_le_bytes(),
+                &0x0605_4b50u32.to_le_bytes(),
+                &[0; 4], // disks
+                &u16::MAX.to_le_bytes(),
+                &u16::MAX.to_le_bytes(),
+                &u32::MAX.to_le_bytes(),
+                &u32::MAX.to_le_bytes(),
+                &0u16.to_le_bytes(),
+            ],
+        );
+        let dir = test_dir(name);
+        let path = dir.path().join("archive.zip");
+        fs::write(&path, zip).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn test_unzip_zip64_archive() {
+        let content = b"zip64 godot";
+        let (_tmp, zip) = zip64_archive(
+            "zip64",
+            "Godot_v4.2.1-stable_linux.x86_64",
+            content,
+            content.len() as u64,
+        );
+        let to = zip.with_file_name("out");
+        unzip(&zip, &to, true, 1, &CancellationToken::new(), &NoProgress).unwrap();
+        assert_eq!(
+            fs::read(to.join("Godot_v4.2.1-stable_linux.x86_64")).unwrap(),
+            content
+        );
+
+        // More than any disk holds.
+        let (_tmp, zip) = zip64_archive("zip64-bomb", "Godot", content, 1 << 60);
+        let to = zip.with_file_name("out");
+        let err =
+            unpack_new(&zip, &to, true, 1, &CancellationToken::new(), &NoProgress).unwrap_err();
+        assert!(err.to_string().contains("unpacks to 1.00 EiB"), "{}", err);
+        assert!(!to.exists());
+
+        // Less than it has.
+        let (_tmp, zip) = zip64_archive("zip64-understated", "Godot", content, 5);
+        let to = zip.with_file_name("out");
+        let err =
+            unpack_new(&zip, &to, true, 1, &CancellationToken::new(), &NoProgress).unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with("is larger than the 5B it declares"),
+            "{}",
+            err
+        );
+        assert!(!to.exists());
+    }
+
+    #[test]
+    fn test_unzip_flat_archive() {
+        let (_tmp, zip) = synthetic_zip("flat", &["Godot_v4.2.1-stable_linux.x86_64"]);
+        let to = zip.with_file_name("out");
+        unzip(&zip, &to, true, 1, &CancellationToken::new(), &NoProgress).unwrap();
+        assert!(to.join("Godot_v4.2.1-stable_linux.x86_64").is_file());
+    }
+
+    #[test]
+    fn test_unzip_mono_archive() {
+        let (_tmp, zip) = synthetic_zip(
+            "mono",
+            &[
+                "Godot_v4.2.1-stable_mono_linux_x86_64/",
+                "Godot_v4.2.1-stable_mono_linux_x86_64/Godot_v4.2.1-stable_mono_linux.x86_64",
+                "Godot_v4.2.1-stable_mono_linux_x86_64/GodotSharp/",
+                "Godot_v4.2.1-stable_mono_linux_x86_64/GodotSharp/Api/GodotSharp.dll",
+            ],
+        );
+        let to = zip.with_file_name("out");
+        unzip(&zip, &to, true, 1, &CancellationToken::new(), &NoProgress).unwrap();
+        assert!(to.join("Godot_v4.2.1-stable_mono_linux.x86_64").is_file());
+        assert!(to.join("GodotSharp/Api/GodotSharp.dll").is_file());
+        assert!(!to.join("Godot_v4.2.1-stable_mono_linux_x86_64").exists());
+    }
+
+    #[test]
+    fn test_unzip_flattens_wrapped_archive() {
+        let exe = "Godot_v4.2.1-stable_mono_linux.x86_64";
+        let dll = "GodotSharp/Api/GodotSharp.dll";
+        let (_tmp, flat) = synthetic_zip("layout-flat", &[exe, "GodotSharp/", dll]);
+        let (_tmp, wrapped) = synthetic_zip(
+            "layout-wrapped",
+            &[
+                "Godot_v4.2.1-stable_mono_linux_x86_64/",
+                &format!("Godot_v4.2.1-stable_mono_linux_x86_64/{}", exe),
+                "Godot_v4.2.1-stable_mono_linux_x86_64/GodotSharp/",
+                &format!("Godot_v4.2.1-stable_mono_linux_x86_64/{}", dll),
+            ],
+        );
+        let layout = |zip: &Path, flatten| {
+            let to = zip.with_file_name(if flatten { "flat" } else { "wrapped" });
+            unzip(zip, &to, flatten, 1, &CancellationToken::new(), &NoProgress).unwrap();
+            let exe = find_executable(&to, godot::Platform::Linux64).unwrap();
+            let paths: Vec<_> = tree_hashes(&to).into_keys().collect();
+            (exe.strip_prefix(&to).unwrap().to_path_buf(), paths)
+        };
+        let (exe_path, paths) = layout(&flat, true);
+        assert_eq!(exe_path, Path::new(exe));
+        assert_eq!(layout(&wrapped, true), (exe_path, paths));
+
+        // Left wrapped, the executable is still found.
+        let (exe_path, _) = layout(&wrapped, false);
+        assert_eq!(
+            exe_path,
+            Path::new("Godot_v4.2.1-stable_mono_linux_x86_64").join(exe)
+        );
+    }
+
+    #[test]
+    fn test_unzip_keeps_modification_times() {
+        let tmp = test_dir("mtime");
+        let dir = tmp.path();
+        let zip_path = dir.join("archive.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+        let released = zip::DateTime::from_date_and_time(2023, 12, 1, 12, 30, 0).unwrap();
+        let options = zip::write::FileOptions::default().last_modified_time(released);
+        zip.add_directory("GodotSharp/", options).unwrap();
+        zip.start_file("GodotSharp/GodotSharp.dll", options)
+            .unwrap();
+        zip.start_file("Godot_v4.2.1-stable_linux.x86_64", options)
+            .unwrap();
+        let invalid = options.last_modified_time(zip::DateTime::from_msdos(0, 0));
+        zip.start_file("README.txt", invalid).unwrap();
+        zip.finish().unwrap();
+
+        let to = dir.join("out");
+        let before = SystemTime::now() - Duration::from_secs(5);
+        unzip(
+            &zip_path,
+            &to,
+            true,
+            4,
+            &CancellationToken::new(),
+            &NoProgress,
+        )
+        .unwrap();
+        let modified = |path: &str| fs::metadata(to.join(path)).unwrap().modified().unwrap();
+        let expected = SystemTime::UNIX_EPOCH + Duration::from_secs(1_701_433_800);
+        assert_eq!(modified("Godot_v4.2.1-stable_linux.x86_64"), expected);
+        assert_eq!(modified("GodotSharp/GodotSharp.dll"), expected);
+        // Even though a file was written into it since.
+        assert_eq!(modified("GodotSharp"), expected);
+        assert!(modified("README.txt") > before);
+    }
+
+    /// A zip of empty `entries`, with the placeholders in `raw` then swapped
+    /// for as many raw bytes in their names, which aren't flagged as UTF-8.
+    fn legacy_zip(
+        name: &str,
+        entries: &[&str],
+        raw: &[(&str, &[u8])],
+    ) -> (tempfile::TempDir, PathBuf) {
+        let dir = test_dir(name);
+        let path = dir.path().join("archive.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+        for entry in entries {
+            let options = zip::write::FileOptions::default()
+                .compression_method(zip::CompressionMethod::Stored);
+            if entry.ends_with('/') {
+                zip.add_directory(*entry, options).unwrap();
+            } else {
+                zip.start_file(*entry, options).unwrap();
+            }
+        }
+        zip.finish().unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        for (placeholder, name) in raw {
+            assert_eq!(placeholder.len(), name.len());
+            let mut found = 0;
+            while let Some(at) = bytes
+                .windows(placeholder.len())
+                .position(|window| window == placeholder.as_bytes())
+            {
+                bytes[at..at + name.len()].copy_from_slice(name);
+                found += 1;
+            }
+            // In the local header and the central directory.
+            assert_eq!(found, 2, "{}", placeholder);
+        }
+        fs::write(&path, bytes).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn test_unzip_legacy_names() {
+        let (_tmp, zip) = legacy_zip(
+            "legacy-names",
+            &["Godot_v3.5-stable_x11.64", "docs/cafX.txt", "docs/naiQ.txt"],
+            // "café" in cp437 and "naï" in UTF-8.
+            &[("cafX", b"caf\x82"), ("naiQ", b"na\xc3\xaf")],
+        );
+        let to = zip.with_file_name("out");
+        unzip(&zip, &to, true, 1, &CancellationToken::new(), &NoProgress).unwrap();
+        assert!(to.join("docs/café.txt").is_file());
+        assert!(to.join("docs/naï.txt").is_file());
+
+        // Names flagged as UTF-8 that aren't can't be told.
+        let unreadable = |name: &str, entries: &[&str], placeholder| {
+            let (tmp, zip) = legacy_zip(name, entries, &[(placeholder, b"\xff\xfe")]);
+            let mut bytes = fs::read(&zip).unwrap();
+            // Set the UTF-8 flag of both headers of the last entry.
+            for signature in [[0x50, 0x4b, 3, 4], [0x50, 0x4b, 1, 2]] {
+                let at = bytes
+                    .windows(4)
+                    .rposition(|window| window == signature)
+                    .unwrap();
+                let flags = if signature[2] == 3 { at + 6 } else { at + 8 };
+                bytes[flags + 1] |= 1 << 3;
+            }
+            fs::write(&zip, bytes).unwrap();
+            let to = zip.with_file_name("out");
+            let result = unzip(&zip, &to, true, 1, &CancellationToken::new(), &NoProgress);
+            (tmp, result, to)
+        };
+        let (_tmp, result, to) = unreadable(
+            "legacy-unreadable",
+            &["Godot_v3.5-stable_x11.64", "docs/", "docs/QQ.txt"],
+            "QQ",
+        );
+        result.unwrap();
+        assert_eq!(fs::read_dir(to.join("docs")).unwrap().count(), 0);
+        let (_tmp, result, _) = unreadable("legacy-executable", &["docs/", "GodotQQ"], "QQ");
+        let err = result.unwrap_err().to_string();
+        assert!(
+            err.starts_with("Refusing to skip Godot\u{fffd}\u{fffd}")
+                && err.ends_with("isn't valid UTF-8, as it looks like an executable"),
+            "{}",
+            err
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unzip_without_unix_modes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // As made on Windows: no external attributes at all.
+        let windows_zip = |name: &str, entries: &[(&str, &[u8])]| {
+            let tmp = test_dir(name);
+            let dir = tmp.path();
+            let path = dir.join("archive.zip");
+            let mut zip = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+            for (name, content) in entries {
+                zip.start_file(*name, Default::default()).unwrap();
+                zip.write_all(content).unwrap();
+            }
+            zip.finish().unwrap();
+            let mut bytes = fs::read(&path).unwrap();
+            let mut at = 0;
+            while let Some(found) = bytes[at..]
+                .windows(4)
+                .position(|window| window == [0x50, 0x4b, 1, 2])
+            {
+                at += found;
+                bytes[at + 38..at + 42].fill(0);
+                at += 4;
+            }
+            fs::write(&path, bytes).unwrap();
+            let to = dir.join("out");
+            unzip(&path, &to, true, 1, &CancellationToken::new(), &NoProgress).unwrap();
+            ensure_executable(&to, godot::Platform::Linux64).unwrap();
+            let mode =
+                |name: &str| fs::metadata(to.join(name)).unwrap().permissions().mode() & 0o777;
+            entries
+                .iter()
+                .map(|(name, _)| mode(name))
+                .collect::<Vec<_>>()
+        };
+        let modes = windows_zip(
+            "no-modes-named",
+            &[
+                ("Godot_v4.2.1-stable_linux.x86_64", b"\x7fELF"),
+                ("Godot_v4.2.1-stable_linux.pck", b"GDPC"),
+            ],
+        );
+        assert_eq!(modes, [0o755, 0o644]);
+        let modes = windows_zip(
+            "no-modes-unnamed",
+            &[
+                ("editor.bin", b"\x7fELF large"),
+                ("libsmall.so", b"\x7fELF"),
+                ("notes.txt", b"larger than any of them"),
+            ],
+        );
+        assert_eq!(modes, [0o755, 0o644, 0o644]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unzip_keeps_app_bundle() {
+        let framework = "Godot_mono.app/Contents/Frameworks/Mono.framework";
+        let (_tmp, zip) = synthetic_zip(
+            "app",
+            &[
+                "Godot_mono.app/",
+                "Godot_mono.app/Contents/Info.plist",
+                "Godot_mono.app/Contents/MacOS/Crashpad",
+                "Godot_mono.app/Contents/MacOS/Godot",
+                "Godot_mono.app/Contents/Resources/GodotSharp/Api/GodotSharp.dll",
+                &format!("{}/Versions/A/Mono", framework),
+                &format!("{}/Versions/Current -> A", framework),
+                &format!("{}/Mono -> Versions/Current/Mono", framework),
+            ],
+        );
+        let to = zip.with_file_name("out");
+        unzip(&zip, &to, true, 4, &CancellationToken::new(), &NoProgress).unwrap();
+        let framework = to.join(framework);
+        assert_eq!(
+            fs::read_link(framework.join("Versions/Current")).unwrap(),
+            Path::new("A")
+        );
+        assert_eq!(
+            fs::read_to_string(framework.join("Mono")).unwrap(),
+            "Godot_mono.app/Contents/Frameworks/Mono.framework/Versions/A/Mono"
+        );
+
+        // Without an `Info.plist` naming it, the first one.
+        let macos = to.join("Godot_mono.app/Contents/MacOS");
+        assert_eq!(
+            find_executable(&to, godot::Platform::Macos).unwrap(),
+            macos.join("Crashpad")
+        );
+        let plist = "<plist version=\"1.0\">\n<dict>\n\t<key>CFBundleExecutable</key>\n\t<string>Godot</string>\n</dict>\n</plist>\n";
+        fs::write(to.join("Godot_mono.app/Contents/Info.plist"), plist).unwrap();
+        assert_eq!(
+            find_executable(&to, godot::Platform::Macos).unwrap(),
+            macos.join("Godot")
+        );
+        // Not what other platforms would run.
+        assert!(find_executable(&to, godot::Platform::Linux64).is_err());
+    }
+
+    #[test]
+    fn test_untar() {
+        let long = format!("Godot/data/{}.pck", "x".repeat(120));
+        let entries = [
+            "Godot/",
+            "Godot/Godot",
+            "Godot/data/",
+            &long,
+            "Godot/lib -> data",
+        ];
+        for extension in [".tar", ".tar.gz", ".tar.xz"] {
+            let name = format!("untar{}", extension.replace('.', "-"));
+            let (_tmp, tar) = synthetic_tar(&name, extension, &entries);
+            check_archive(&tar, None).unwrap();
+            let to = tar.with_file_name("out");
+            let progress = RecordedProgress::default();
+            let files =
+                unpack_new(&tar, &to, true, 4, &CancellationToken::new(), &progress).unwrap();
+            // What it unpacks to isn't known up front, so the bar is the
+            // archive read.
+            let size = tar.metadata().unwrap().len();
+            assert_eq!(
+                progress.take(),
+                [
+                    String::from("Extracting"),
+                    format!("started Some({})", size),
+                    format!("advanced {}", size),
+                    String::from("finished"),
+                ],
+                "{}",
+                extension
+            );
+
+            assert_eq!(fs::read_to_string(to.join("Godot")).unwrap(), "Godot/Godot");
+            let pck = to.join(long.strip_prefix("Godot/").unwrap());
+            assert_eq!(fs::read_to_string(&pck).unwrap(), long);
+            assert_eq!(
+                pck.metadata().unwrap().modified().unwrap(),
+                SystemTime::UNIX_EPOCH + Duration::from_secs(1_701_433_800)
+            );
+            assert_eq!(
+                to.join("data").metadata().unwrap().modified().unwrap(),
+                SystemTime::UNIX_EPOCH + Duration::from_secs(1_701_433_800)
+            );
+            let mut paths: Vec<_> = files.iter().map(|file| file.path.clone()).collect();
+            paths.sort();
+            // Symlinks are only made on unix.
+            let expected: Vec<_> = ["Godot", &long[6..], "lib"]
+                .into_iter()
+                .filter(|path| cfg!(unix) || *path != "lib")
+                .collect();
+            assert_eq!(paths, expected, "{}", extension);
+            #[cfg(unix)]
+            assert_eq!(fs::read_link(to.join("lib")).unwrap(), Path::new("data"));
+            assert_eq!(files[0].sha256, Some(hex(&Sha256::digest("Godot/Godot"))));
+            assert_eq!(files[0].mode, 0o644);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_untar_hostile_archive() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (_tmp, tar) = synthetic_tar(
+            "untar-hostile",
+            ".tar.gz",
+            &[
+                "Godot/",
+                "../evil",
+                "/tmp/evil",
+                "Godot/../../evil",
+                "Godot/out -> ../../..",
+                "Godot/Godot",
+            ],
+        );
+        let to = tar.with_file_name("out");
+        let files =
+            unpack_new(&tar, &to, false, 1, &CancellationToken::new(), &NoProgress).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "Godot/Godot");
+        assert!(!tar.with_file_name("evil").exists());
+        assert!(!to.join("Godot/out").exists());
+
+        // Nor does it keep setuid, setgid or sticky bits.
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_ustar();
+        header.set_mode(0o7755);
+        header.set_size(5);
+        builder
+            .append_data(&mut header, "Godot", &b"Godot"[..])
+            .unwrap();
+        let setuid = tar.with_file_name("setuid.tar");
+        fs::write(&setuid, builder.into_inner().unwrap()).unwrap();
+        let to = tar.with_file_name("setuid");
+        let files = unpack_new(
+            &setuid,
+            &to,
+            true,
+            1,
+            &CancellationToken::new(),
+            &NoProgress,
+        )
+        .unwrap();
+        assert_eq!(files[0].mode, 0o755);
+        let mode = fs::metadata(to.join("Godot")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o7777, 0o755);
+
+        // Cut short, it fails and leaves nothing behind.
+        let bytes = fs::read(&tar).unwrap();
+        fs::write(&tar, &bytes[..bytes.len() / 2]).unwrap();
+        let to = tar.with_file_name("short");
+        let err =
+            unpack_new(&tar, &to, true, 1, &CancellationToken::new(), &NoProgress).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("{} is corrupt or was cut short", tar.display())
+        );
+        assert!(!to.exists());
+
+        // A header that doesn't add up.
+        let (_tmp, tar) = synthetic_tar("untar-corrupt", ".tar", &["Godot/Godot"]);
+        let mut bytes = fs::read(&tar).unwrap();
+        bytes[0] = b'g';
+        fs::write(&tar, bytes).unwrap();
+        let err =
+            unpack_new(&tar, &to, true, 1, &CancellationToken::new(), &NoProgress).unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("checksum mismatch"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_check_archive() {
+        let (_tmp, zip) = synthetic_zip("check", &["Godot/", "Godot/Godot"]);
+        let len = zip.metadata().unwrap().len();
+        check_archive(&zip, None).unwrap();
+        check_archive(&zip, Some(len)).unwrap();
+        let reason = |path: &Path, size| {
+            let err = check_archive(path, size).unwrap_err().to_string();
+            assert!(err.contains("--force-download"), "{}", err);
+            let (_, reason) = err.split_once(", ").unwrap();
+            reason.split_once("; ").unwrap().0.to_string()
+        };
+        assert_eq!(
+            reason(&zip, Some(len + 1)),
+            format!(
+                "it is {} where {} was expected",
+                HumanBytes(len),
+                HumanBytes(len + 1)
+            )
+        );
+
+        let bad = zip.with_file_name("bad.zip");
+        let bytes = fs::read(&zip).unwrap();
+        fs::write(&bad, &bytes[..bytes.len() - 10]).unwrap();
+        assert_eq!(
+            reason(&bad, None),
+            "its end of central directory record is missing, so it was cut short"
+        );
+        fs::write(&bad, "<!DOCTYPE html><title>404 Not Found</title>").unwrap();
+        assert_eq!(
+            reason(&bad, None),
+            "it looks like a web page, likely an error from the server"
+        );
+        fs::write(&bad, "").unwrap();
+        assert_eq!(reason(&bad, Some(len)), "it is empty");
+    }
+}
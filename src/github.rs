@@ -0,0 +1,321 @@
+//! The GitHub API, for building a version list from the releases of
+//! repositories such as `godotengine/godot-builds`.
+
+use anyhow::{Context, Result};
+use reqwest::{header, Client, StatusCode};
+use serde::Deserialize;
+
+use crate::download::send_traced;
+use crate::version_list::{next_page_link, templates_version};
+use crate::{godot, unix_now};
+
+pub(crate) const GITHUB_API: &str = "https://api.github.com";
+
+/// A GET of the GitHub API `url`, authenticated with `token` only if it
+/// really goes to GitHub.
+pub(crate) fn github_request(
+    client: &Client,
+    url: &str,
+    token: Option<&str>,
+) -> reqwest::RequestBuilder {
+    let request = client
+        .get(url)
+        .header(header::ACCEPT, "application/vnd.github+json");
+    let to_github = url
+        .strip_prefix(GITHUB_API)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+    match token {
+        Some(token) if to_github => request.bearer_auth(token),
+        _ => request,
+    }
+}
+
+/// Fails on an unsuccessful GitHub API `response`, telling when the rate
+/// limit resets if that is what it ran into.
+pub(crate) fn github_response(
+    response: reqwest::Response,
+    url: &str,
+    token: Option<&str>,
+) -> Result<reqwest::Response> {
+    let header = |name| {
+        response
+            .headers()
+            .get(name)?
+            .to_str()
+            .ok()?
+            .parse::<u64>()
+            .ok()
+    };
+    let limited = matches!(
+        response.status(),
+        StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+    ) && header("x-ratelimit-remaining") == Some(0);
+    if limited {
+        return Err(GithubRateLimited {
+            reset: header("x-ratelimit-reset"),
+            authenticated: token.is_some(),
+        }
+        .into());
+    }
+    response
+        .error_for_status()
+        .with_context(|| format!("GitHub API request {} failed", url))
+}
+
+/// The GitHub API refused a request until the rate limit resets.
+#[derive(Debug)]
+pub(crate) struct GithubRateLimited {
+    /// When the limit resets, in seconds since the epoch.
+    pub(crate) reset: Option<u64>,
+    pub(crate) authenticated: bool,
+}
+
+impl std::fmt::Display for GithubRateLimited {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GitHub API rate limit exceeded")?;
+        if let Some(reset) = self.reset {
+            let minutes = reset.saturating_sub(unix_now()).div_ceil(60);
+            write!(f, ", it resets in {} min", minutes)?;
+        }
+        if !self.authenticated {
+            write!(
+                f,
+                "; set github_token in the config, or GODOTUP_GITHUB_TOKEN or GITHUB_TOKEN, for a higher limit"
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for GithubRateLimited {}
+
+#[derive(Deserialize)]
+struct GithubRelease {
+    published_at: Option<String>,
+    assets: Vec<GithubAsset>,
+}
+
+#[derive(Deserialize)]
+struct GithubAsset {
+    name: String,
+    browser_download_url: String,
+    size: u64,
+}
+
+/// Builds a version list from the release assets of each of `repos`, e.g.
+/// `godotengine/godot-builds`. Earlier repos win when several publish the
+/// same build.
+pub(crate) async fn fetch_github_version_list(
+    client: &Client,
+    repos: &[String],
+    token: Option<&str>,
+) -> Result<godot::VersionList> {
+    let mut list = godot::VersionList::default();
+    for repo in repos {
+        let mut url = Some(format!(
+            "{}/repos/{}/releases?per_page=100",
+            GITHUB_API, repo
+        ));
+        while let Some(page) = url {
+            tracing::debug!(url = %page, "fetching releases");
+            let resp = send_traced(github_request(client, &page, token)).await?;
+            let resp = github_response(resp, &page, token)?;
+            url = resp
+                .headers()
+                .get(header::LINK)
+                .and_then(|link| link.to_str().ok())
+                .and_then(next_page_link);
+            add_github_releases(&mut list, resp.json::<Vec<GithubRelease>>().await?);
+        }
+    }
+    Ok(list)
+}
+
+fn add_github_releases(list: &mut godot::VersionList, releases: Vec<GithubRelease>) {
+    for release in releases {
+        let date = release
+            .published_at
+            .as_deref()
+            .map(|d| d[..10.min(d.len())].to_string());
+        for asset in release.assets {
+            if asset.name.ends_with(".tpz") {
+                if let Some(version) = templates_version(&asset.name) {
+                    let info = list.release_info_mut(&version);
+                    info.export_templates.get_or_insert(godot::Artifact {
+                        url: asset.browser_download_url,
+                        filename: asset.name,
+                        sha512: None,
+                        size: Some(asset.size),
+                        source: None,
+                    });
+                }
+                continue;
+            }
+            let Some(build) = godot::Build::from_filename(&asset.name) else {
+                continue;
+            };
+            if let Some(date) = &date {
+                let info = list.release_info_mut(&build.version);
+                info.release_date.get_or_insert_with(|| date.clone());
+            }
+            list.insert(
+                build,
+                godot::Artifact {
+                    url: asset.browser_download_url,
+                    filename: asset.name,
+                    sha512: None,
+                    size: Some(asset.size),
+                    source: None,
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::notes::fetch_github_release_notes;
+    use crate::test_util::{serve_with, test_client};
+
+    #[test]
+    fn test_version_list_from_github_releases() {
+        let asset = |name: &str| {
+            format!(
+                r#"{{"name": "{0}", "browser_download_url": "https://github.com/godotengine/godot-builds/releases/download/x/{0}", "size": 42}}"#,
+                name
+            )
+        };
+        let release = |date: &str, names: &[&str]| {
+            format!(
+                r#"{{"tag_name": "x", "published_at": "{}", "assets": [{}]}}"#,
+                date,
+                names
+                    .iter()
+                    .map(|n| asset(n))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        };
+        let page = format!(
+            "[{}, {}]",
+            release(
+                "2023-12-12T15:36:13Z",
+                &[
+                    "Godot_v4.2.1-stable_linux.x86_64.zip",
+                    "Godot_v4.2.1-stable_mono_linux_x86_64.zip",
+                    "Godot_v4.2.1-stable_win64.exe.zip",
+                    "Godot_v4.2.1-stable_export_templates.tpz",
+                    "Godot_v4.2.1-stable_mono_export_templates.tpz",
+                    "Godot_v4.2.1-stable_web_editor.zip",
+                    "SHA512-SUMS.txt",
+                ]
+            ),
+            release(
+                "2023-09-22T10:00:00Z",
+                &[
+                    "Godot_v3.5.3-stable_x11.64.zip",
+                    "Godot_v3.5.3-stable_osx.universal.zip",
+                ]
+            ),
+        );
+        let page = format!(
+            "{}, {}]",
+            page.trim_end_matches(']'),
+            release(
+                "2024-03-21T12:00:00Z",
+                &[
+                    "Godot_v4.3-dev5_linux.x86_64.zip",
+                    "Godot_v4.3-dev5_mono_linux_x86_64.zip",
+                ]
+            )
+        );
+        let mut list = godot::VersionList::default();
+        add_github_releases(&mut list, serde_json::from_str(&page).unwrap());
+
+        let v = |s| godot::Version::parse(s).unwrap();
+        let artifact = list
+            .find_artifact(&v("4.2.1-mono"), godot::Platform::Linux64)
+            .unwrap();
+        assert_eq!(
+            artifact.filename,
+            "Godot_v4.2.1-stable_mono_linux_x86_64.zip"
+        );
+        assert_eq!(artifact.size, Some(42));
+        assert!(artifact
+            .url
+            .ends_with("/Godot_v4.2.1-stable_mono_linux_x86_64.zip"));
+        assert!(list
+            .find_artifact(&v("4.2.1"), godot::Platform::Win64)
+            .is_some());
+        assert!(list
+            .find_artifact(&v("3.5.3"), godot::Platform::Macos)
+            .is_some());
+        assert_eq!(list.len(godot::Platform::Linux64), 5);
+        let dev = list
+            .filter_channel(godot::Channel::Dev)
+            .map(|b| b.version.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(dev, ["Godot_v4.3-dev5_mono", "Godot_v4.3-dev5"]);
+
+        let info = list.release_info(&v("4.2.1")).unwrap();
+        assert_eq!(info.release_date.as_deref(), Some("2023-12-12"));
+        let templates = list.find_templates(&v("4.2.1")).unwrap();
+        assert_eq!(
+            templates.filename,
+            "Godot_v4.2.1-stable_export_templates.tpz"
+        );
+        assert_eq!(templates.size, Some(42));
+        assert!(templates.url.ends_with(&templates.filename));
+        let templates = list.find_templates(&v("4.2.1-mono")).unwrap();
+        assert_eq!(
+            templates.filename,
+            "Godot_v4.2.1-stable_mono_export_templates.tpz"
+        );
+        assert!(list.find_templates(&v("3.5.3")).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_github_rate_limit() {
+        let reset = unix_now() + 10 * 60;
+        let url = serve_with(move |head| {
+            // The token never leaves for anywhere but GitHub.
+            assert!(!head.to_ascii_lowercase().contains("authorization"));
+            format!(
+                "HTTP/1.1 403 Forbidden\r\nX-RateLimit-Remaining: 0\r\nX-RateLimit-Reset: {}\r\nContent-Length: 2\r\n\r\n{{}}",
+                reset
+            )
+        });
+        let api = url.trim_end_matches("/versions.yml");
+        let repos = [String::from("godotengine/godot")];
+        let client = test_client();
+        let err = fetch_github_release_notes(&client, api, &repos, Some("secret"), "4.2.1-stable")
+            .await
+            .unwrap_err();
+        let message = err.to_string();
+        assert!(
+            message.starts_with("GitHub API rate limit exceeded, it resets in 10 min"),
+            "{}",
+            message
+        );
+        assert!(!message.contains("secret"));
+        let err = fetch_github_release_notes(&client, api, &repos, None, "4.2.1-stable")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("GODOTUP_GITHUB_TOKEN"), "{}", err);
+
+        let authorized = |url| {
+            github_request(&client, url, Some("secret"))
+                .build()
+                .unwrap()
+                .headers()
+                .contains_key(header::AUTHORIZATION)
+        };
+        assert!(authorized(
+            "https://api.github.com/repos/godotengine/godot/releases"
+        ));
+        assert!(!authorized("https://api.github.com.example.org/repos"));
+        assert!(!authorized("https://github.com/godotengine/godot/releases"));
+    }
+}
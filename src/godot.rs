@@ -0,0 +1,3308 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BTreeMap, HashMap, HashSet},
+    env,
+    fmt::Display,
+    fs,
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Every known build and where to download it. Serialized as the v2
+/// schema, see [`VersionListFile`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(try_from = "VersionListFile", into = "VersionListFile")]
+pub struct VersionList {
+    builds: HashMap<Build, Artifact>,
+    releases: HashMap<Version, ReleaseInfo>,
+    /// Builds that came from a local overlay, see [`VersionList::overlay`].
+    local: HashSet<Build>,
+}
+
+/// A downloadable archive of one [`Build`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artifact {
+    pub url: String,
+    pub filename: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha512: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// The version list source this artifact was merged from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+impl Artifact {
+    /// An artifact known only by its URL, named after its last segment.
+    pub fn from_url(url: String) -> Artifact {
+        Artifact {
+            filename: url.rsplit('/').next().unwrap_or_default().to_string(),
+            url,
+            sha512: None,
+            size: None,
+            source: None,
+        }
+    }
+
+    /// Name to download the artifact as: its filename, with the archive
+    /// extension of its URL if that differs, so it is clear what kind of
+    /// archive the download is.
+    pub fn download_name(&self) -> String {
+        let url = self.url.split(['?', '#']).next().unwrap_or_default();
+        let url = url.to_ascii_lowercase();
+        let Some(extension) = ARCHIVE_EXTENSIONS.iter().find(|ext| url.ends_with(*ext)) else {
+            return self.filename.clone();
+        };
+        let lowercase = self.filename.to_ascii_lowercase();
+        if lowercase.ends_with(extension) {
+            return self.filename.clone();
+        }
+        let stem_len = ARCHIVE_EXTENSIONS
+            .iter()
+            .find(|ext| lowercase.ends_with(*ext))
+            .map_or(self.filename.len(), |ext| self.filename.len() - ext.len());
+        format!("{}{}", &self.filename[..stem_len], extension)
+    }
+}
+
+/// Extensions of the archives godotup can extract, longer ones first.
+pub const ARCHIVE_EXTENSIONS: [&str; 6] = [".tar.gz", ".tar.xz", ".tgz", ".txz", ".tar", ".zip"];
+
+/// Metadata shared by all builds of a release.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReleaseInfo {
+    /// Day of the release as `YYYY-MM-DD`.
+    #[serde(default, alias = "released", skip_serializing_if = "Option::is_none")]
+    pub release_date: Option<String>,
+    /// The export templates matching this release and flavor. Older lists
+    /// name only their URL, as `templates_url`.
+    #[serde(
+        default,
+        alias = "templates_url",
+        deserialize_with = "artifact_or_url",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub export_templates: Option<Artifact>,
+    /// Where to read the release notes, as markdown or plain text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes_url: Option<String>,
+}
+
+fn artifact_or_url<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Artifact>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ArtifactOrUrl {
+        Url(String),
+        Artifact(Artifact),
+    }
+    Ok(match Option::<ArtifactOrUrl>::deserialize(deserializer)? {
+        Some(ArtifactOrUrl::Url(url)) => Some(Artifact::from_url(url)),
+        Some(ArtifactOrUrl::Artifact(artifact)) => Some(artifact),
+        None => None,
+    })
+}
+
+/// On-disk form of a [`VersionList`]: each release is listed once with
+/// the artifacts of every platform it was built for.
+///
+/// ```yaml
+/// schema: 2
+/// releases:
+/// - major: 4
+///   minor: 2
+///   patch: 1
+///   suffix: stable
+///   is_mono: false
+///   release_date: 2023-12-12
+///   export_templates:
+///     url: https://.../Godot_v4.2.1-stable_export_templates.tpz
+///     filename: Godot_v4.2.1-stable_export_templates.tpz
+///     size: 1044361237
+///   artifacts:
+///     linux64:
+///       url: https://.../Godot_v4.2.1-stable_linux.x86_64.zip
+///       filename: Godot_v4.2.1-stable_linux.x86_64.zip
+///       sha512: 9b3c...
+///       size: 55403162
+/// ```
+#[derive(Serialize, Deserialize)]
+struct VersionListFile {
+    schema: u8,
+    releases: Vec<Release>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Release {
+    #[serde(flatten)]
+    version: Version,
+    #[serde(flatten)]
+    info: ReleaseInfo,
+    artifacts: BTreeMap<Platform, Artifact>,
+}
+
+impl TryFrom<VersionListFile> for VersionList {
+    type Error = anyhow::Error;
+
+    fn try_from(file: VersionListFile) -> Result<Self> {
+        if file.schema != 2 {
+            return Err(anyhow!(
+                "unsupported version list schema {}, expected 2",
+                file.schema
+            ));
+        }
+        let mut list = VersionList::default();
+        for release in file.releases {
+            for (platform, artifact) in release.artifacts {
+                let build = Build {
+                    version: release.version.clone(),
+                    platform,
+                };
+                list.builds.insert(build, artifact);
+            }
+            list.releases.insert(release.version, release.info);
+        }
+        Ok(list)
+    }
+}
+
+impl From<VersionList> for VersionListFile {
+    fn from(list: VersionList) -> Self {
+        let mut releases = BTreeMap::<Version, Release>::new();
+        for (build, artifact) in list.builds {
+            releases
+                .entry(build.version.clone())
+                .or_insert_with(|| Release {
+                    info: list
+                        .releases
+                        .get(&build.version)
+                        .cloned()
+                        .unwrap_or_default(),
+                    version: build.version,
+                    artifacts: BTreeMap::new(),
+                })
+                .artifacts
+                .insert(build.platform, artifact);
+        }
+        VersionListFile {
+            schema: 2,
+            releases: releases.into_values().collect(),
+        }
+    }
+}
+
+impl VersionList {
+    pub fn find_artifact(&self, vers: &Version, platform: Platform) -> Option<&Artifact> {
+        self.builds.get(&Build {
+            version: vers.clone(),
+            platform,
+        })
+    }
+
+    pub fn release_info(&self, vers: &Version) -> Option<&ReleaseInfo> {
+        self.releases.get(vers)
+    }
+
+    /// The export templates for `vers`, which differ between the mono and
+    /// standard flavors.
+    pub fn find_templates(&self, vers: &Version) -> Option<&Artifact> {
+        self.release_info(vers)?.export_templates.as_ref()
+    }
+
+    /// When `vers` was released, if known.
+    pub fn release_date(&self, vers: &Version) -> Option<&str> {
+        self.release_info(vers)?.release_date.as_deref()
+    }
+
+    /// Versions for `platform` released on or after `date` (`YYYY-MM-DD`),
+    /// newest first. Versions without a known date are left out.
+    pub fn released_since(&self, date: &str, platform: Platform) -> Result<Vec<&Version>> {
+        let date = parse_date(date)?;
+        Ok(self.filter(platform, |v| {
+            self.release_date(v)
+                .is_some_and(|released| released >= date)
+        }))
+    }
+
+    /// All versions for `platform`, most recently released first. Versions
+    /// without a known date come last, newest first among themselves.
+    pub fn iter_by_date(&self, platform: Platform) -> Vec<&Version> {
+        let mut versions: Vec<_> = self.iter(platform).collect();
+        versions.sort_by(|a, b| match (self.release_date(a), self.release_date(b)) {
+            (Some(a), Some(b)) => b.cmp(a),
+            (a, b) => a.is_none().cmp(&b.is_none()),
+        });
+        versions
+    }
+
+    /// Adds `artifact` for `build` unless the list already has one.
+    pub fn insert(&mut self, build: Build, artifact: Artifact) {
+        self.builds.entry(build).or_insert(artifact);
+    }
+
+    /// Merges `local` over this list: its builds and release metadata
+    /// replace any existing ones and are reported by
+    /// [`VersionList::is_local`].
+    pub fn overlay(&mut self, local: VersionList) {
+        for (build, artifact) in local.builds {
+            self.local.insert(build.clone());
+            self.builds.insert(build, artifact);
+        }
+        self.releases.extend(local.releases);
+    }
+
+    /// Whether `build` came from a local overlay.
+    pub fn is_local(&self, build: &Build) -> bool {
+        self.local.contains(build)
+    }
+
+    /// Releases available in `newer` but not in this list and vice versa,
+    /// regardless of platform.
+    pub fn diff(&self, newer: &VersionList) -> VersionListDiff {
+        let versions = |list: &VersionList| {
+            list.builds
+                .keys()
+                .map(|b| b.version.clone())
+                .collect::<HashSet<_>>()
+        };
+        let (old, new) = (versions(self), versions(newer));
+        let sorted = |versions: HashSet<&Version>| {
+            let mut versions = versions.into_iter().cloned().collect::<Vec<_>>();
+            versions.sort_by(|a, b| b.cmp(a));
+            versions
+        };
+        VersionListDiff {
+            added: sorted(new.iter().filter(|v| !old.contains(v)).collect()),
+            removed: sorted(old.iter().filter(|v| !new.contains(v)).collect()),
+        }
+    }
+
+    /// Adds the builds and release metadata of `other` that this list
+    /// does not have yet.
+    pub fn merge(&mut self, other: VersionList) {
+        for (build, artifact) in other.builds {
+            self.insert(build, artifact);
+        }
+        for (version, info) in other.releases {
+            self.releases.entry(version).or_insert(info);
+        }
+    }
+
+    /// Records `source` as the origin of every artifact.
+    pub fn set_source(&mut self, source: &str) {
+        for artifact in self.builds.values_mut() {
+            artifact.source = Some(source.to_string());
+        }
+    }
+
+    /// Mutable metadata of `vers`, created empty if missing.
+    pub fn release_info_mut(&mut self, vers: &Version) -> &mut ReleaseInfo {
+        self.releases.entry(vers.clone()).or_default()
+    }
+
+    /// Resolves a partial version spec such as `4`, `4.1`, `4.1.2`, `4.1-rc`
+    /// or `4.2-mono` to the newest matching version available for
+    /// `platform`. Stable releases win over prereleases unless the spec
+    /// names a channel. Requirements such as `^4.1` or `>=4.0,<4.3` are
+    /// handed to [`VersionList::find_best`].
+    ///
+    /// The keywords `latest` (newest stable), `latest-prerelease` (newest
+    /// alpha, beta, rc or stable release), `latest-dev` (the same, but
+    /// including dev snapshots) and `latest-mono` (newest stable mono build)
+    /// are accepted as well. Dev snapshots are only picked by `latest-dev`
+    /// or a spec naming them, such as `4.3-dev` or `4.3-dev5`.
+    ///
+    /// Any spec may carry a `mono@` prefix (`mono@4.2.1`, `mono@^4.1`,
+    /// `mono@latest`) as an alternative to the `-mono` suffix.
+    pub fn resolve(&self, spec: &str, platform: Platform) -> Result<Version> {
+        self.resolve_with(spec, platform, false)
+    }
+
+    /// Like [`resolve`](Self::resolve), but with `prereleases` alpha,
+    /// beta and rc releases are as good as stable ones: `latest` and
+    /// `latest-mono` pick the newest of them and a spec like `4.3` its
+    /// newest release, whatever the channel.
+    pub fn resolve_with(
+        &self,
+        spec: &str,
+        platform: Platform,
+        prereleases: bool,
+    ) -> Result<Version> {
+        resolve_among(&self.versions_for(platform), spec, platform, prereleases)
+    }
+
+    /// Versions for `platform` of the same flavor and minor series as
+    /// `vers` that are newer than it, newest first. Dev snapshots only
+    /// count if `vers` is one.
+    pub fn newer_than(&self, vers: &Version, platform: Platform) -> Vec<&Version> {
+        let dev = vers.channel() == Channel::Dev;
+        self.filter(platform, |v| {
+            (v.major, v.minor, v.is_mono) == (vers.major, vers.minor, vers.is_mono)
+                && v > vers
+                && (dev || v.channel() != Channel::Dev)
+        })
+    }
+
+    /// What `vers` should be upgraded to, if anything: the newest stable
+    /// release of its series, or for a prerelease without a newer
+    /// stable, the newest prerelease.
+    pub fn upgrade_for(&self, vers: &Version, platform: Platform) -> Option<&Version> {
+        let newer = self.newer_than(vers, platform);
+        let stable = newer.iter().find(|v| !v.is_prerelease());
+        match stable {
+            Some(stable) => Some(*stable),
+            None if vers.is_prerelease() => newer.first().copied(),
+            None => None,
+        }
+    }
+
+    /// Builds whose version is on `channel`, newest first.
+    pub fn filter_channel(&self, channel: Channel) -> impl Iterator<Item = &Build> {
+        self.builds()
+            .map(|(build, _)| build)
+            .filter(move |b| b.version.channel() == channel)
+    }
+
+    /// Every build and its artifact, newest version first, then by
+    /// platform. Builds of the same release are adjacent, mono first.
+    pub fn builds(&self) -> impl Iterator<Item = (&Build, &Artifact)> {
+        let mut builds: Vec<_> = self.builds.iter().collect();
+        builds
+            .sort_by(|(a, _), (b, _)| b.version.cmp(&a.version).then(a.platform.cmp(&b.platform)));
+        builds.into_iter()
+    }
+
+    /// The newest version available for `platform` that satisfies `req`.
+    pub fn find_best(&self, req: &VersionReq, platform: Platform) -> Option<&Version> {
+        self.versions_for(platform)
+            .into_iter()
+            .filter(|v| req.matches(v))
+            .max()
+    }
+
+    /// All versions with a build for `platform`, newest first.
+    pub fn iter(&self, platform: Platform) -> impl Iterator<Item = &Version> {
+        self.versions_for(platform).into_iter().rev()
+    }
+
+    /// Number of versions with a build for `platform`.
+    pub fn len(&self, platform: Platform) -> usize {
+        self.builds
+            .keys()
+            .filter(|b| b.platform == platform)
+            .count()
+    }
+
+    pub fn is_empty(&self, platform: Platform) -> bool {
+        self.len(platform) == 0
+    }
+
+    /// The newest stable, non-mono version for `platform`.
+    pub fn latest_stable(&self, platform: Platform) -> Option<&Version> {
+        self.latest(Channel::Stable, platform)
+    }
+
+    /// The newest non-mono version on `channel` for `platform`.
+    pub fn latest(&self, channel: Channel, platform: Platform) -> Option<&Version> {
+        self.iter(platform)
+            .find(|v| v.channel() == channel && !v.is_mono)
+    }
+
+    /// Every version of Godot `major` for `platform`, newest first.
+    pub fn all_for_major(&self, major: u8, platform: Platform) -> Vec<&Version> {
+        self.filter(platform, |v| v.major == major)
+    }
+
+    /// Versions for `platform` matching `predicate`, newest first.
+    pub fn filter<P>(&self, platform: Platform, predicate: P) -> Vec<&Version>
+    where
+        P: Fn(&Version) -> bool,
+    {
+        self.iter(platform).filter(|v| predicate(v)).collect()
+    }
+
+    /// Versions on any platform whose [`Version::short`] form contains
+    /// `query`, newest first. `*` in the query matches any run of
+    /// characters, so `4.*-rc*` finds every 4.x release candidate; an
+    /// empty query matches everything.
+    pub fn search(&self, query: &str) -> Vec<&Version> {
+        self.search_by(query, |_| true)
+    }
+
+    /// Like [`search`](Self::search), but only mono builds.
+    pub fn search_mono(&self, query: &str) -> Vec<&Version> {
+        self.search_by(query, |v| v.is_mono)
+    }
+
+    /// Like [`search`](Self::search), but only versions on `channel`.
+    pub fn search_channel(&self, query: &str, channel: Channel) -> Vec<&Version> {
+        self.search_by(query, |v| v.channel() == channel)
+    }
+
+    /// Matches of `query` that also satisfy `predicate`, newest first.
+    pub fn search_by<P>(&self, query: &str, predicate: P) -> Vec<&Version>
+    where
+        P: Fn(&Version) -> bool,
+    {
+        let mut versions: Vec<_> = self
+            .builds
+            .keys()
+            .map(|b| &b.version)
+            .filter(|v| predicate(v) && matches_query(&v.short(), query))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        versions.sort_by(|a, b| b.cmp(a));
+        versions
+    }
+
+    /// All versions with a build for `platform`, oldest first.
+    fn versions_for(&self, platform: Platform) -> Vec<&Version> {
+        let mut versions: Vec<_> = self
+            .builds
+            .keys()
+            .filter(|b| b.platform == platform)
+            .map(|b| &b.version)
+            .collect();
+        versions.sort();
+        versions
+    }
+
+    /// Reads a version list in the v2 schema, the v1 schema (a flat map
+    /// from [`Build`] to URL) or the legacy one where every key carried
+    /// its own `platform` field.
+    pub fn from_yaml(s: &str) -> Result<VersionList> {
+        let err = match serde_yaml::from_str::<VersionList>(s) {
+            Ok(list) => return Ok(list),
+            Err(err) => err,
+        };
+        if let Ok(v1) = serde_yaml::from_str::<VersionListV1>(s) {
+            return Ok(v1.builds.into_iter().collect());
+        }
+        match serde_yaml::from_str::<LegacyVersionList>(s) {
+            Ok(legacy) => Ok(legacy.into()),
+            Err(_) => Err(err.into()),
+        }
+    }
+
+    /// Serializes the list in the v2 schema.
+    pub fn to_yaml(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+
+    /// Reads a version list in any [`ListFormat`], telling them apart by
+    /// content. YAML lists may use any schema [`from_yaml`](Self::from_yaml)
+    /// accepts; JSON and TOML lists must use the v2 schema.
+    pub fn parse(s: &str) -> Result<VersionList> {
+        match ListFormat::sniff(s) {
+            ListFormat::Yaml => VersionList::from_yaml(s),
+            ListFormat::Json => Ok(serde_json::from_str(s)?),
+            ListFormat::Toml => Ok(toml::from_str(s)?),
+        }
+    }
+
+    /// Serializes the list in the v2 schema as `format`.
+    pub fn to_format(&self, format: ListFormat) -> Result<String> {
+        match format {
+            ListFormat::Yaml => self.to_yaml(),
+            ListFormat::Json => Ok(serde_json::to_string_pretty(self)? + "\n"),
+            // Going through `Value` puts plain values ahead of tables, as
+            // TOML requires.
+            ListFormat::Toml => Ok(toml::to_string(&toml::Value::try_from(self)?)?),
+        }
+    }
+}
+
+/// A serialization format for version lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListFormat {
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl ListFormat {
+    /// The format a file name's extension stands for, if any.
+    pub fn from_path(path: &Path) -> Option<ListFormat> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "yml" | "yaml" => Some(ListFormat::Yaml),
+            "json" => Some(ListFormat::Json),
+            "toml" => Some(ListFormat::Toml),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format of a list from its first meaningful line.
+    fn sniff(s: &str) -> ListFormat {
+        let first = s
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty() && !l.starts_with('#'))
+            .unwrap_or_default();
+        if first.starts_with('{') {
+            ListFormat::Json
+        } else if first.starts_with('[') || first.starts_with("schema =") {
+            ListFormat::Toml
+        } else {
+            ListFormat::Yaml
+        }
+    }
+}
+
+impl FromStr for ListFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "yaml" | "yml" => Ok(ListFormat::Yaml),
+            "json" => Ok(ListFormat::Json),
+            "toml" => Ok(ListFormat::Toml),
+            _ => Err(anyhow!(
+                "unknown list format `{}`: expected yaml, json or toml",
+                s
+            )),
+        }
+    }
+}
+
+/// Checks that `date` looks like `YYYY-MM-DD`, so it compares correctly
+/// with release dates.
+pub fn parse_date(date: &str) -> Result<&str> {
+    let valid = date.len() == 10
+        && date.char_indices().all(|(i, c)| match i {
+            4 | 7 => c == '-',
+            _ => c.is_ascii_digit(),
+        })
+        && (1..=12).contains(&date[5..7].parse::<u8>().unwrap_or(0))
+        && (1..=31).contains(&date[8..10].parse::<u8>().unwrap_or(0));
+    if valid {
+        Ok(date)
+    } else {
+        Err(anyhow!("invalid date {:?}, expected YYYY-MM-DD", date))
+    }
+}
+
+/// Whether `query` occurs in `text`, with `*` matching any run of
+/// characters.
+fn matches_query(text: &str, query: &str) -> bool {
+    let mut rest = text;
+    for part in query.split('*') {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// What changed between two version lists, newest first. See
+/// [`VersionList::diff`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VersionListDiff {
+    pub added: Vec<Version>,
+    pub removed: Vec<Version>,
+}
+
+impl VersionListDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+
+    /// `versions` with the mono and standard builds of a release folded
+    /// into one entry, e.g. `4.3-beta1 (mono and standard)`.
+    fn summarize(versions: &[Version]) -> String {
+        let mut releases: Vec<(Version, bool, bool)> = Vec::new();
+        for v in versions {
+            let release = Version {
+                is_mono: false,
+                ..v.clone()
+            };
+            match releases.iter_mut().find(|(r, ..)| *r == release) {
+                Some((_, standard, mono)) => {
+                    *standard |= !v.is_mono;
+                    *mono |= v.is_mono;
+                }
+                None => releases.push((release, !v.is_mono, v.is_mono)),
+            }
+        }
+        releases
+            .into_iter()
+            .map(|(release, standard, mono)| match (standard, mono) {
+                (true, true) => format!("{} (mono and standard)", release.short()),
+                (false, true) => format!("{} (mono)", release.short()),
+                _ => release.short(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl Display for VersionListDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return f.write_str("No new versions");
+        }
+        if !self.added.is_empty() {
+            write!(f, "New: {}", Self::summarize(&self.added))?;
+        }
+        if !self.removed.is_empty() {
+            if !self.added.is_empty() {
+                f.write_str("\n")?;
+            }
+            write!(f, "Removed: {}", Self::summarize(&self.removed))?;
+        }
+        Ok(())
+    }
+}
+
+/// Rewrites a `mono@`-prefixed spec into the equivalent `-mono` form.
+fn with_flavor_prefix(spec: &str) -> Result<String> {
+    let Some((prefix, body)) = spec.trim().split_once('@') else {
+        return Ok(spec.to_string());
+    };
+    if !prefix.eq_ignore_ascii_case("mono") {
+        return Err(anyhow!(
+            "unknown prefix `{}@` in version spec `{}`: expected mono@VERSION, e.g. mono@4.2.1",
+            prefix,
+            spec
+        ));
+    }
+    let body = body.trim();
+    Ok(match body {
+        "latest" => "latest-mono".to_string(),
+        "latest-prerelease" => "latest-prerelease-mono".to_string(),
+        "latest-dev" => "latest-dev-mono".to_string(),
+        _ if body.ends_with("-mono") || body.contains('@') => body.to_string(),
+        _ => format!("{}-mono", body),
+    })
+}
+
+/// A spec nothing matched, with up to three of the closest available
+/// versions as suggestions. Returned inside [`anyhow::Error`], so callers
+/// can `downcast_ref` to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionNotFound {
+    pub spec: String,
+    pub platform: Platform,
+    pub searched: usize,
+    pub suggestions: Vec<Version>,
+}
+
+impl Display for VersionNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "no version matching `{}` among {} versions available for {:?}",
+            self.spec, self.searched, self.platform
+        )?;
+        let suggestions = self
+            .suggestions
+            .iter()
+            .map(|v| v.short())
+            .collect::<Vec<_>>();
+        match suggestions.as_slice() {
+            [] => Ok(()),
+            [only] => write!(f, "; did you mean {}?", only),
+            [init @ .., last] => write!(f, "; did you mean {} or {}?", init.join(", "), last),
+        }
+    }
+}
+
+impl std::error::Error for VersionNotFound {}
+
+/// Resolves `spec` among `available`, the versions for `platform`
+/// sorted oldest first, as described at [`VersionList::resolve_with`].
+pub fn resolve_among(
+    available: &[&Version],
+    spec: &str,
+    platform: Platform,
+    prereleases: bool,
+) -> Result<Version> {
+    let spec = &with_flavor_prefix(spec)?;
+    let newest = || available.iter().rev().copied();
+    let latest = match (spec.trim(), prereleases) {
+        ("latest", false) => Some(newest().find(|v| v.channel() == Channel::Stable && !v.is_mono)),
+        ("latest", true) | ("latest-prerelease", _) => {
+            Some(newest().find(|v| !v.is_mono && v.channel() != Channel::Dev))
+        }
+        ("latest-dev", _) => Some(newest().find(|v| !v.is_mono)),
+        ("latest-mono", false) => Some(newest().find(|v| !v.is_prerelease() && v.is_mono)),
+        ("latest-mono", true) | ("latest-prerelease-mono", _) => {
+            Some(newest().find(|v| v.is_mono && v.channel() != Channel::Dev))
+        }
+        ("latest-dev-mono", _) => Some(newest().find(|v| v.is_mono)),
+        _ => None,
+    };
+    if let Some(latest) = latest {
+        return latest
+            .cloned()
+            .ok_or_else(|| not_found(spec, platform, available.len(), &[]));
+    }
+    if VersionReq::is_requirement(spec) {
+        let req = VersionReq::parse(spec)?;
+        if let Some(version) = newest().find(|v| req.matches(v)) {
+            return Ok(version.clone());
+        }
+        let mut candidates = available.to_vec();
+        candidates.sort_by_key(|v| (v.is_mono != req.is_mono, Reverse(*v)));
+        return Err(not_found(spec, platform, available.len(), &candidates));
+    }
+    let pattern = VersionPattern::parse(spec)?;
+    let matching = available.iter().filter(|v| pattern.matches(v));
+    let best = match pattern.kind {
+        Some(_) => matching.max(),
+        None => matching
+            .filter(|v| v.channel() != Channel::Dev)
+            .max_by_key(|v| (!prereleases && !v.is_prerelease(), **v)),
+    };
+    if let Some(version) = best {
+        return Ok((*version).clone());
+    }
+    let mut candidates = available.to_vec();
+    candidates.sort_by_key(|v| (pattern.distance(v), Reverse(*v)));
+    Err(not_found(spec, platform, available.len(), &candidates))
+}
+
+/// Error for a spec nothing matched, naming the best few `candidates`.
+fn not_found(
+    spec: &str,
+    platform: Platform,
+    searched: usize,
+    candidates: &[&Version],
+) -> anyhow::Error {
+    VersionNotFound {
+        spec: spec.trim().to_string(),
+        platform,
+        searched,
+        suggestions: candidates.iter().take(3).map(|v| (*v).clone()).collect(),
+    }
+    .into()
+}
+
+/// Number of single-character edits turning `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+impl FromIterator<(Build, String)> for VersionList {
+    fn from_iter<T: IntoIterator<Item = (Build, String)>>(iter: T) -> Self {
+        VersionList {
+            builds: iter
+                .into_iter()
+                .map(|(build, url)| (build, Artifact::from_url(url)))
+                .collect(),
+            releases: HashMap::new(),
+            local: HashSet::new(),
+        }
+    }
+}
+
+/// A partially specified version, see [`VersionList::resolve`].
+struct VersionPattern {
+    text: String,
+    major: u8,
+    minor: Option<u8>,
+    patch: Option<u8>,
+    kind: Option<&'static str>,
+    number: Option<u8>,
+    is_mono: bool,
+}
+
+impl VersionPattern {
+    fn parse(spec: &str) -> Result<VersionPattern> {
+        let invalid = || {
+            anyhow!(
+                "invalid version spec `{}`: expected [mono@]MAJOR[.MINOR[.PATCH]][-CHANNEL[N]][-mono], e.g. 4, 4.1, 4.1-rc or mono@4.2",
+                spec
+            )
+        };
+        let body = spec.trim();
+        let (body, is_mono) = match body.strip_suffix("-mono") {
+            Some(body) => (body, true),
+            None => (body, false),
+        };
+        let (numbers, kind, number) = match body.split_once('-') {
+            Some((numbers, suffix)) => {
+                let suffix = suffix.to_ascii_lowercase();
+                let (kind, number) = suffix.split_at(
+                    suffix
+                        .find(|c: char| c.is_ascii_digit())
+                        .unwrap_or(suffix.len()),
+                );
+                let kind = Suffix::KINDS
+                    .into_iter()
+                    .find(|k| *k == kind)
+                    .ok_or_else(invalid)?;
+                let number = match number {
+                    "" => None,
+                    _ if kind == "stable" => return Err(invalid()),
+                    n => Some(n.parse::<u8>().map_err(|_| invalid())?),
+                };
+                (numbers, Some(kind), number)
+            }
+            None => (body, None, None),
+        };
+        let numbers = numbers
+            .split('.')
+            .map(|n| n.parse::<u8>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>>>()?;
+        let (major, minor, patch) = match numbers[..] {
+            [major] => (major, None, None),
+            [major, minor] => (major, Some(minor), None),
+            [major, minor, patch] => (major, Some(minor), Some(patch)),
+            _ => return Err(invalid()),
+        };
+        Ok(VersionPattern {
+            text: body.to_ascii_lowercase(),
+            major,
+            minor,
+            patch,
+            kind,
+            number,
+            is_mono,
+        })
+    }
+
+    fn matches(&self, v: &Version) -> bool {
+        v.major == self.major
+            && self.minor.is_none_or(|minor| v.minor == minor)
+            && self.patch.is_none_or(|patch| v.patch == patch)
+            && self.kind.is_none_or(|kind| v.suffix.kind() == kind)
+            && self.number.is_none_or(|n| v.suffix.number() == Some(n))
+            && v.is_mono == self.is_mono
+    }
+
+    /// How far `v` is from what was asked for, used to rank suggestions:
+    /// same flavor and major first, then likely typos by edit distance,
+    /// then the nearest minor and patch.
+    fn distance(&self, v: &Version) -> (bool, u8, usize, u8, u8) {
+        let mut spelled = v.versnum_to_str();
+        if v.is_prerelease() {
+            spelled.push_str(&format!("-{}", v.suffix));
+        }
+        (
+            v.is_mono != self.is_mono,
+            v.major.abs_diff(self.major),
+            edit_distance(&self.text, &spelled),
+            self.minor.map_or(0, |minor| v.minor.abs_diff(minor)),
+            self.patch.map_or(0, |patch| v.patch.abs_diff(patch)),
+        )
+    }
+}
+
+/// A semver-style version requirement such as `^4.1`, `~4.2`,
+/// `>=4.0,<4.3`, `4.2.*` or `*`, optionally followed by `-mono`.
+///
+/// A bare version (`4.2.1`) is treated like `^4.2.1`. As with semver,
+/// prereleases only match when a comparator names a prerelease of the
+/// same `major.minor.patch`, e.g. `>=4.2.0-rc1`. Godot has no 0.x
+/// releases, so `^` always allows any newer version of the same major.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionReq {
+    comparators: Vec<Comparator>,
+    pub is_mono: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+    Tilde,
+    Caret,
+    Wildcard,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Comparator {
+    op: Op,
+    major: u8,
+    minor: Option<u8>,
+    patch: Option<u8>,
+    suffix: Option<Suffix>,
+}
+
+impl VersionReq {
+    pub fn parse(s: &str) -> Result<VersionReq> {
+        s.parse()
+    }
+
+    /// Whether `spec` uses requirement syntax rather than a plain version.
+    pub fn is_requirement(spec: &str) -> bool {
+        spec.trim_start()
+            .starts_with(['=', '>', '<', '~', '^', '*'])
+            || spec.contains([',', '*'])
+    }
+
+    pub fn matches(&self, v: &Version) -> bool {
+        v.is_mono == self.is_mono
+            && self.comparators.iter().all(|c| c.matches(v))
+            && (v.suffix == Suffix::Stable
+                || self.comparators.iter().any(|c| {
+                    c.suffix.is_some()
+                        && (c.major, c.minor, c.patch) == (v.major, Some(v.minor), Some(v.patch))
+                }))
+    }
+}
+
+impl Comparator {
+    fn parse(s: &str, req: &str) -> Result<Comparator> {
+        let invalid = || {
+            anyhow!(
+                "invalid version requirement `{}`: expected comparators like ^4.1, ~4.2, >=4.0,<4.3, 4.2.* or *",
+                req
+            )
+        };
+        let s = s.trim();
+        let (op, rest) = [
+            (">=", Op::GreaterEq),
+            ("<=", Op::LessEq),
+            ("=", Op::Exact),
+            (">", Op::Greater),
+            ("<", Op::Less),
+            ("~", Op::Tilde),
+            ("^", Op::Caret),
+        ]
+        .into_iter()
+        .find_map(|(prefix, op)| s.strip_prefix(prefix).map(|rest| (Some(op), rest.trim())))
+        .unwrap_or((None, s));
+        let (numbers, suffix) = match rest.split_once('-') {
+            Some((numbers, suffix)) => (numbers, Some(suffix.parse::<Suffix>()?)),
+            None => (rest, None),
+        };
+        let parts = numbers.split('.').collect::<Vec<_>>();
+        if parts.len() > 3 {
+            return Err(invalid());
+        }
+        let mut numbers = [None; 3];
+        let mut wildcard = false;
+        for (i, part) in parts.into_iter().enumerate() {
+            if matches!(part, "*" | "x" | "X") {
+                wildcard = true;
+            } else if wildcard {
+                return Err(invalid());
+            } else {
+                numbers[i] = Some(part.parse::<u8>().map_err(|_| invalid())?);
+            }
+        }
+        let [major, minor, patch] = numbers;
+        let major = major.ok_or_else(invalid)?;
+        // `4.2-rc1` is 4.2.0-rc1, as everywhere else.
+        let patch = match (suffix, minor, patch) {
+            (Some(_), None, _) => return Err(invalid()),
+            (Some(_), Some(_), None) if !wildcard => Some(0),
+            (Some(_), _, None) => return Err(invalid()),
+            _ => patch,
+        };
+        let op = match op {
+            Some(op) if !wildcard => op,
+            None if wildcard => Op::Wildcard,
+            None => Op::Caret,
+            Some(_) => return Err(invalid()),
+        };
+        Ok(Comparator {
+            op,
+            major,
+            minor,
+            patch,
+            suffix,
+        })
+    }
+
+    fn matches(&self, v: &Version) -> bool {
+        let bound = (
+            self.major,
+            self.minor.unwrap_or(0),
+            self.patch.unwrap_or(0),
+            self.suffix.unwrap_or(Suffix::Stable),
+        );
+        let key = (v.major, v.minor, v.patch, v.suffix);
+        let same_major = v.major == self.major;
+        let same_minor = same_major && self.minor.is_none_or(|minor| v.minor == minor);
+        match self.op {
+            Op::Exact | Op::Wildcard => match (self.minor, self.patch) {
+                (None, _) => same_major,
+                (Some(_), None) => same_minor,
+                (Some(_), Some(_)) => key == bound,
+            },
+            Op::Greater => match (self.minor, self.patch) {
+                (None, _) => v.major > self.major,
+                (Some(minor), None) => (v.major, v.minor) > (self.major, minor),
+                (Some(_), Some(_)) => key > bound,
+            },
+            Op::GreaterEq => key >= bound,
+            Op::Less => match (self.minor, self.patch) {
+                (None, _) => v.major < self.major,
+                (Some(minor), None) => (v.major, v.minor) < (self.major, minor),
+                (Some(_), Some(_)) => key < bound,
+            },
+            Op::LessEq => match (self.minor, self.patch) {
+                (None, _) => v.major <= self.major,
+                (Some(minor), None) => (v.major, v.minor) <= (self.major, minor),
+                (Some(_), Some(_)) => key <= bound,
+            },
+            Op::Tilde => key >= bound && same_minor,
+            Op::Caret => key >= bound && same_major,
+        }
+    }
+}
+
+impl FromStr for VersionReq {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let body = s.trim();
+        let (body, is_mono) = match body.strip_suffix("-mono") {
+            Some(body) => (body, true),
+            None => (body, false),
+        };
+        if body == "*" {
+            return Ok(VersionReq {
+                comparators: Vec::new(),
+                is_mono,
+            });
+        }
+        let comparators = body
+            .split(',')
+            .map(|c| Comparator::parse(c, s))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(VersionReq {
+            comparators,
+            is_mono,
+        })
+    }
+}
+
+impl Display for VersionReq {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.comparators.is_empty() {
+            f.write_str("*")?;
+        }
+        for (i, c) in self.comparators.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            let op = match c.op {
+                Op::Exact => "=",
+                Op::Greater => ">",
+                Op::GreaterEq => ">=",
+                Op::Less => "<",
+                Op::LessEq => "<=",
+                Op::Tilde => "~",
+                Op::Caret => "^",
+                Op::Wildcard => "",
+            };
+            write!(f, "{}{}", op, c.major)?;
+            match (c.minor, c.patch) {
+                (Some(minor), Some(patch)) => write!(f, ".{}.{}", minor, patch)?,
+                (Some(minor), None) => write!(f, ".{}", minor)?,
+                (None, _) => {}
+            }
+            if c.op == Op::Wildcard {
+                f.write_str(".*")?;
+            }
+            if let Some(suffix) = c.suffix {
+                write!(f, "-{}", suffix)?;
+            }
+        }
+        if self.is_mono {
+            f.write_str("-mono")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads `project.godot` (or the one inside the project directory `path`)
+/// and returns the engine requirement it implies: `~4.2` for a Godot 4
+/// project listing `"4.2"` in `config/features`, `~4` when that array is
+/// missing and `~3` for Godot 3 projects. Projects with the `C#` feature
+/// require a mono build.
+pub fn detect_project_version(path: &Path) -> Result<VersionReq> {
+    let file = if path.is_dir() {
+        path.join("project.godot")
+    } else {
+        path.to_path_buf()
+    };
+    let text = fs::read_to_string(&file)
+        .with_context(|| format!("could not read Godot project file {}", file.display()))?;
+    parse_project_version(&text)
+        .with_context(|| format!("could not detect the Godot version of {}", file.display()))
+}
+
+fn parse_project_version(text: &str) -> Result<VersionReq> {
+    let mut config_version = None;
+    let mut features = None;
+    for line in text.lines().map(str::trim) {
+        if let Some(value) = line.strip_prefix("config_version=") {
+            config_version = Some(
+                value
+                    .parse::<u8>()
+                    .map_err(|_| anyhow!("invalid config_version `{}`", value))?,
+            );
+        } else if let Some(value) = line.strip_prefix("config/features=") {
+            features = Some(value);
+        }
+    }
+    let config_version = config_version
+        .ok_or_else(|| anyhow!("no config_version found, is this a Godot project file?"))?;
+    match config_version {
+        3 | 4 => return VersionReq::parse("~3"),
+        5 => {}
+        n => {
+            return Err(anyhow!(
+                "unsupported config_version {}: only Godot 3 and 4 projects are recognized",
+                n
+            ))
+        }
+    }
+    let Some(features) = features else {
+        return VersionReq::parse("~4");
+    };
+    let features = features
+        .strip_prefix("PackedStringArray(")
+        .and_then(|f| f.strip_suffix(')'))
+        .ok_or_else(|| anyhow!("malformed config/features `{}`", features))?
+        .split(',')
+        .map(|f| f.trim().trim_matches('"'))
+        .collect::<Vec<_>>();
+    let version = features
+        .iter()
+        .find(|f| f.starts_with(|c: char| c.is_ascii_digit()))
+        .ok_or_else(|| anyhow!("config/features does not name an engine version"))?;
+    let (major, minor) = version
+        .split_once('.')
+        .and_then(|(major, minor)| Some((major.parse::<u8>().ok()?, minor.parse::<u8>().ok()?)))
+        .ok_or_else(|| anyhow!("invalid engine version `{}` in config/features", version))?;
+    let mono = if features.contains(&"C#") {
+        "-mono"
+    } else {
+        ""
+    };
+    VersionReq::parse(&format!("~{}.{}{}", major, minor, mono))
+}
+
+#[derive(Deserialize)]
+struct VersionListV1 {
+    builds: HashMap<Build, String>,
+}
+
+/// The original format, keyed by version and platform as complex YAML
+/// mapping keys. Still read so caches from before the v2 schema load.
+#[derive(Deserialize)]
+struct LegacyVersionList {
+    versions: HashMap<LegacyVersion, String>,
+}
+
+#[derive(Deserialize, PartialEq, Eq, Hash)]
+struct LegacyVersion {
+    major: u8,
+    minor: u8,
+    patch: u8,
+    suffix: Suffix,
+    is_mono: bool,
+    platform: Platform,
+}
+
+impl From<LegacyVersionList> for VersionList {
+    fn from(legacy: LegacyVersionList) -> Self {
+        legacy
+            .versions
+            .into_iter()
+            .map(|(v, url)| {
+                let version = Version {
+                    major: v.major,
+                    minor: v.minor,
+                    patch: v.patch,
+                    suffix: v.suffix,
+                    is_mono: v.is_mono,
+                };
+                (
+                    Build {
+                        version,
+                        platform: v.platform,
+                    },
+                    url,
+                )
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Platform {
+    Win32,
+    Win64,
+    Linux32,
+    Linux64,
+    /// Universal build covering both x86_64 and arm64 Macs.
+    Macos,
+    WinArm64,
+    LinuxArm32,
+    LinuxArm64,
+}
+
+/// Release channel of a [`Version`], i.e. its [`Suffix`] without the number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    Stable,
+    Rc,
+    Beta,
+    Alpha,
+    Dev,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Suffix {
+    Stable,
+    Dev(u8),
+    Alpha(u8),
+    Beta(u8),
+    Rc(u8),
+}
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct Version {
+    pub major: u8,
+    pub minor: u8,
+    pub patch: u8,
+    pub suffix: Suffix,
+    pub is_mono: bool,
+}
+
+/// A concrete artifact of a [`Version`] built for one platform.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Build {
+    pub version: Version,
+    pub platform: Platform,
+}
+
+impl Build {
+    /// Filesystem-safe identifier unique per build, e.g.
+    /// `4.0.3-stable-mono-linux64`.
+    pub fn slug(&self) -> String {
+        format!("{}-{}", self.version.slug(), self.platform.id())
+    }
+
+    /// Recognizes an upstream archive name, the inverse of
+    /// [`Build::to_filename`]. Anything else, such as export templates or
+    /// checksum files, yields `None`.
+    pub fn from_filename(name: &str) -> Option<Build> {
+        let (version, tail) = name.strip_prefix("Godot_v")?.split_once('_')?;
+        let mut version = Version::parse(version).ok()?;
+        version.is_mono = tail.starts_with("mono_");
+        Platform::ALL
+            .into_iter()
+            .map(|platform| Build {
+                version: version.clone(),
+                platform,
+            })
+            .find(|build| build.to_filename() == name)
+    }
+
+    /// Upstream archive name, e.g. `Godot_v4.0.3-stable_linux.x86_64.zip`.
+    /// Mono archives spell Linux platforms with underscores and drop the
+    /// `.exe` on Windows: `Godot_v4.2.1-stable_mono_linux_x86_64.zip`.
+    pub fn to_filename(&self) -> String {
+        let suffix = self.platform.archive_suffix(&self.version);
+        let suffix = match self.platform {
+            _ if !self.version.is_mono => suffix.to_string(),
+            Platform::Win32 | Platform::Win64 | Platform::WinArm64 => {
+                suffix.trim_end_matches(".exe").to_string()
+            }
+            Platform::Linux32 | Platform::Linux64 | Platform::LinuxArm32 | Platform::LinuxArm64 => {
+                suffix.replace('.', "_")
+            }
+            Platform::Macos => suffix.to_string(),
+        };
+        format!("{}_{}.zip", self.version, suffix)
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mono_str = if self.is_mono { "_mono" } else { "" };
+        write!(
+            f,
+            "Godot_v{}-{}{}",
+            self.versnum_to_upstream_str(),
+            self.suffix,
+            mono_str
+        )
+    }
+}
+
+impl Display for Suffix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.number() {
+            Some(x) => write!(f, "{}{}", self.kind(), x),
+            None => f.write_str(self.kind()),
+        }
+    }
+}
+
+impl Suffix {
+    pub const KINDS: [&'static str; 5] = ["stable", "dev", "alpha", "beta", "rc"];
+
+    /// The release kind without its number, e.g. `rc` for `rc3`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Suffix::Stable => "stable",
+            Suffix::Dev(_) => "dev",
+            Suffix::Alpha(_) => "alpha",
+            Suffix::Beta(_) => "beta",
+            Suffix::Rc(_) => "rc",
+        }
+    }
+
+    pub fn number(&self) -> Option<u8> {
+        match *self {
+            Suffix::Stable => None,
+            Suffix::Dev(x) | Suffix::Alpha(x) | Suffix::Beta(x) | Suffix::Rc(x) => Some(x),
+        }
+    }
+
+    /// Rank of the release kind: dev < alpha < beta < rc < stable.
+    fn rank(&self) -> (u8, u8) {
+        match *self {
+            Suffix::Dev(x) => (0, x),
+            Suffix::Alpha(x) => (1, x),
+            Suffix::Beta(x) => (2, x),
+            Suffix::Rc(x) => (3, x),
+            Suffix::Stable => (4, 0),
+        }
+    }
+}
+
+impl Ord for Suffix {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+impl PartialOrd for Suffix {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Versions are ordered by major, minor, patch and suffix. Builds of the
+/// same release are tie-broken so that the standard build sorts before
+/// the mono build.
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch, self.suffix)
+            .cmp(&(other.major, other.minor, other.patch, other.suffix))
+            .then(self.is_mono.cmp(&other.is_mono))
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl FromStr for Suffix {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.to_ascii_lowercase();
+        if s == "stable" {
+            return Ok(Suffix::Stable);
+        }
+        let (kind, number) = s.split_at(s.find(|c: char| c.is_ascii_digit()).unwrap_or(s.len()));
+        let number = number
+            .parse::<u8>()
+            .map_err(|_| anyhow!("invalid suffix `{}`: expected a number after `{}`", s, kind));
+        match kind {
+            "dev" => Ok(Suffix::Dev(number?)),
+            "alpha" => Ok(Suffix::Alpha(number?)),
+            "beta" => Ok(Suffix::Beta(number?)),
+            "rc" => Ok(Suffix::Rc(number?)),
+            _ => Err(anyhow!(
+                "unknown suffix `{}`: expected stable, devN, alphaN, betaN or rcN",
+                s
+            )),
+        }
+    }
+}
+
+impl FromStr for Channel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "stable" => Ok(Channel::Stable),
+            "rc" => Ok(Channel::Rc),
+            "beta" => Ok(Channel::Beta),
+            "alpha" => Ok(Channel::Alpha),
+            "dev" => Ok(Channel::Dev),
+            _ => Err(anyhow!(
+                "unknown channel `{}`: expected stable, rc, beta, alpha or dev",
+                s
+            )),
+        }
+    }
+}
+
+/// Suffixes are stored as their display form, e.g. `stable` or `rc3`.
+impl Serialize for Suffix {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Accepts the display form as well as the externally tagged form older
+/// lists were written in (`Stable`, `!Rc 3`).
+impl<'de> Deserialize<'de> for Suffix {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(SuffixVisitor)
+    }
+}
+
+struct SuffixVisitor;
+
+impl<'de> de::Visitor<'de> for SuffixVisitor {
+    type Value = Suffix;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a suffix such as stable, dev5, alpha11, beta2 or rc3")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Suffix, E> {
+        v.parse().map_err(E::custom)
+    }
+
+    fn visit_enum<A: de::EnumAccess<'de>>(self, data: A) -> Result<Suffix, A::Error> {
+        use de::VariantAccess;
+        let (kind, variant) = data.variant::<String>()?;
+        if kind == "Stable" {
+            variant.unit_variant()?;
+            return Ok(Suffix::Stable);
+        }
+        let number = variant.newtype_variant::<u8>()?;
+        format!("{}{}", kind, number)
+            .parse()
+            .map_err(de::Error::custom)
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Suffix, A::Error> {
+        let (kind, number) = map
+            .next_entry::<String, u8>()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        format!("{}{}", kind, number)
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl FromStr for Version {
+    type Err = anyhow::Error;
+
+    /// Parses strings like `4.1.2`, `4.2-stable`, `4.0.3-rc3`,
+    /// `4.2.0-beta1-mono` or the `Display` form `Godot_v4.0.3-stable_mono`.
+    /// A missing patch component means `.0`.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            anyhow!(
+                "invalid version `{}`: expected MAJOR.MINOR[.PATCH][-SUFFIX][-mono], e.g. 4.1.2 or 4.0.3-rc3",
+                s
+            )
+        };
+        let body = s.trim();
+        let body = body.strip_prefix("Godot_v").unwrap_or(body);
+        let (body, is_mono) = match body
+            .strip_suffix("-mono")
+            .or_else(|| body.strip_suffix("_mono"))
+        {
+            Some(body) => (body, true),
+            None => (body, false),
+        };
+        let (numbers, suffix) = match body.split_once('-') {
+            Some((numbers, suffix)) => (
+                numbers,
+                suffix
+                    .parse()
+                    .map_err(|e: anyhow::Error| e.context(invalid()))?,
+            ),
+            None => (body, Suffix::Stable),
+        };
+        let numbers = numbers
+            .split('.')
+            .map(|n| n.parse::<u8>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>>>()?;
+        let (major, minor, patch) = match numbers[..] {
+            [major, minor] => (major, minor, 0),
+            [major, minor, patch] => (major, minor, patch),
+            _ => return Err(invalid()),
+        };
+        Ok(Version {
+            major,
+            minor,
+            patch,
+            suffix,
+            is_mono,
+        })
+    }
+}
+
+impl Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl FromStr for Platform {
+    type Err = anyhow::Error;
+
+    /// Parses the lowercase identifier, ignoring case so that lists
+    /// written with the old variant names (`Win64`) still load.
+    fn from_str(s: &str) -> Result<Self> {
+        let id = s.to_ascii_lowercase();
+        Platform::ALL
+            .into_iter()
+            .find(|p| p.id() == id)
+            .ok_or_else(|| {
+                anyhow!(
+                    "unknown platform `{}`: expected win32, win64, winarm64, linux32, linux64, linuxarm32, linuxarm64 or macos",
+                    s
+                )
+            })
+    }
+}
+
+impl Serialize for Platform {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.id())
+    }
+}
+
+impl<'de> Deserialize<'de> for Platform {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl Platform {
+    pub const ALL: [Platform; 8] = [
+        Platform::Win32,
+        Platform::Win64,
+        Platform::Linux32,
+        Platform::Linux64,
+        Platform::Macos,
+        Platform::WinArm64,
+        Platform::LinuxArm32,
+        Platform::LinuxArm64,
+    ];
+
+    /// Lowercase identifier used in version lists, e.g. `win64`.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Platform::Win32 => "win32",
+            Platform::Win64 => "win64",
+            Platform::Linux32 => "linux32",
+            Platform::Linux64 => "linux64",
+            Platform::Macos => "macos",
+            Platform::WinArm64 => "winarm64",
+            Platform::LinuxArm32 => "linuxarm32",
+            Platform::LinuxArm64 => "linuxarm64",
+        }
+    }
+
+    /// How upstream archive names spell the platform for `version`, e.g.
+    /// `win64.exe`. Godot 3 used `x11.64` and `osx.universal`; the 4.0
+    /// snapshots up to alpha14 still used `linux.64` before the
+    /// `linux.x86_64` / `macos.universal` names settled.
+    pub fn archive_suffix(&self, version: &Version) -> &'static str {
+        let legacy_4 = version.major == 4
+            && version.minor == 0
+            && version.patch == 0
+            && match version.suffix {
+                Suffix::Dev(_) => true,
+                Suffix::Alpha(n) => n < 15,
+                _ => false,
+            };
+        match (self, version.major) {
+            (Platform::Win32, _) => "win32.exe",
+            (Platform::Win64, _) => "win64.exe",
+            (Platform::WinArm64, _) => "windows_arm64.exe",
+            (Platform::Linux32, ..=3) => "x11.32",
+            (Platform::Linux64, ..=3) => "x11.64",
+            (Platform::Macos, ..=3) => "osx.universal",
+            (Platform::Linux32, _) if legacy_4 => "linux.32",
+            (Platform::Linux64, _) if legacy_4 => "linux.64",
+            (Platform::Macos, _) if legacy_4 => "osx.universal",
+            (Platform::Linux32, _) => "linux.x86_32",
+            (Platform::Linux64, _) => "linux.x86_64",
+            (Platform::Macos, _) => "macos.universal",
+            (Platform::LinuxArm32, _) => "linux.arm32",
+            (Platform::LinuxArm64, _) => "linux.arm64",
+        }
+    }
+
+    /// Whether the platform is Windows, whose builds come with a console
+    /// wrapper next to the editor.
+    pub fn is_windows(&self) -> bool {
+        matches!(self, Platform::Win32 | Platform::Win64 | Platform::WinArm64)
+    }
+
+    /// Whether builds for the platform ship as an `.app` bundle rather
+    /// than a bare executable.
+    pub fn is_app_bundle(&self) -> bool {
+        matches!(self, Platform::Macos)
+    }
+
+    /// The platform godotup is currently running on.
+    pub fn host() -> Result<Platform> {
+        match (env::consts::OS, get_arch()) {
+            ("windows", "x86_32") => Ok(Platform::Win32),
+            ("windows", "x86_64") => Ok(Platform::Win64),
+            ("linux", "x86_32") => Ok(Platform::Linux32),
+            ("linux", "x86_64") => Ok(Platform::Linux64),
+            ("windows", "arm64") => Ok(Platform::WinArm64),
+            ("linux", "arm32") => Ok(Platform::LinuxArm32),
+            ("linux", "arm64") => Ok(Platform::LinuxArm64),
+            ("macos", _) => Ok(Platform::Macos),
+            (os, arch) => Err(anyhow!(
+                "godotup is not available on {} ({}) currently.",
+                os,
+                arch
+            )),
+        }
+    }
+}
+
+impl Version {
+    pub fn parse(s: &str) -> Result<Version> {
+        s.parse()
+    }
+    /// Parses what a Godot binary prints for `--version`, e.g.
+    /// `4.2.1.stable.mono.official.b09f793f5` or
+    /// `3.5.2.stable.official.170ba337a`. The build name and commit hash
+    /// are ignored, as is any output before the version line.
+    pub fn from_engine_string(s: &str) -> Result<Version> {
+        let invalid = || {
+            anyhow!(
+                "unrecognized engine version `{}`: expected MAJOR.MINOR[.PATCH].STATUS[.mono].BUILD[.HASH], e.g. 4.2.1.stable.official.b09f793f5",
+                s.trim()
+            )
+        };
+        let line = s
+            .lines()
+            .map(str::trim)
+            .find(|l| l.starts_with(|c: char| c.is_ascii_digit()))
+            .ok_or_else(invalid)?;
+        let mut parts = line.split('.').peekable();
+        let mut numbers = Vec::new();
+        while let Some(part) = parts.next_if(|p| p.starts_with(|c: char| c.is_ascii_digit())) {
+            numbers.push(part.parse::<u8>().map_err(|_| invalid())?);
+        }
+        let (major, minor, patch) = match numbers[..] {
+            [major, minor] => (major, minor, 0),
+            [major, minor, patch] => (major, minor, patch),
+            _ => return Err(invalid()),
+        };
+        let suffix = parts
+            .next()
+            .ok_or_else(invalid)?
+            .parse::<Suffix>()
+            .map_err(|e| e.context(invalid()))?;
+        let is_mono = parts.next() == Some("mono");
+        Ok(Version {
+            major,
+            minor,
+            patch,
+            suffix,
+            is_mono,
+        })
+    }
+    /// Normalized `major.minor.patch` triple, e.g. `4.2.0`.
+    pub fn versnum_to_str(&self) -> String {
+        format!("{}.{}.{}", self.major, self.minor, self.patch)
+    }
+    /// Version number as upstream spells it in archive names: `.0` patch
+    /// releases drop the patch component (`4.2`, but `4.2.1`).
+    pub fn versnum_to_upstream_str(&self) -> String {
+        if self.patch == 0 {
+            format!("{}.{}", self.major, self.minor)
+        } else {
+            self.versnum_to_str()
+        }
+    }
+    /// Name of the upstream git tag, e.g. `4.2-stable` or `4.3-beta1`.
+    pub fn release_tag(&self) -> String {
+        format!("{}-{}", self.versnum_to_upstream_str(), self.suffix)
+    }
+    pub fn channel(&self) -> Channel {
+        match self.suffix {
+            Suffix::Stable => Channel::Stable,
+            Suffix::Rc(_) => Channel::Rc,
+            Suffix::Beta(_) => Channel::Beta,
+            Suffix::Alpha(_) => Channel::Alpha,
+            Suffix::Dev(_) => Channel::Dev,
+        }
+    }
+    /// Whether this is anything but a stable release.
+    pub fn is_prerelease(&self) -> bool {
+        self.channel() != Channel::Stable
+    }
+    /// User-facing form such as `4.2`, `4.0.3-rc3` or `4.0.3 (mono)`.
+    pub fn short(&self) -> String {
+        let mut short = self.versnum_to_upstream_str();
+        if self.suffix != Suffix::Stable {
+            short.push_str(&format!("-{}", self.suffix));
+        }
+        if self.is_mono {
+            short.push_str(" (mono)");
+        }
+        short
+    }
+    /// Filesystem-safe identifier, e.g. `4.0.3-rc3-mono`. See
+    /// [`Build::slug`] for one that is unique across platforms.
+    pub fn slug(&self) -> String {
+        let mono_str = if self.is_mono { "-mono" } else { "" };
+        format!("{}-{}{}", self.versnum_to_str(), self.suffix, mono_str)
+    }
+    /// Archive name of this version for the platform godotup runs on.
+    pub fn to_filename_for_host(&self) -> Result<String> {
+        Ok(Build {
+            version: self.clone(),
+            platform: Platform::host()?,
+        }
+        .to_filename())
+    }
+}
+
+const fn get_arch() -> &'static str {
+    let _result = "unknown";
+    #[cfg(target_arch = "x86")]
+    let _result = "x86_32";
+    #[cfg(target_arch = "x86_64")]
+    let _result = "x86_64";
+    #[cfg(target_arch = "arm")]
+    let _result = "arm32";
+    #[cfg(target_arch = "aarch64")]
+    let _result = "arm64";
+    _result
+}
+
+#[test]
+fn test_display_version() {
+    let vcs = Version {
+        major: 4,
+        minor: 0,
+        patch: 3,
+        suffix: Suffix::Stable,
+        is_mono: false,
+    };
+    assert_eq!(vcs.to_string(), "Godot_v4.0.3-stable");
+    let vcs = Version {
+        suffix: Suffix::Rc(3),
+        ..vcs
+    };
+    assert_eq!(vcs.to_string(), "Godot_v4.0.3-rc3");
+    let vcs = Version {
+        suffix: Suffix::Stable,
+        is_mono: true,
+        ..vcs
+    };
+    assert_eq!(vcs.to_string(), "Godot_v4.0.3-stable_mono");
+    let vcs = Version {
+        suffix: Suffix::Alpha(11),
+        ..vcs
+    };
+    assert_eq!(vcs.to_string(), "Godot_v4.0.3-alpha11_mono");
+}
+
+#[test]
+fn test_display_dev_version() {
+    let vcs = Version {
+        major: 4,
+        minor: 0,
+        patch: 3,
+        suffix: Suffix::Dev(5),
+        is_mono: false,
+    };
+    assert_eq!(vcs.to_string(), "Godot_v4.0.3-dev5");
+    assert!(vcs
+        .to_filename_for_host()
+        .unwrap()
+        .starts_with("Godot_v4.0.3-dev5_"));
+}
+
+#[test]
+fn test_version_forms() {
+    let forms = |s, platform| {
+        let build = Build {
+            version: Version::parse(s).unwrap(),
+            platform,
+        };
+        (
+            build.version.to_string(),
+            build.version.short(),
+            build.slug(),
+        )
+    };
+    assert_eq!(
+        forms("4.0.3-mono", Platform::Linux64),
+        (
+            "Godot_v4.0.3-stable_mono".to_string(),
+            "4.0.3 (mono)".to_string(),
+            "4.0.3-stable-mono-linux64".to_string()
+        )
+    );
+    assert_eq!(
+        forms("4.2-rc1", Platform::Win64),
+        (
+            "Godot_v4.2-rc1".to_string(),
+            "4.2-rc1".to_string(),
+            "4.2.0-rc1-win64".to_string()
+        )
+    );
+    assert_eq!(
+        forms("4.2.1-alpha11-mono", Platform::Macos),
+        (
+            "Godot_v4.2.1-alpha11_mono".to_string(),
+            "4.2.1-alpha11 (mono)".to_string(),
+            "4.2.1-alpha11-mono-macos".to_string()
+        )
+    );
+    assert_eq!(forms("3.5", Platform::Win32).1, "3.5");
+    assert_ne!(
+        forms("4.2", Platform::Linux32).2,
+        forms("4.2", Platform::Linux64).2
+    );
+}
+
+#[test]
+fn test_filename_per_platform() {
+    let filename = |s, platform| {
+        Build {
+            version: Version::parse(s).unwrap(),
+            platform,
+        }
+        .to_filename()
+    };
+    for (platform, expected) in [
+        (Platform::Win32, "Godot_v4.0.3-stable_win32.exe.zip"),
+        (Platform::Win64, "Godot_v4.0.3-stable_win64.exe.zip"),
+        (Platform::Linux32, "Godot_v4.0.3-stable_linux.x86_32.zip"),
+        (Platform::Linux64, "Godot_v4.0.3-stable_linux.x86_64.zip"),
+        (Platform::Macos, "Godot_v4.0.3-stable_macos.universal.zip"),
+    ] {
+        assert_eq!(filename("4.0.3", platform), expected);
+    }
+    assert_eq!(
+        filename("4.2-rc1", Platform::Win64),
+        "Godot_v4.2-rc1_win64.exe.zip"
+    );
+    let host = Platform::host().unwrap();
+    assert_eq!(
+        Version::parse("4.0.3")
+            .unwrap()
+            .to_filename_for_host()
+            .unwrap(),
+        filename("4.0.3", host)
+    );
+}
+
+#[test]
+fn test_filename_per_era() {
+    let cases = [
+        (
+            "3.5.3",
+            Platform::Win32,
+            "Godot_v3.5.3-stable_win32.exe.zip",
+        ),
+        (
+            "3.5.3",
+            Platform::Win64,
+            "Godot_v3.5.3-stable_win64.exe.zip",
+        ),
+        ("3.5.3", Platform::Linux32, "Godot_v3.5.3-stable_x11.32.zip"),
+        ("3.5.3", Platform::Linux64, "Godot_v3.5.3-stable_x11.64.zip"),
+        (
+            "3.5.3",
+            Platform::Macos,
+            "Godot_v3.5.3-stable_osx.universal.zip",
+        ),
+        (
+            "4.0.3",
+            Platform::Win64,
+            "Godot_v4.0.3-stable_win64.exe.zip",
+        ),
+        (
+            "4.0.3",
+            Platform::Linux32,
+            "Godot_v4.0.3-stable_linux.x86_32.zip",
+        ),
+        (
+            "4.0.3",
+            Platform::Linux64,
+            "Godot_v4.0.3-stable_linux.x86_64.zip",
+        ),
+        (
+            "4.0.3",
+            Platform::Macos,
+            "Godot_v4.0.3-stable_macos.universal.zip",
+        ),
+        (
+            "4.2.1",
+            Platform::Win32,
+            "Godot_v4.2.1-stable_win32.exe.zip",
+        ),
+        (
+            "4.2.1",
+            Platform::Linux64,
+            "Godot_v4.2.1-stable_linux.x86_64.zip",
+        ),
+        (
+            "4.2.1",
+            Platform::Macos,
+            "Godot_v4.2.1-stable_macos.universal.zip",
+        ),
+        (
+            "4.0-alpha1",
+            Platform::Linux64,
+            "Godot_v4.0-alpha1_linux.64.zip",
+        ),
+        (
+            "4.0-alpha14",
+            Platform::Macos,
+            "Godot_v4.0-alpha14_osx.universal.zip",
+        ),
+        (
+            "4.0-alpha15",
+            Platform::Linux64,
+            "Godot_v4.0-alpha15_linux.x86_64.zip",
+        ),
+        (
+            "4.0-beta1",
+            Platform::Linux32,
+            "Godot_v4.0-beta1_linux.x86_32.zip",
+        ),
+    ];
+    for (version, platform, expected) in cases {
+        let build = Build {
+            version: Version::parse(version).unwrap(),
+            platform,
+        };
+        assert_eq!(build.to_filename(), expected);
+    }
+}
+
+#[test]
+fn test_arm_filename() {
+    let cases = [
+        (
+            "4.2.1",
+            Platform::LinuxArm64,
+            "Godot_v4.2.1-stable_linux.arm64.zip",
+        ),
+        (
+            "4.2.1",
+            Platform::LinuxArm32,
+            "Godot_v4.2.1-stable_linux.arm32.zip",
+        ),
+        (
+            "4.3",
+            Platform::WinArm64,
+            "Godot_v4.3-stable_windows_arm64.exe.zip",
+        ),
+        (
+            "4.2.1-mono",
+            Platform::LinuxArm64,
+            "Godot_v4.2.1-stable_mono_linux_arm64.zip",
+        ),
+        (
+            "4.3-mono",
+            Platform::WinArm64,
+            "Godot_v4.3-stable_mono_windows_arm64.zip",
+        ),
+    ];
+    for (version, platform, expected) in cases {
+        let build = Build {
+            version: Version::parse(version).unwrap(),
+            platform,
+        };
+        assert_eq!(build.to_filename(), expected);
+    }
+}
+
+#[test]
+fn test_artifact_download_name() {
+    let artifact = |url: &str, filename: &str| Artifact {
+        filename: filename.to_string(),
+        ..Artifact::from_url(url.to_string())
+    };
+    let zip = "Godot_v4.2.1-stable_linux.x86_64.zip";
+    assert_eq!(
+        artifact(&format!("https://example.org/{}", zip), zip).download_name(),
+        zip
+    );
+    assert_eq!(
+        artifact("https://example.org/custom.tar.xz?token=1", zip).download_name(),
+        "Godot_v4.2.1-stable_linux.x86_64.tar.xz"
+    );
+    assert_eq!(
+        artifact("https://example.org/custom.TGZ", "custom").download_name(),
+        "custom.tgz"
+    );
+    assert_eq!(
+        artifact("https://example.org/download?id=4", zip).download_name(),
+        zip
+    );
+}
+
+#[test]
+fn test_build_from_filename() {
+    for spec in [
+        "3.5.3",
+        "3.5.3-mono",
+        "4.0-alpha3",
+        "4.2.1",
+        "4.2.1-mono",
+        "4.3-rc2",
+    ] {
+        for platform in Platform::ALL {
+            let build = Build {
+                version: Version::parse(spec).unwrap(),
+                platform,
+            };
+            assert_eq!(Build::from_filename(&build.to_filename()), Some(build));
+        }
+    }
+    for name in [
+        "Godot_v4.2.1-stable_export_templates.tpz",
+        "Godot_v4.2.1-stable_web_editor.zip",
+        "Godot_v4.2.1-stable_linux.x86_64",
+        "godot-4.2.1-stable.tar.xz",
+        "SHA512-SUMS.txt",
+    ] {
+        assert_eq!(Build::from_filename(name), None, "{}", name);
+    }
+}
+
+#[test]
+fn test_mono_filename() {
+    let cases = [
+        (
+            "4.2.1-mono",
+            Platform::Win32,
+            "Godot_v4.2.1-stable_mono_win32.zip",
+        ),
+        (
+            "4.2.1-mono",
+            Platform::Win64,
+            "Godot_v4.2.1-stable_mono_win64.zip",
+        ),
+        (
+            "4.2.1-mono",
+            Platform::Linux32,
+            "Godot_v4.2.1-stable_mono_linux_x86_32.zip",
+        ),
+        (
+            "4.2.1-mono",
+            Platform::Linux64,
+            "Godot_v4.2.1-stable_mono_linux_x86_64.zip",
+        ),
+        (
+            "4.2.1-mono",
+            Platform::Macos,
+            "Godot_v4.2.1-stable_mono_macos.universal.zip",
+        ),
+        (
+            "4.0.3-rc2-mono",
+            Platform::Linux64,
+            "Godot_v4.0.3-rc2_mono_linux_x86_64.zip",
+        ),
+        (
+            "3.5.3-mono",
+            Platform::Linux64,
+            "Godot_v3.5.3-stable_mono_x11_64.zip",
+        ),
+        (
+            "3.5.3-mono",
+            Platform::Win64,
+            "Godot_v3.5.3-stable_mono_win64.zip",
+        ),
+        (
+            "3.5.3-mono",
+            Platform::Macos,
+            "Godot_v3.5.3-stable_mono_osx.universal.zip",
+        ),
+    ];
+    for (version, platform, expected) in cases {
+        let build = Build {
+            version: Version::parse(version).unwrap(),
+            platform,
+        };
+        assert_eq!(build.to_filename(), expected);
+    }
+}
+
+#[test]
+fn test_parse_version() {
+    let vcs = Version::parse("4.1.2").unwrap();
+    assert_eq!((vcs.major, vcs.minor, vcs.patch), (4, 1, 2));
+    assert_eq!((vcs.suffix, vcs.is_mono), (Suffix::Stable, false));
+
+    let parsed = |s| {
+        let vcs = Version::parse(s).unwrap();
+        (vcs.suffix, vcs.is_mono)
+    };
+    assert_eq!(parsed("3.5.2-stable"), (Suffix::Stable, false));
+    assert_eq!(parsed("4.0.3-rc3"), (Suffix::Rc(3), false));
+    assert_eq!(parsed("4.2.0-beta1-mono"), (Suffix::Beta(1), true));
+    assert_eq!(parsed("4.0.0-alpha11"), (Suffix::Alpha(11), false));
+    assert_eq!(parsed("4.1.0-mono"), (Suffix::Stable, true));
+    assert_eq!(parsed("4.3.0-dev5"), (Suffix::Dev(5), false));
+
+    for s in [
+        "Godot_v4.0.3-stable",
+        "Godot_v4.0.3-rc3",
+        "Godot_v4.0.3-stable_mono",
+        "Godot_v4.0.3-alpha11_mono",
+        "Godot_v4.2-beta1",
+    ] {
+        assert_eq!(Version::parse(s).unwrap().to_string(), s);
+    }
+}
+
+#[test]
+fn test_version_without_patch() {
+    let vcs = Version::parse("4.2-stable").unwrap();
+    assert_eq!((vcs.major, vcs.minor, vcs.patch), (4, 2, 0));
+    assert_eq!(vcs, Version::parse("4.2.0").unwrap());
+    assert_eq!(vcs.to_string(), "Godot_v4.2-stable");
+    assert_eq!(vcs.versnum_to_str(), "4.2.0");
+    assert!(vcs
+        .to_filename_for_host()
+        .unwrap()
+        .starts_with("Godot_v4.2-stable_"));
+
+    let vcs = Version::parse("4.2.1").unwrap();
+    assert_eq!(vcs.to_string(), "Godot_v4.2.1-stable");
+    assert_eq!(vcs.versnum_to_str(), "4.2.1");
+    assert!(vcs
+        .to_filename_for_host()
+        .unwrap()
+        .starts_with("Godot_v4.2.1-stable_"));
+
+    let vcs = Version::parse("4.3-dev5").unwrap();
+    assert_eq!(vcs.to_string(), "Godot_v4.3-dev5");
+    assert_eq!(Version::parse("Godot_v4.3-dev5").unwrap(), vcs);
+}
+
+#[test]
+fn test_from_engine_string() {
+    let parsed = |s| Version::from_engine_string(s).unwrap().to_string();
+    assert_eq!(
+        parsed("4.2.1.stable.mono.official.b09f793f5"),
+        "Godot_v4.2.1-stable_mono"
+    );
+    assert_eq!(
+        parsed("4.2.1.stable.official.b09f793f5"),
+        "Godot_v4.2.1-stable"
+    );
+    assert_eq!(
+        parsed("3.5.2.stable.official.170ba337a"),
+        "Godot_v3.5.2-stable"
+    );
+    assert_eq!(
+        parsed("3.5.3.stable.mono.official.6c814135b"),
+        "Godot_v3.5.3-stable_mono"
+    );
+    assert_eq!(parsed("4.2.stable.official.46dc27791"), "Godot_v4.2-stable");
+    assert_eq!(parsed("4.0.rc2.official.6296b4600"), "Godot_v4.0-rc2");
+    assert_eq!(parsed("4.3.dev5.official.89850d553"), "Godot_v4.3-dev5");
+    assert_eq!(parsed("4.0.alpha11.official"), "Godot_v4.0-alpha11");
+    assert_eq!(
+        parsed("4.2.1.stable.custom_build.b09f793f5\n"),
+        "Godot_v4.2.1-stable"
+    );
+    assert_eq!(
+        parsed("Godot Engine v4.2.1 - warning\n4.2.1.stable.official.b09f793f5"),
+        "Godot_v4.2.1-stable"
+    );
+
+    for s in [
+        "",
+        "Godot",
+        "4.2.1",
+        "4.stable.official",
+        "4.2.1.gamma.official",
+        "4.2.1.3.4.stable",
+        "4.300.stable.official",
+    ] {
+        let err = Version::from_engine_string(s).unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("unrecognized engine version"),
+            "{}: {:#}",
+            s,
+            err
+        );
+    }
+}
+
+#[test]
+fn test_parse_version_rejects_garbage() {
+    for s in [
+        "",
+        "4",
+        "4.1.2.3",
+        "4.x.2",
+        "4.1.2-",
+        "4.1.2-rc",
+        "4.1.2-gamma1",
+        "4.1.256",
+        "hello",
+    ] {
+        let err = Version::parse(s).unwrap_err();
+        assert!(
+            format!("{:#}", err).contains(&format!("`{}`", s)),
+            "{:#}",
+            err
+        );
+    }
+}
+
+#[test]
+fn test_version_ordering() {
+    let parse = |s| Version::parse(s).unwrap();
+    let mut versions = [
+        "4.2.0-rc2",
+        "3.5.2",
+        "4.2.0-beta8",
+        "4.0.0-dev6",
+        "4.2.0-mono",
+        "4.0.0-alpha11",
+        "4.2.0",
+        "4.0.0-alpha2",
+        "4.1.10",
+        "4.2.0-rc1",
+        "4.1.2",
+    ]
+    .map(parse);
+    versions.sort();
+    let sorted = versions.map(|v| v.to_string());
+    assert_eq!(
+        sorted,
+        [
+            "Godot_v3.5.2-stable",
+            "Godot_v4.0-dev6",
+            "Godot_v4.0-alpha2",
+            "Godot_v4.0-alpha11",
+            "Godot_v4.1.2-stable",
+            "Godot_v4.1.10-stable",
+            "Godot_v4.2-beta8",
+            "Godot_v4.2-rc1",
+            "Godot_v4.2-rc2",
+            "Godot_v4.2-stable",
+            "Godot_v4.2-stable_mono",
+        ]
+    );
+    assert!(parse("4.2.0-rc2") > parse("4.2.0-beta8"));
+    assert!(parse("4.2.0-mono") > parse("4.2.0"));
+}
+
+#[test]
+fn test_serde_versionlist() {
+    let mut builds = HashMap::new();
+    builds.insert(
+        Build {
+            version: Version {
+                major: 4,
+                minor: 0,
+                patch: 3,
+                suffix: Suffix::Stable,
+                is_mono: false,
+            },
+            platform: Platform::Linux32,
+        },
+        "https:sss".to_string(),
+    );
+    builds.insert(
+        Build {
+            version: Version {
+                major: 4,
+                minor: 0,
+                patch: 0,
+                suffix: Suffix::Alpha(8),
+                is_mono: false,
+            },
+            platform: Platform::Linux64,
+        },
+        "https:sss".to_string(),
+    );
+    let list = builds.into_iter().collect::<VersionList>();
+    let yaml = serde_yaml::to_string(&list).unwrap();
+    let list = VersionList::from_yaml(&yaml).unwrap();
+    let vcs = Version::parse("4.0-alpha8").unwrap();
+    assert!(list.find_artifact(&vcs, Platform::Linux64).is_some());
+    assert!(list.find_artifact(&vcs, Platform::Linux32).is_none());
+}
+
+#[test]
+fn test_versionlist_schema_v2() {
+    let yaml = "\
+schema: 2
+releases:
+- major: 4
+  minor: 2
+  patch: 1
+  suffix: stable
+  is_mono: false
+  release_date: 2023-12-12
+  templates_url: https://example.org/Godot_v4.2.1-stable_export_templates.tpz
+  artifacts:
+    linux64:
+      url: https://example.org/Godot_v4.2.1-stable_linux.x86_64.zip
+      filename: Godot_v4.2.1-stable_linux.x86_64.zip
+      sha512: abc123
+      size: 55403162
+    win64:
+      url: https://example.org/Godot_v4.2.1-stable_win64.exe.zip
+      filename: Godot_v4.2.1-stable_win64.exe.zip
+";
+    let list = VersionList::from_yaml(yaml).unwrap();
+    let vcs = Version::parse("4.2.1").unwrap();
+    let artifact = list.find_artifact(&vcs, Platform::Linux64).unwrap();
+    assert_eq!(
+        artifact.url,
+        "https://example.org/Godot_v4.2.1-stable_linux.x86_64.zip"
+    );
+    assert_eq!(artifact.sha512.as_deref(), Some("abc123"));
+    assert_eq!(artifact.size, Some(55403162));
+    let artifact = list.find_artifact(&vcs, Platform::Win64).unwrap();
+    assert_eq!(artifact.filename, "Godot_v4.2.1-stable_win64.exe.zip");
+    assert_eq!((artifact.sha512.as_ref(), artifact.size), (None, None));
+    let info = list.release_info(&vcs).unwrap();
+    assert_eq!(info.release_date.as_deref(), Some("2023-12-12"));
+    let templates = list.find_templates(&vcs).unwrap();
+    assert_eq!(
+        templates.filename,
+        "Godot_v4.2.1-stable_export_templates.tpz"
+    );
+    assert_eq!(templates.size, None);
+
+    let again = VersionList::from_yaml(&list.to_yaml().unwrap()).unwrap();
+    assert_eq!(
+        again.find_artifact(&vcs, Platform::Linux64),
+        list.find_artifact(&vcs, Platform::Linux64)
+    );
+    assert_eq!(again.release_info(&vcs), list.release_info(&vcs));
+
+    let err = VersionList::from_yaml("schema: 3\nreleases: []").unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("unsupported version list schema 3"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_convert_versionlist() {
+    let v1 = "\
+builds:
+  ? version:
+      major: 4
+      minor: 0
+      patch: 3
+      suffix: stable
+      is_mono: false
+    platform: linux64
+  : https://example.org/4.0.3/Godot_v4.0.3-stable_linux.x86_64.zip
+";
+    for old in [v1, include_str!("../tests/fixtures/versions.legacy.yml")] {
+        let list = VersionList::from_yaml(old).unwrap();
+        let yaml = list.to_yaml().unwrap();
+        assert!(yaml.starts_with("schema: 2\n"), "{}", yaml);
+        let converted = VersionList::from_yaml(&yaml).unwrap();
+        assert_eq!(converted.builds, list.builds);
+    }
+    let list = VersionList::from_yaml(v1).unwrap();
+    let artifact = list
+        .find_artifact(&Version::parse("4.0.3").unwrap(), Platform::Linux64)
+        .unwrap();
+    assert_eq!(artifact.filename, "Godot_v4.0.3-stable_linux.x86_64.zip");
+}
+
+#[cfg(test)]
+fn synthetic_list(entries: &[(&str, Platform)]) -> VersionList {
+    entries
+        .iter()
+        .map(|(spec, platform)| {
+            let version = Version::parse(spec).unwrap();
+            let url = format!("{}/{}.zip", spec, version);
+            (
+                Build {
+                    version,
+                    platform: *platform,
+                },
+                url,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_resolve_partial_version() {
+    let list = synthetic_list(&[
+        ("3.5.3", Platform::Linux64),
+        ("4.0.3", Platform::Linux64),
+        ("4.1.1", Platform::Linux64),
+        ("4.1.2", Platform::Linux64),
+        ("4.1.2-mono", Platform::Linux64),
+        ("4.1.3-rc1", Platform::Linux64),
+        ("4.1.4", Platform::Win64),
+        ("4.2-beta3", Platform::Linux64),
+    ]);
+    let resolve = |spec| {
+        list.resolve(spec, Platform::Linux64)
+            .map(|v| v.to_string())
+            .unwrap()
+    };
+    assert_eq!(resolve("4"), "Godot_v4.1.2-stable");
+    assert_eq!(resolve("4.1"), "Godot_v4.1.2-stable");
+    assert_eq!(resolve("4.1.1"), "Godot_v4.1.1-stable");
+    assert_eq!(resolve("4.1-rc"), "Godot_v4.1.3-rc1");
+    assert_eq!(resolve("4.1-rc1"), "Godot_v4.1.3-rc1");
+    assert_eq!(resolve("4.1-stable"), "Godot_v4.1.2-stable");
+    assert_eq!(resolve("4.2"), "Godot_v4.2-beta3");
+    assert_eq!(resolve("4.1-mono"), "Godot_v4.1.2-stable_mono");
+    assert_eq!(resolve("3"), "Godot_v3.5.3-stable");
+    assert_eq!(
+        list.resolve("4.1", Platform::Win64).unwrap().to_string(),
+        "Godot_v4.1.4-stable"
+    );
+
+    let err = list.resolve("4.1.9", Platform::Linux64).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "no version matching `4.1.9` among 7 versions available for Linux64; \
+             did you mean 4.1.2, 4.1.1 or 4.0.3?"
+    );
+    assert!(list.resolve("5", Platform::Macos).is_err());
+    for spec in ["4.x", "", "4.1-gamma", "4.1-stable2", "4.1.2.3"] {
+        let err = list.resolve(spec, Platform::Linux64).unwrap_err();
+        assert!(
+            err.to_string().starts_with("invalid version spec"),
+            "{}",
+            err
+        );
+    }
+}
+
+#[test]
+fn test_version_req() {
+    let req = |s| VersionReq::parse(s).unwrap();
+    let v = |s: &str| Version::parse(s).unwrap();
+    let matching = |r, versions: &[&str]| {
+        let r = req(r);
+        versions
+            .iter()
+            .filter(|s| r.matches(&v(s)))
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+    };
+    let versions = [
+        "3.5.3",
+        "4.0",
+        "4.0.4",
+        "4.1.2",
+        "4.2-rc1",
+        "4.2",
+        "4.2.1",
+        "4.2.1-mono",
+        "4.3-beta2",
+        "4.3.1",
+    ];
+    assert_eq!(
+        matching("*", &versions),
+        ["3.5.3", "4.0", "4.0.4", "4.1.2", "4.2", "4.2.1", "4.3.1"]
+    );
+    assert_eq!(matching("*-mono", &versions), ["4.2.1-mono"]);
+    assert_eq!(
+        matching("^4.1", &versions),
+        ["4.1.2", "4.2", "4.2.1", "4.3.1"]
+    );
+    assert_eq!(matching("4.1", &versions), matching("^4.1", &versions));
+    assert_eq!(matching("~4.2", &versions), ["4.2", "4.2.1"]);
+    assert_eq!(matching("~4", &versions), matching("^4.0", &versions));
+    assert_eq!(
+        matching(">=4.0,<4.3", &versions),
+        ["4.0", "4.0.4", "4.1.2", "4.2", "4.2.1"]
+    );
+    assert_eq!(matching(">4.2", &versions), ["4.3.1"]);
+    assert_eq!(matching("<=4.0", &versions), ["3.5.3", "4.0", "4.0.4"]);
+    assert_eq!(matching("4.2.*", &versions), ["4.2", "4.2.1"]);
+    assert_eq!(matching("4.*", &versions), matching("^4.0", &versions));
+    assert_eq!(matching("=4.2.0", &versions), ["4.2"]);
+    assert_eq!(
+        matching(">=4.2.0-rc1", &versions),
+        ["4.2-rc1", "4.2", "4.2.1", "4.3.1"]
+    );
+    assert_eq!(matching("=4.3.0-beta2", &versions), ["4.3-beta2"]);
+    assert_eq!(
+        matching(">=4.2-rc1", &versions),
+        matching(">=4.2.0-rc1", &versions)
+    );
+    assert_eq!(matching("=4.3-beta2", &versions), ["4.3-beta2"]);
+    assert!(matching(">=4.3,<4.2", &versions).is_empty());
+    assert!(matching(">=5", &versions).is_empty());
+
+    for s in [
+        "",
+        ">=",
+        "^*",
+        "4.*.1",
+        "4.2.1.0",
+        "abc",
+        "4-rc1",
+        ">=4.0,",
+        "=4.2.x-rc1",
+    ] {
+        let err = VersionReq::parse(s).unwrap_err();
+        assert!(
+            err.to_string().contains("requirement") || err.to_string().contains("suffix"),
+            "{}: {}",
+            s,
+            err
+        );
+    }
+    assert_eq!(req(">=4.0, <4.3").to_string(), ">=4.0, <4.3");
+    assert_eq!(req("4.2.*-mono").to_string(), "4.2.*-mono");
+    assert_eq!(req("*").to_string(), "*");
+    assert_eq!(req("~4.2.0-rc1").to_string(), "~4.2.0-rc1");
+
+    let list = synthetic_list(&[
+        ("4.1.2", Platform::Linux64),
+        ("4.2.1", Platform::Linux64),
+        ("4.3-rc1", Platform::Linux64),
+        ("4.2.2", Platform::Win64),
+    ]);
+    let best = |r| {
+        list.find_best(&req(r), Platform::Linux64)
+            .map(|v| v.to_string())
+    };
+    assert_eq!(best("^4.1").as_deref(), Some("Godot_v4.2.1-stable"));
+    assert_eq!(best("<4.2").as_deref(), Some("Godot_v4.1.2-stable"));
+    assert_eq!(best("*").as_deref(), Some("Godot_v4.2.1-stable"));
+    assert_eq!(best(">=4.3.0-rc1").as_deref(), Some("Godot_v4.3-rc1"));
+    assert_eq!(best(">=5"), None);
+    assert_eq!(
+        list.resolve(">=4.0,<4.2", Platform::Linux64)
+            .unwrap()
+            .to_string(),
+        "Godot_v4.1.2-stable"
+    );
+    assert!(list.resolve("^5", Platform::Linux64).is_err());
+}
+
+#[test]
+fn test_resolve_latest_keywords() {
+    let list = synthetic_list(&[
+        ("4.1.3", Platform::Linux64),
+        ("4.2.1", Platform::Linux64),
+        ("4.2.1-mono", Platform::Linux64),
+        ("4.2.2-rc2", Platform::Linux64),
+        ("4.3-beta1-mono", Platform::Linux64),
+        ("4.2.2", Platform::Win64),
+    ]);
+    let resolve = |spec| {
+        list.resolve(spec, Platform::Linux64)
+            .map(|v| v.to_string())
+            .unwrap()
+    };
+    assert_eq!(resolve("latest"), "Godot_v4.2.1-stable");
+    assert_eq!(resolve("latest-prerelease"), "Godot_v4.2.2-rc2");
+    assert_eq!(resolve("latest-mono"), "Godot_v4.2.1-stable_mono");
+    assert_eq!(
+        list.resolve("latest", Platform::Win64).unwrap().to_string(),
+        "Godot_v4.2.2-stable"
+    );
+    assert!(list.resolve("latest-mono", Platform::Win64).is_err());
+    assert!(list.resolve("latest", Platform::Macos).is_err());
+
+    let with_prereleases = |spec| {
+        list.resolve_with(spec, Platform::Linux64, true)
+            .map(|v| v.short())
+            .unwrap()
+    };
+    assert_eq!(with_prereleases("latest"), "4.2.2-rc2");
+    assert_eq!(with_prereleases("latest-mono"), "4.3-beta1 (mono)");
+    assert_eq!(with_prereleases("4.2"), "4.2.2-rc2");
+    assert_eq!(with_prereleases("4.2.1"), "4.2.1");
+    assert_eq!(resolve("4.2"), "Godot_v4.2.1-stable");
+}
+
+#[test]
+fn test_resolve_dev_builds() {
+    let list = synthetic_list(&[
+        ("4.2.2", Platform::Linux64),
+        ("4.3-dev5", Platform::Linux64),
+        ("4.3-dev6", Platform::Linux64),
+        ("4.3-dev6-mono", Platform::Linux64),
+        ("4.2.2-rc1-mono", Platform::Linux64),
+        ("4.4-dev1", Platform::Linux64),
+    ]);
+    let resolve = |spec| {
+        list.resolve(spec, Platform::Linux64)
+            .map(|v| v.short())
+            .unwrap()
+    };
+    assert_eq!(resolve("latest"), "4.2.2");
+    assert_eq!(resolve("latest-prerelease"), "4.2.2");
+    assert_eq!(resolve("latest-dev"), "4.4-dev1");
+    assert_eq!(resolve("latest-prerelease-mono"), "4.2.2-rc1 (mono)");
+    assert_eq!(resolve("mono@latest-dev"), "4.3-dev6 (mono)");
+    assert_eq!(resolve("4.3-dev"), "4.3-dev6");
+    assert_eq!(resolve("4.3-dev5"), "4.3-dev5");
+    assert_eq!(resolve("4"), "4.2.2");
+
+    // Dev snapshots have to be asked for by channel.
+    let err = list.resolve("4.4", Platform::Linux64).unwrap_err();
+    let err = err.downcast_ref::<VersionNotFound>().unwrap();
+    assert!(err.suggestions.iter().any(|v| v.short() == "4.4-dev1"));
+    assert_eq!(
+        list.filter_channel(Channel::Dev)
+            .map(|b| b.version.short())
+            .collect::<Vec<_>>(),
+        ["4.4-dev1", "4.3-dev6 (mono)", "4.3-dev6", "4.3-dev5"]
+    );
+}
+
+#[test]
+fn test_resolve_mono_prefix() {
+    let list = synthetic_list(&[
+        ("4.2.1", Platform::Linux64),
+        ("4.2.1-mono", Platform::Linux64),
+        ("4.2.2-rc1-mono", Platform::Linux64),
+    ]);
+    let resolve = |spec| {
+        list.resolve(spec, Platform::Linux64)
+            .map(|v| v.to_string())
+            .unwrap()
+    };
+    assert_eq!(resolve("mono@4.2.1"), "Godot_v4.2.1-stable_mono");
+    assert_eq!(resolve("MONO@4.2"), "Godot_v4.2.1-stable_mono");
+    assert_eq!(resolve("mono@4.2.1-mono"), "Godot_v4.2.1-stable_mono");
+    assert_eq!(resolve("mono@^4.1"), "Godot_v4.2.1-stable_mono");
+    assert_eq!(resolve("mono@latest"), "Godot_v4.2.1-stable_mono");
+    assert_eq!(resolve("mono@latest-prerelease"), "Godot_v4.2.2-rc1_mono");
+    assert_eq!(resolve("4.2.1"), "Godot_v4.2.1-stable");
+
+    let err = list.resolve("net@4.2.1", Platform::Linux64).unwrap_err();
+    assert!(
+        err.to_string().starts_with("unknown prefix `net@`"),
+        "{}",
+        err
+    );
+    let err = list
+        .resolve("mono@mono@4.2", Platform::Linux64)
+        .unwrap_err();
+    assert!(err.to_string().contains("[mono@]MAJOR"), "{}", err);
+}
+
+#[test]
+fn test_channels() {
+    let v = |s| Version::parse(s).unwrap();
+    assert_eq!(v("4.2.1").channel(), Channel::Stable);
+    assert!(!v("4.2.1").is_prerelease());
+    assert!(!v("4.2.1-mono").is_prerelease());
+    for (s, channel) in [
+        ("4.2-rc1", Channel::Rc),
+        ("4.2-beta3", Channel::Beta),
+        ("4.0-alpha11", Channel::Alpha),
+        ("4.3-dev5", Channel::Dev),
+    ] {
+        assert_eq!(v(s).channel(), channel);
+        assert!(v(s).is_prerelease(), "{}", s);
+    }
+
+    let list = synthetic_list(&[
+        ("4.2.1", Platform::Linux64),
+        ("4.2.1", Platform::Win64),
+        ("4.3-rc1", Platform::Linux64),
+        ("4.3-beta2", Platform::Linux64),
+    ]);
+    assert_eq!(list.filter_channel(Channel::Stable).count(), 2);
+    let rc = list.filter_channel(Channel::Rc).collect::<Vec<_>>();
+    assert_eq!(rc.len(), 1);
+    assert_eq!(rc[0].version.to_string(), "Godot_v4.3-rc1");
+    assert_eq!(list.filter_channel(Channel::Dev).count(), 0);
+}
+
+#[test]
+fn test_detect_project_version() {
+    let fixture = |name| {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    };
+    let detect = |name| detect_project_version(&fixture(name)).map(|r| r.to_string());
+    assert_eq!(detect("godot4_gl").unwrap(), "~4.2");
+    assert_eq!(detect("godot4_csharp").unwrap(), "~4.1-mono");
+    assert_eq!(detect("godot4_no_features").unwrap(), "~4");
+    assert_eq!(detect("godot3").unwrap(), "~3");
+    assert_eq!(
+        detect_project_version(&fixture("godot4_gl").join("project.godot"))
+            .unwrap()
+            .to_string(),
+        "~4.2"
+    );
+
+    let err = detect("malformed").unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("invalid config_version `five`"),
+        "{:#}",
+        err
+    );
+    let err = detect("missing").unwrap_err();
+    assert!(err.to_string().starts_with("could not read"), "{}", err);
+
+    let parse = |s| parse_project_version(s).map_err(|e| e.to_string());
+    assert_eq!(
+        parse("config_version=5\nconfig/features=PackedStringArray(\"4.x\")").unwrap_err(),
+        "invalid engine version `4.x` in config/features"
+    );
+    assert_eq!(
+        parse("config_version=5\nconfig/features=PackedStringArray(\"Forward Plus\")").unwrap_err(),
+        "config/features does not name an engine version"
+    );
+    assert_eq!(
+        parse("config_version=5\nconfig/features=\"4.2\"").unwrap_err(),
+        "malformed config/features `\"4.2\"`"
+    );
+    assert!(parse("").unwrap_err().starts_with("no config_version"));
+    assert!(parse("config_version=2")
+        .unwrap_err()
+        .starts_with("unsupported"));
+}
+
+#[test]
+fn test_did_you_mean() {
+    let list = synthetic_list(&[
+        ("3.5.3", Platform::Linux64),
+        ("4.0.4", Platform::Linux64),
+        ("4.1.2", Platform::Linux64),
+        ("4.1.3", Platform::Win64),
+        ("4.2.1", Platform::Linux64),
+        ("4.2.1-mono", Platform::Linux64),
+        ("4.2.2-rc1", Platform::Linux64),
+    ]);
+    let suggest = |spec| {
+        let err = list.resolve(spec, Platform::Linux64).unwrap_err();
+        let err = err.downcast_ref::<VersionNotFound>().unwrap();
+        assert_eq!(err.spec, spec);
+        err.suggestions
+            .iter()
+            .map(|v| v.short())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(suggest("4.1.3"), ["4.1.2", "4.0.4", "4.2.1"]);
+    assert_eq!(suggest("4.12"), ["4.1.2", "4.2.1", "4.0.4"]);
+    assert_eq!(suggest("4.2.1-rc1"), ["4.2.2-rc1", "4.2.1", "4.1.2"]);
+    assert_eq!(suggest("3.5.4"), ["3.5.3", "4.0.4", "4.2.1"]);
+    assert_eq!(suggest("4.1.2-mono")[0], "4.2.1 (mono)");
+    assert_eq!(suggest("^5"), ["4.2.2-rc1", "4.2.1", "4.1.2"]);
+
+    let err = list.resolve("4.1.3", Platform::Linux64).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "no version matching `4.1.3` among 6 versions available for Linux64; \
+             did you mean 4.1.2, 4.0.4 or 4.2.1?"
+    );
+    let err = list.resolve("latest-mono", Platform::Win64).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "no version matching `latest-mono` among 1 versions available for Win64"
+    );
+}
+
+#[test]
+fn test_query_versionlist() {
+    let list = synthetic_list(&[
+        ("3.5.2", Platform::Linux64),
+        ("3.5.3", Platform::Linux64),
+        ("3.6-beta3", Platform::Linux64),
+        ("4.1.3", Platform::Linux64),
+        ("4.2.1", Platform::Linux64),
+        ("4.2.1-mono", Platform::Linux64),
+        ("4.3-rc2", Platform::Linux64),
+        ("4.3-dev6", Platform::Linux64),
+        ("4.2.2", Platform::Win64),
+    ]);
+    let shorts = |versions: Vec<&Version>| versions.iter().map(|v| v.short()).collect::<Vec<_>>();
+    let linux = Platform::Linux64;
+    assert_eq!(
+        shorts(list.iter(linux).collect()),
+        [
+            "4.3-rc2",
+            "4.3-dev6",
+            "4.2.1 (mono)",
+            "4.2.1",
+            "4.1.3",
+            "3.6-beta3",
+            "3.5.3",
+            "3.5.2"
+        ]
+    );
+    assert_eq!(list.len(linux), 8);
+    assert_eq!(list.len(Platform::Win64), 1);
+    assert!(list.is_empty(Platform::Macos));
+    assert_eq!(list.latest_stable(linux).unwrap().short(), "4.2.1");
+    assert_eq!(
+        list.latest_stable(Platform::Win64).unwrap().short(),
+        "4.2.2"
+    );
+    assert_eq!(list.latest(Channel::Rc, linux).unwrap().short(), "4.3-rc2");
+    assert_eq!(
+        list.latest(Channel::Beta, linux).unwrap().short(),
+        "3.6-beta3"
+    );
+    assert_eq!(list.latest(Channel::Alpha, linux), None);
+    assert_eq!(
+        shorts(list.all_for_major(3, linux)),
+        ["3.6-beta3", "3.5.3", "3.5.2"]
+    );
+    assert!(list.all_for_major(3, Platform::Win64).is_empty());
+    assert_eq!(
+        shorts(list.filter(linux, |v| v.is_mono || v.minor == 1)),
+        ["4.2.1 (mono)", "4.1.3"]
+    );
+}
+
+#[test]
+fn test_list_formats() {
+    let yaml = "\
+schema: 2
+releases:
+- major: 4
+  minor: 2
+  patch: 1
+  suffix: stable
+  is_mono: true
+  release_date: 2023-12-12
+  notes_url: https://example.org/4.2.1.md
+  export_templates:
+    url: https://example.org/Godot_v4.2.1-stable_mono_export_templates.tpz
+    filename: Godot_v4.2.1-stable_mono_export_templates.tpz
+    sha512: def456
+    size: 1044361237
+  artifacts:
+    linux64:
+      url: https://example.org/Godot_v4.2.1-stable_mono_linux_x86_64.zip
+      filename: Godot_v4.2.1-stable_mono_linux_x86_64.zip
+      sha512: abc123
+      size: 55403162
+      source: https://example.org/versions.yml
+    win64:
+      url: https://example.org/Godot_v4.2.1-stable_mono_win64.zip
+      filename: Godot_v4.2.1-stable_mono_win64.zip
+- major: 4
+  minor: 3
+  patch: 0
+  suffix: dev6
+  is_mono: false
+  artifacts:
+    macos:
+      url: https://example.org/Godot_v4.3-dev6_macos.universal.zip
+      filename: Godot_v4.3-dev6_macos.universal.zip
+";
+    let list = VersionList::parse(yaml).unwrap();
+    let v421 = Version::parse("4.2.1-mono").unwrap();
+    let v43 = Version::parse("4.3-dev6").unwrap();
+    for format in [ListFormat::Yaml, ListFormat::Json, ListFormat::Toml] {
+        let text = list.to_format(format).unwrap();
+        assert_eq!(ListFormat::sniff(&text), format, "{}", text);
+        let again = VersionList::parse(&text).unwrap();
+        for (version, platform) in [
+            (&v421, Platform::Linux64),
+            (&v421, Platform::Win64),
+            (&v43, Platform::Macos),
+        ] {
+            assert_eq!(
+                again.find_artifact(version, platform),
+                list.find_artifact(version, platform),
+                "{:?}",
+                format
+            );
+        }
+        assert_eq!(again.release_info(&v421), list.release_info(&v421));
+        assert_eq!(again.release_info(&v43), list.release_info(&v43));
+        assert_eq!(again.to_yaml().unwrap(), list.to_yaml().unwrap());
+    }
+    let info = list.release_info(&v421).unwrap();
+    assert_eq!(
+        info.export_templates.as_ref().unwrap().sha512.as_deref(),
+        Some("def456")
+    );
+    assert_eq!(
+        info.notes_url.as_deref(),
+        Some("https://example.org/4.2.1.md")
+    );
+
+    assert_eq!(
+        ListFormat::from_path(Path::new("out/versions.JSON")),
+        Some(ListFormat::Json)
+    );
+    assert_eq!(ListFormat::from_path(Path::new("versions")), None);
+    assert_eq!("toml".parse::<ListFormat>().unwrap(), ListFormat::Toml);
+    assert!("xml".parse::<ListFormat>().is_err());
+}
+
+#[test]
+fn test_release_dates() {
+    let mut list = VersionList::from_yaml(
+        r#"
+schema: 2
+releases:
+- { major: 4, minor: 1, patch: 3, suffix: stable, is_mono: false, released: 2024-02-29,
+    artifacts: { linux64: { url: a, filename: a } } }
+- { major: 4, minor: 2, patch: 1, suffix: stable, is_mono: false, release_date: 2023-12-12,
+    artifacts: { linux64: { url: b, filename: b } } }
+- { major: 4, minor: 2, patch: 2, suffix: stable, is_mono: false,
+    artifacts: { linux64: { url: c, filename: c } } }
+- { major: 4, minor: 3, patch: 0, suffix: dev1, is_mono: false,
+    artifacts: { linux64: { url: d, filename: d } } }
+- { major: 3, minor: 5, patch: 0, suffix: stable, is_mono: false, released: 2022-08-05,
+    artifacts: { linux64: { url: e, filename: e } } }
+"#,
+    )
+    .unwrap();
+    let shorts = |versions: Vec<&Version>| versions.iter().map(|v| v.short()).collect::<Vec<_>>();
+    let v413 = Version::parse("4.1.3").unwrap();
+    assert_eq!(list.release_date(&v413), Some("2024-02-29"));
+    assert_eq!(list.release_date(&Version::parse("4.2.2").unwrap()), None);
+
+    assert_eq!(
+        shorts(list.iter_by_date(Platform::Linux64)),
+        ["4.1.3", "4.2.1", "3.5", "4.3-dev1", "4.2.2"]
+    );
+    assert_eq!(
+        shorts(
+            list.released_since("2023-01-01", Platform::Linux64)
+                .unwrap()
+        ),
+        ["4.2.1", "4.1.3"]
+    );
+    assert_eq!(
+        shorts(
+            list.released_since("2023-12-12", Platform::Linux64)
+                .unwrap()
+        ),
+        ["4.2.1", "4.1.3"]
+    );
+    assert!(list
+        .released_since("2025-01-01", Platform::Linux64)
+        .unwrap()
+        .is_empty());
+    for bad in ["2024-1-1", "yesterday", "2024-13-01", "2024-01-00", ""] {
+        assert!(
+            list.released_since(bad, Platform::Linux64).is_err(),
+            "{}",
+            bad
+        );
+    }
+
+    // Dates survive a round trip under their canonical name.
+    list = VersionList::from_yaml(&list.to_yaml().unwrap()).unwrap();
+    assert_eq!(list.release_date(&v413), Some("2024-02-29"));
+}
+
+#[test]
+fn test_listings_sorted_newest_first() {
+    let list = synthetic_list(&[
+        ("4.1-rc2", Platform::Linux64),
+        ("3.5.3", Platform::Win64),
+        ("4.2.1-mono", Platform::Linux64),
+        ("4.1", Platform::Linux64),
+        ("4.2.1", Platform::Win64),
+        ("4.10", Platform::Linux64),
+        ("4.2.1", Platform::Linux64),
+        ("4.3-beta1", Platform::Linux64),
+        ("4.1-mono", Platform::Linux64),
+        ("4.2-dev3", Platform::Linux64),
+        ("4.1.1-rc1", Platform::Linux64),
+        ("4.2", Platform::Linux64),
+    ]);
+    let builds = list.builds().map(|(b, _)| b.slug()).collect::<Vec<_>>();
+    assert_eq!(
+        builds,
+        [
+            "4.10.0-stable-linux64",
+            "4.3.0-beta1-linux64",
+            "4.2.1-stable-mono-linux64",
+            "4.2.1-stable-win64",
+            "4.2.1-stable-linux64",
+            "4.2.0-stable-linux64",
+            "4.2.0-dev3-linux64",
+            "4.1.1-rc1-linux64",
+            "4.1.0-stable-mono-linux64",
+            "4.1.0-stable-linux64",
+            "4.1.0-rc2-linux64",
+            "3.5.3-stable-win64",
+        ]
+    );
+    let linux = list
+        .iter(Platform::Linux64)
+        .map(|v| v.short())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        linux,
+        [
+            "4.10",
+            "4.3-beta1",
+            "4.2.1 (mono)",
+            "4.2.1",
+            "4.2",
+            "4.2-dev3",
+            "4.1.1-rc1",
+            "4.1 (mono)",
+            "4.1",
+            "4.1-rc2",
+        ]
+    );
+    let stable = list
+        .filter_channel(Channel::Stable)
+        .map(|b| b.slug())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        stable,
+        [
+            "4.10.0-stable-linux64",
+            "4.2.1-stable-mono-linux64",
+            "4.2.1-stable-win64",
+            "4.2.1-stable-linux64",
+            "4.2.0-stable-linux64",
+            "4.1.0-stable-mono-linux64",
+            "4.1.0-stable-linux64",
+            "3.5.3-stable-win64",
+        ]
+    );
+}
+
+#[test]
+fn test_versionlist_search() {
+    let mut entries = Vec::new();
+    for spec in [
+        "3.5.3",
+        "3.6-beta2",
+        "4.0",
+        "4.0.4",
+        "4.1",
+        "4.1-rc1",
+        "4.1-rc3",
+        "4.1.1",
+        "4.1.3",
+        "4.1.4-rc1",
+        "4.2-beta4",
+        "4.2-rc2",
+        "4.2.2",
+        "4.3-dev6",
+        "4.3-beta1",
+    ] {
+        entries.push((spec.to_string(), Platform::Linux64));
+        entries.push((spec.to_string(), Platform::Win64));
+        entries.push((format!("{}-mono", spec), Platform::Win64));
+    }
+    let entries: Vec<_> = entries.iter().map(|(s, p)| (s.as_str(), *p)).collect();
+    let list = synthetic_list(&entries);
+    let search = |versions: Vec<&Version>| versions.iter().map(|v| v.short()).collect::<Vec<_>>();
+
+    assert_eq!(
+        search(list.search("4.1")),
+        [
+            "4.1.4-rc1 (mono)",
+            "4.1.4-rc1",
+            "4.1.3 (mono)",
+            "4.1.3",
+            "4.1.1 (mono)",
+            "4.1.1",
+            "4.1 (mono)",
+            "4.1",
+            "4.1-rc3 (mono)",
+            "4.1-rc3",
+            "4.1-rc1 (mono)",
+            "4.1-rc1",
+        ]
+    );
+    assert_eq!(
+        search(list.search("4.*-rc*")),
+        [
+            "4.2-rc2 (mono)",
+            "4.2-rc2",
+            "4.1.4-rc1 (mono)",
+            "4.1.4-rc1",
+            "4.1-rc3 (mono)",
+            "4.1-rc3",
+            "4.1-rc1 (mono)",
+            "4.1-rc1",
+        ]
+    );
+    assert_eq!(
+        search(list.search("3.*")),
+        ["3.6-beta2 (mono)", "3.6-beta2", "3.5.3 (mono)", "3.5.3"]
+    );
+    assert_eq!(
+        search(list.search_mono("4.3")),
+        ["4.3-beta1 (mono)", "4.3-dev6 (mono)"]
+    );
+    assert_eq!(
+        search(list.search_channel("4", Channel::Beta)),
+        [
+            "4.3-beta1 (mono)",
+            "4.3-beta1",
+            "4.2-beta4 (mono)",
+            "4.2-beta4"
+        ]
+    );
+    assert_eq!(list.search("").len(), 30);
+    assert_eq!(list.search("*").len(), 30);
+    assert!(list.search("5.0").is_empty());
+    assert!(list.search("4.*-stable").is_empty());
+}
+
+#[test]
+fn test_versionlist_diff() {
+    let old = synthetic_list(&[
+        ("4.1.3", Platform::Linux64),
+        ("4.2.1", Platform::Linux64),
+        ("4.2.1", Platform::Win64),
+        ("4.3-beta1", Platform::Win64),
+    ]);
+    let new = synthetic_list(&[
+        ("4.2.1", Platform::Linux64),
+        ("4.2.1", Platform::Win64),
+        ("4.2.2", Platform::Linux64),
+        ("4.2.2", Platform::Win64),
+        ("4.3-beta1", Platform::Win64),
+        ("4.3-beta1-mono", Platform::Linux64),
+        ("4.3-beta2", Platform::Linux64),
+        ("4.3-beta2-mono", Platform::Win64),
+    ]);
+    let diff = old.diff(&new);
+    let shorts = |versions: &[Version]| versions.iter().map(|v| v.short()).collect::<Vec<_>>();
+    assert_eq!(
+        shorts(&diff.added),
+        ["4.3-beta2 (mono)", "4.3-beta2", "4.3-beta1 (mono)", "4.2.2"]
+    );
+    assert_eq!(shorts(&diff.removed), ["4.1.3"]);
+    assert_eq!(
+        diff.to_string(),
+        "New: 4.3-beta2 (mono and standard), 4.3-beta1 (mono), 4.2.2\nRemoved: 4.1.3"
+    );
+
+    assert!(new.diff(&new).is_empty());
+    assert_eq!(new.diff(&new).to_string(), "No new versions");
+    assert_eq!(
+        VersionList::default().diff(&old).to_string(),
+        "New: 4.3-beta1, 4.2.1, 4.1.3"
+    );
+    assert_eq!(
+        old.diff(&VersionList::default()).to_string(),
+        "Removed: 4.3-beta1, 4.2.1, 4.1.3"
+    );
+}
+
+#[test]
+fn test_load_legacy_versionlist() {
+    let list =
+        VersionList::from_yaml(include_str!("../tests/fixtures/versions.legacy.yml")).unwrap();
+    let vcs = Version::parse("4.0.2-stable").unwrap();
+    assert_eq!(
+        list.find_artifact(&vcs, Platform::Win64).unwrap().url,
+        "4.0.2/Godot_v4.0.2-stable_win64.exe.zip"
+    );
+    assert_eq!(
+        list.find_artifact(&vcs, Platform::Linux64).unwrap().url,
+        "4.0.2/Godot_v4.0.2-stable_linux_x86_64.zip"
+    );
+    let vcs = Version::parse("4.0.3-rc2-mono").unwrap();
+    assert_eq!(
+        list.find_artifact(&vcs, Platform::Macos).unwrap().url,
+        "4.0.3/rc2/mono/Godot_v4.0.3-rc2_mono_macos.universal.zip"
+    );
+    assert!(VersionList::from_yaml("versions: [1, 2]").is_err());
+
+    // The published list is the same data in the v2 schema.
+    let published = VersionList::from_yaml(include_str!("../versions.yml")).unwrap();
+    assert_eq!(published.builds, list.builds);
+    assert!(include_str!("../versions.yml").starts_with("schema: 2\n"));
+}
+
+#[test]
+fn test_serde_suffix() {
+    for (suffix, yaml) in [
+        (Suffix::Stable, "stable"),
+        (Suffix::Dev(5), "dev5"),
+        (Suffix::Alpha(11), "alpha11"),
+        (Suffix::Beta(2), "beta2"),
+        (Suffix::Rc(3), "rc3"),
+    ] {
+        assert_eq!(serde_yaml::to_string(&suffix).unwrap().trim(), yaml);
+        assert_eq!(serde_yaml::from_str::<Suffix>(yaml).unwrap(), suffix);
+    }
+    // Externally tagged form written by earlier releases.
+    let legacy = |s| serde_yaml::from_str::<Suffix>(s).unwrap();
+    assert_eq!(legacy("Stable"), Suffix::Stable);
+    assert_eq!(legacy("!Rc 3"), Suffix::Rc(3));
+    assert_eq!(legacy("!Alpha 11"), Suffix::Alpha(11));
+    assert_eq!(legacy("Dev: 5"), Suffix::Dev(5));
+    assert!(serde_yaml::from_str::<Suffix>("gamma1").is_err());
+    assert!(serde_yaml::from_str::<Suffix>("!Rc x").is_err());
+    // The published list must keep loading.
+    VersionList::from_yaml(include_str!("../versions.yml")).unwrap();
+}
+
+#[test]
+fn test_serde_platform() {
+    for (platform, yaml) in [
+        (Platform::Win32, "win32"),
+        (Platform::Win64, "win64"),
+        (Platform::Linux32, "linux32"),
+        (Platform::Linux64, "linux64"),
+        (Platform::Macos, "macos"),
+        (Platform::WinArm64, "winarm64"),
+        (Platform::LinuxArm32, "linuxarm32"),
+        (Platform::LinuxArm64, "linuxarm64"),
+    ] {
+        assert_eq!(serde_yaml::to_string(&platform).unwrap().trim(), yaml);
+        assert_eq!(serde_yaml::from_str::<Platform>(yaml).unwrap(), platform);
+        let legacy = format!("{:?}", platform);
+        assert_eq!(serde_yaml::from_str::<Platform>(&legacy).unwrap(), platform);
+    }
+    assert!(serde_yaml::from_str::<Platform>("amiga").is_err());
+
+    let build = Build {
+        version: Version::parse("4.0.3-rc3-mono").unwrap(),
+        platform: Platform::Win64,
+    };
+    let yaml = serde_yaml::to_string(&build).unwrap();
+    assert!(yaml.contains("suffix: rc3"), "{}", yaml);
+    assert!(yaml.contains("platform: win64"), "{}", yaml);
+    assert_eq!(serde_yaml::from_str::<Build>(&yaml).unwrap(), build);
+}
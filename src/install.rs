@@ -0,0 +1,1493 @@
+//! Installed builds: where they live and what is kept with them.
+
+use anyhow::anyhow;
+use anyhow::{Context, Result};
+use indicatif::HumanBytes;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+#[cfg(not(unix))]
+use std::time::Duration;
+use std::{
+    collections::HashSet,
+    env,
+    path::{Path, PathBuf},
+    process,
+    sync::Arc,
+};
+use std::{fs, io};
+use tokio_util::sync::CancellationToken;
+
+use crate::download::hex;
+use crate::extract::{enclosed_path, extract};
+use crate::progress::ProgressReporter;
+use crate::{godot, unix_now, Cancelled, OnExisting};
+
+/// What godotup knows about an installed build, stored next to it as
+/// `.godotup.yml`. `verified` is `None` when verification was skipped.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InstallRecord {
+    pub build: godot::Build,
+    pub verified: Option<bool>,
+    /// The console wrapper Windows builds ship next to the editor, relative
+    /// to the install.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub console: Option<PathBuf>,
+}
+
+/// A build installed under the install root.
+#[derive(Debug, Clone)]
+pub struct InstalledBuild {
+    pub dir: PathBuf,
+    pub record: InstallRecord,
+}
+
+/// Every installed build, newest version first, then by platform.
+#[derive(Debug, Default)]
+pub struct InstalledBuilds {
+    builds: Vec<InstalledBuild>,
+}
+
+impl InstalledBuilds {
+    /// Installed versions for `platform`, newest first.
+    pub fn iter(&self, platform: godot::Platform) -> impl Iterator<Item = &godot::Version> {
+        self.builds
+            .iter()
+            .map(|installed| &installed.record.build)
+            .filter(move |build| build.platform == platform)
+            .map(|build| &build.version)
+    }
+
+    /// The newest build for `platform` matching `spec`, resolved like
+    /// [`godot::VersionList::resolve`].
+    pub fn resolve(&self, spec: &str, platform: godot::Platform) -> Result<&InstalledBuild> {
+        let mut available = self.iter(platform).collect::<Vec<_>>();
+        available.reverse();
+        let version = godot::resolve_among(&available, spec, platform, false)?;
+        Ok(self
+            .builds
+            .iter()
+            .find(|installed| {
+                let build = &installed.record.build;
+                build.platform == platform && build.version == version
+            })
+            .expect("resolved to an installed version"))
+    }
+
+    pub fn builds(&self) -> &[InstalledBuild] {
+        &self.builds
+    }
+
+    /// The install of `build`, if any.
+    pub fn get(&self, build: &godot::Build) -> Option<&InstalledBuild> {
+        self.builds
+            .iter()
+            .find(|installed| installed.record.build == *build)
+    }
+}
+
+impl FromIterator<InstalledBuild> for InstalledBuilds {
+    fn from_iter<T: IntoIterator<Item = InstalledBuild>>(iter: T) -> Self {
+        let mut builds = iter.into_iter().collect::<Vec<_>>();
+        builds.sort_by(|a, b| {
+            let (a, b) = (&a.record.build, &b.record.build);
+            b.version.cmp(&a.version).then(a.platform.cmp(&b.platform))
+        });
+        InstalledBuilds { builds }
+    }
+}
+
+/// Version of the `manifest.json` format this godotup writes. Fields can be
+/// added without changing it; it is only bumped when older versions would
+/// misread a manifest.
+pub const MANIFEST_FORMAT: u32 = 1;
+
+/// Every file extracted into an install, stored next to it as
+/// `manifest.json`, so it can be checked for changes and uninstalled
+/// without touching files added to it later.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Manifest {
+    pub format: u32,
+    /// File name of the archive the install was extracted from.
+    pub archive: String,
+    /// Its SHA-512, if the version list has one.
+    pub archive_sha512: Option<String>,
+    /// When it was installed, in seconds since the unix epoch.
+    pub installed_at: u64,
+    pub files: Vec<ManifestFile>,
+}
+
+/// A file in a [`Manifest`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ManifestFile {
+    /// Relative to the install, separated by `/`.
+    pub path: String,
+    pub size: u64,
+    /// Unix permission bits, only checked on unix.
+    pub mode: u32,
+    /// SHA-256 of the contents, for everything but symlinks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Target of a symlink.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+}
+
+impl Manifest {
+    /// The manifest of `files`, extracted from the archive of `artifact` now.
+    pub(crate) fn new(artifact: &godot::Artifact, mut files: Vec<ManifestFile>) -> Self {
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Self {
+            format: MANIFEST_FORMAT,
+            archive: artifact.filename.clone(),
+            archive_sha512: artifact.sha512.clone(),
+            installed_at: unix_now(),
+            files,
+        }
+    }
+}
+
+/// What [`CliApp::verify`](crate::CliApp::verify) found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verification {
+    pub version: godot::Version,
+    /// How many files the manifest lists.
+    pub files: usize,
+    /// The files that changed since the install, by path.
+    pub changes: Vec<(String, FileChange)>,
+}
+
+/// How a file in an install differs from its [`Manifest`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChange {
+    Missing,
+    Resized,
+    Modified,
+    ModeChanged,
+    Relinked,
+}
+
+impl std::fmt::Display for FileChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            FileChange::Missing => "missing",
+            FileChange::Resized => "size changed",
+            FileChange::Modified => "contents changed",
+            FileChange::ModeChanged => "permissions changed",
+            FileChange::Relinked => "link target changed",
+        })
+    }
+}
+
+/// How much larger an extracted archive is assumed to be than the archive,
+/// when checking for free space before installing.
+pub(crate) const EXTRACTED_SIZE_FACTOR: u64 = 3;
+
+/// The device of the filesystem holding `path`, or its closest existing
+/// ancestor, and how many bytes on it are free to unprivileged users.
+#[cfg(unix)]
+pub(crate) fn free_space(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::{ffi::OsStrExt, fs::MetadataExt};
+    let path = path.ancestors().find(|path| path.exists())?;
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: statvfs is plain data, and is only read once filled in below.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `c_path` is NUL-terminated and `stat` is a valid out pointer.
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    #[allow(clippy::unnecessary_cast)]
+    let free = stat.f_bavail as u64 * stat.f_frsize as u64;
+    Some((path.metadata().ok()?.dev(), free))
+}
+
+/// Free space isn't checked off Unix.
+#[cfg(not(unix))]
+pub(crate) fn free_space(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+/// Fails if the bytes to be written under each path don't fit in the free
+/// space of its filesystem, adding up what goes to the same filesystem.
+/// Paths whose free space can't be told are skipped.
+pub(crate) fn check_free_space(needs: &[(PathBuf, u64)]) -> Result<()> {
+    let mut devices: Vec<(u64, u64, u64, Vec<&Path>)> = Vec::new();
+    for (path, needed) in needs {
+        let Some((device, free)) = free_space(path) else {
+            continue;
+        };
+        match devices.iter_mut().find(|(known, ..)| *known == device) {
+            Some((_, _, total, paths)) => {
+                *total += needed;
+                if !paths.contains(&path.as_path()) {
+                    paths.push(path);
+                }
+            }
+            None => devices.push((device, free, *needed, vec![path])),
+        }
+    }
+    for (_, free, needed, paths) in devices {
+        if needed > free {
+            let paths: Vec<_> = paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            return Err(anyhow!(
+                "Not enough disk space for {}: needs about {} but only {} is free, {} short; use --force to install anyway",
+                paths.join(" and "),
+                HumanBytes(needed),
+                HumanBytes(free),
+                HumanBytes(needed - free)
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn install_root() -> PathBuf {
+    dirs::home_dir().unwrap().join(".godotup")
+}
+
+/// Where [`CliApp::switch`](crate::CliApp::switch) links the current version; add it to `PATH` to
+/// run it as `godot`.
+pub fn bin_dir() -> PathBuf {
+    install_root().join("bin")
+}
+
+pub(crate) fn godot_version_dir(build: &godot::Build) -> PathBuf {
+    install_root().join(build.slug())
+}
+
+/// Every build with an install record under `root`.
+pub(crate) fn installed_builds(root: &Path) -> Result<InstalledBuilds> {
+    if !root.exists() {
+        return Ok(InstalledBuilds::default());
+    }
+    let mut builds = Vec::new();
+    for entry in fs::read_dir(root)? {
+        let dir = entry?.path();
+        let name = dir.file_name().unwrap_or_default().to_string_lossy();
+        if STAGING_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+        {
+            continue;
+        }
+        if let Some(record) = read_install_record(&dir)? {
+            builds.push(InstalledBuild { dir, record });
+        }
+    }
+    Ok(builds.into_iter().collect())
+}
+
+/// The version of the installed build the `godot` or `godot4` link in `bin`
+/// points at. Copies, made where links aren't available, can't be traced
+/// back to a build.
+pub(crate) fn switched_build<'a>(
+    bin: &Path,
+    installed: &'a InstalledBuilds,
+) -> Option<&'a godot::Version> {
+    ["godot", "godot4"].iter().find_map(|name| {
+        let alias = bin.join(format!("{}{}", name, env::consts::EXE_SUFFIX));
+        let target = fs::canonicalize(alias).ok()?;
+        installed
+            .builds()
+            .iter()
+            .find(|installed| {
+                fs::canonicalize(&installed.dir).is_ok_and(|dir| target.starts_with(dir))
+            })
+            .map(|installed| &installed.record.build.version)
+    })
+}
+
+pub(crate) fn installed_version_list() -> Result<InstalledBuilds> {
+    installed_builds(&install_root())
+}
+
+fn install_record_path(dir: &Path) -> PathBuf {
+    dir.join(".godotup.yml")
+}
+
+pub(crate) fn write_install_record(dir: &Path, record: &InstallRecord) -> Result<()> {
+    fs::write(install_record_path(dir), serde_yaml::to_string(record)?)?;
+    Ok(())
+}
+
+/// The record written when `dir` was installed, if any.
+pub fn read_install_record(dir: &Path) -> Result<Option<InstallRecord>> {
+    let path = install_record_path(dir);
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(serde_yaml::from_str(&fs::read_to_string(path)?)?))
+}
+
+/// Name of the [`Manifest`] in every install.
+const MANIFEST: &str = "manifest.json";
+
+/// What godotup adds to an install besides the extracted files, which its
+/// manifest doesn't list.
+const INSTALL_EXTRAS: [&str; 4] = [EXECUTABLE_ALIAS, CONSOLE_ALIAS, ".godotup.yml", MANIFEST];
+
+pub(crate) fn write_manifest(dir: &Path, manifest: &Manifest) -> Result<()> {
+    fs::write(dir.join(MANIFEST), serde_json::to_string_pretty(manifest)?)?;
+    Ok(())
+}
+
+/// The manifest written when `dir` was installed, if any.
+pub fn read_manifest(dir: &Path) -> Result<Option<Manifest>> {
+    #[derive(Deserialize)]
+    struct Format {
+        format: u32,
+    }
+
+    let path = dir.join(MANIFEST);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let json = fs::read_to_string(&path)?;
+    let format = serde_json::from_str::<Format>(&json)
+        .with_context(|| format!("Couldn't read {}", path.display()))?
+        .format;
+    if format > MANIFEST_FORMAT {
+        return Err(anyhow!(
+            "{} is in format {}, which needs a newer godotup",
+            path.display(),
+            format
+        ));
+    }
+    Ok(Some(serde_json::from_str(&json).with_context(|| {
+        format!("Couldn't read {}", path.display())
+    })?))
+}
+
+/// `path` in `dir` as a manifest lists it.
+pub(crate) fn manifest_path(dir: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(dir).unwrap_or(path);
+    let components: Vec<_> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    components.join("/")
+}
+
+/// Lowercase hex SHA-256 of the contents of `path`.
+pub(crate) fn sha256_file(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    fs::File::open(path)
+        .and_then(|mut file| io::copy(&mut file, &mut hasher))
+        .with_context(|| format!("Couldn't read {}", path.display()))?;
+    Ok(hex(&hasher.finalize()))
+}
+
+/// The files of `manifest` that changed in `dir` since it was installed.
+pub(crate) fn changed_files(dir: &Path, manifest: &Manifest) -> Result<Vec<(String, FileChange)>> {
+    let mut changes = Vec::new();
+    for file in &manifest.files {
+        let Some(path) = enclosed_path(&file.path).map(|path| dir.join(path)) else {
+            changes.push((file.path.clone(), FileChange::Missing));
+            continue;
+        };
+        let metadata = match path.symlink_metadata() {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                changes.push((file.path.clone(), FileChange::Missing));
+                continue;
+            }
+            Err(err) => {
+                return Err(err).with_context(|| format!("Couldn't read {}", path.display()))
+            }
+        };
+        let change = match &file.link {
+            Some(target) => (!metadata.is_symlink()
+                || fs::read_link(&path).ok().as_deref() != Some(Path::new(target)))
+            .then_some(FileChange::Relinked),
+            None if !metadata.is_file() || metadata.len() != file.size => Some(FileChange::Resized),
+            None if file.sha256.as_deref() != Some(&*sha256_file(&path)?) => {
+                Some(FileChange::Modified)
+            }
+            None => mode_changed(&metadata, file.mode).then_some(FileChange::ModeChanged),
+        };
+        if let Some(change) = change {
+            changes.push((file.path.clone(), change));
+        }
+    }
+    Ok(changes)
+}
+
+/// Whether the permissions in `metadata` are no longer `mode`.
+#[cfg(unix)]
+fn mode_changed(metadata: &fs::Metadata, mode: u32) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o777 != mode
+}
+
+/// Whether the permissions in `metadata` are no longer `mode`, which only
+/// unix has.
+#[cfg(not(unix))]
+fn mode_changed(_metadata: &fs::Metadata, _mode: u32) -> bool {
+    false
+}
+
+/// The files in `dir` that neither `manifest` nor [`INSTALL_EXTRAS`] list,
+/// as a manifest would.
+fn untracked_files(dir: &Path, manifest: &Manifest) -> Result<Vec<String>> {
+    let tracked: HashSet<&str> = manifest
+        .files
+        .iter()
+        .map(|file| file.path.as_str())
+        .chain(INSTALL_EXTRAS)
+        .collect();
+    let mut untracked = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(next) = pending.pop() {
+        let entries =
+            fs::read_dir(&next).with_context(|| format!("Couldn't read {}", next.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                pending.push(entry.path());
+                continue;
+            }
+            let path = manifest_path(dir, &entry.path());
+            if !tracked.contains(path.as_str()) {
+                untracked.push(path);
+            }
+        }
+    }
+    untracked.sort();
+    Ok(untracked)
+}
+
+/// Removes the install in `dir`: the files its manifest lists, what godotup
+/// added and the directories left empty. Files added since, or an install
+/// without a manifest, are refused unless `force`, which removes all of
+/// `dir`.
+pub(crate) fn remove_install(dir: &Path, force: bool) -> Result<()> {
+    if force {
+        return fs::remove_dir_all(dir)
+            .with_context(|| format!("Couldn't remove {}", dir.display()));
+    }
+    let manifest = read_manifest(dir)?.with_context(|| {
+        format!(
+            "{} has no manifest listing the files installed; use --force to remove all of it",
+            dir.display()
+        )
+    })?;
+    let untracked = untracked_files(dir, &manifest)?;
+    if let Some(first) = untracked.first() {
+        return Err(anyhow!(
+            "{} holds {} file(s) godotup didn't install, like {}; use --force to remove them too",
+            dir.display(),
+            untracked.len(),
+            first
+        ));
+    }
+    // The manifest goes last, so an interrupted uninstall can be retried.
+    let paths = manifest.files.iter().map(|file| file.path.as_str());
+    for path in paths.chain(INSTALL_EXTRAS).filter_map(enclosed_path) {
+        let path = dir.join(path);
+        match fs::remove_file(&path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                return Err(err).with_context(|| format!("Couldn't remove {}", path.display()))
+            }
+            _ => {}
+        }
+    }
+    remove_empty_dirs(dir)
+}
+
+/// Removes `dir` and the directories in it, which must hold nothing else.
+fn remove_empty_dirs(dir: &Path) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Couldn't read {}", dir.display()))? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            remove_empty_dirs(&entry.path())?;
+        }
+    }
+    fs::remove_dir(dir).with_context(|| format!("Couldn't remove {}", dir.display()))
+}
+
+/// Deletes the `Zone.Identifier` stream Windows marks downloads with from
+/// `path`. Whether it had one.
+#[cfg(windows)]
+fn remove_zone_identifier(path: &Path) -> io::Result<bool> {
+    let mut stream = path.as_os_str().to_owned();
+    stream.push(":Zone.Identifier");
+    match fs::remove_file(&stream) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// [`remove_zone_identifier`], only warning `progress` when it fails.
+#[cfg(windows)]
+pub(crate) fn unblock(path: &Path, progress: &dyn ProgressReporter) {
+    match remove_zone_identifier(path) {
+        Ok(true) => tracing::debug!(path = %path.display(), "unblocked"),
+        Ok(false) => {}
+        Err(err) => progress.warning(&format!(
+            "could not unblock {}: {}; Windows may ask before running it",
+            path.display(),
+            err
+        )),
+    }
+}
+
+/// [`unblock`]s every `.exe` under `dir`.
+#[cfg(windows)]
+pub(crate) fn unblock_executables(dir: &Path, progress: &dyn ProgressReporter) {
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(next) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&next) else {
+            continue;
+        };
+        for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+            if path.is_dir() {
+                pending.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("exe"))
+            {
+                unblock(&path, progress);
+            }
+        }
+    }
+}
+
+/// The macOS tool that edits extended attributes.
+pub(crate) const XATTR: &str = "/usr/bin/xattr";
+
+/// Removes the `com.apple.quarantine` attribute Gatekeeper checks from
+/// everything in `dir`, with the `xattr` tool at `xattr`.
+pub(crate) fn remove_quarantine(xattr: &Path, dir: &Path) -> Result<()> {
+    let output = process::Command::new(xattr)
+        .args(["-dr", "com.apple.quarantine"])
+        .arg(dir)
+        .output()
+        .with_context(|| format!("could not run {}", xattr.display()))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "could not remove the quarantine attribute from {}: {}",
+            dir.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Runs the executable installed in `dir` with `--version --headless` and
+/// checks it reports the version of `record`, which is written with the
+/// outcome either way.
+pub(crate) fn verify_install(dir: &Path, mut record: InstallRecord) -> Result<()> {
+    let build = &record.build;
+    let result = find_executable(dir, build.platform).and_then(|exe| {
+        let output = process::Command::new(&exe)
+            .args(["--version", "--headless"])
+            .output()
+            .with_context(|| format!("could not run {}", exe.display()))?;
+        let reported =
+            godot::Version::from_engine_string(&String::from_utf8_lossy(&output.stdout))?;
+        if reported != build.version {
+            return Err(anyhow!(
+                "{} reports version {} but {} was expected",
+                exe.display(),
+                reported.short(),
+                build.version.short()
+            ));
+        }
+        Ok(())
+    });
+    record.verified = Some(result.is_ok());
+    write_install_record(dir, &record)?;
+    result
+}
+
+/// Name of the link to the Godot binary in every install, the same for all
+/// versions.
+const EXECUTABLE_ALIAS: &str = if cfg!(windows) { "godot.exe" } else { "godot" };
+
+/// What to run for a `platform` build installed in `dir`: its
+/// [`EXECUTABLE_ALIAS`], or the binary itself for installs without one.
+pub(crate) fn find_executable(dir: &Path, platform: godot::Platform) -> Result<PathBuf> {
+    let alias = dir.join(EXECUTABLE_ALIAS);
+    if alias.is_file() {
+        return Ok(alias);
+    }
+    find_binary(dir, platform)
+}
+
+/// Name of the link to the console wrapper of Windows builds, next to
+/// [`EXECUTABLE_ALIAS`].
+const CONSOLE_ALIAS: &str = "godot_console.exe";
+
+/// The console wrapper of a `platform` build installed in `dir`, which only
+/// Windows builds have: its [`CONSOLE_ALIAS`], or else the one recorded or
+/// found next to the editor.
+pub(crate) fn find_console(dir: &Path, platform: godot::Platform) -> Result<PathBuf> {
+    has_console(platform)?;
+    let alias = dir.join(CONSOLE_ALIAS);
+    if alias.is_file() {
+        return Ok(alias);
+    }
+    if let Some(console) = read_install_record(dir)?.and_then(|record| record.console) {
+        return Ok(dir.join(console));
+    }
+    let binary = find_binary(dir, platform)?;
+    console_next_to(&binary)
+        .with_context(|| format!("no console wrapper found in {}", dir.display()))
+}
+
+/// Fails unless builds for `platform` have a console wrapper.
+pub(crate) fn has_console(platform: godot::Platform) -> Result<()> {
+    if !platform.is_windows() {
+        return Err(anyhow!(
+            "only Windows builds have a console wrapper; the {} build writes to the terminal itself",
+            platform.id()
+        ));
+    }
+    Ok(())
+}
+
+/// The `_console.exe` wrapper in the same directory as `binary`.
+fn console_next_to(binary: &Path) -> Option<PathBuf> {
+    let mut entries: Vec<_> = fs::read_dir(binary.parent()?)
+        .ok()?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .collect();
+    entries.sort();
+    entries.into_iter().find(|p| {
+        let name = p.file_name().unwrap_or_default().to_string_lossy();
+        p.is_file() && name.starts_with("Godot") && name.ends_with("_console.exe")
+    })
+}
+
+/// Links [`EXECUTABLE_ALIAS`] in `dir` to the binary of the `platform` build
+/// installed there.
+pub(crate) fn create_executable_alias(dir: &Path, platform: godot::Platform) -> Result<PathBuf> {
+    link_alias(dir, EXECUTABLE_ALIAS, &find_binary(dir, platform)?)
+}
+
+/// Links [`CONSOLE_ALIAS`] in `dir` to the console wrapper of the `platform`
+/// build installed there, if it has one. Where the wrapper is, relative to
+/// `dir`.
+pub(crate) fn create_console_alias(
+    dir: &Path,
+    platform: godot::Platform,
+) -> Result<Option<PathBuf>> {
+    if !platform.is_windows() {
+        return Ok(None);
+    }
+    let Some(console) = console_next_to(&find_binary(dir, platform)?) else {
+        return Ok(None);
+    };
+    link_alias(dir, CONSOLE_ALIAS, &console)?;
+    Ok(Some(console.strip_prefix(dir)?.to_path_buf()))
+}
+
+/// Links `alias` in `dir` to `binary`, replacing any link there. Windows
+/// gets a hard link, or else a copy, as symlinks need privileges there.
+pub(crate) fn link_alias(dir: &Path, alias: &str, binary: &Path) -> Result<PathBuf> {
+    let alias = dir.join(alias);
+    if alias.symlink_metadata().is_ok() {
+        fs::remove_file(&alias).with_context(|| format!("Couldn't replace {}", alias.display()))?;
+    }
+    #[cfg(unix)]
+    let linked = std::os::unix::fs::symlink(binary.strip_prefix(dir).unwrap_or(binary), &alias);
+    #[cfg(not(unix))]
+    let linked = fs::hard_link(binary, &alias).or_else(|_| fs::copy(binary, &alias).map(drop));
+    linked.with_context(|| format!("Couldn't link {} to {}", alias.display(), binary.display()))?;
+    Ok(alias)
+}
+
+/// The Godot binary of a `platform` build installed in `dir`, or inside the
+/// single directory it holds when the archive wasn't flattened. On macOS
+/// this is the binary inside the `.app` bundle; console wrappers and `.pck`
+/// files are skipped.
+fn find_binary(dir: &Path, platform: godot::Platform) -> Result<PathBuf> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    if platform.is_app_bundle() {
+        return match entries
+            .iter()
+            .find(|p| p.is_dir() && p.extension().is_some_and(|ext| ext == "app"))
+        {
+            Some(app) => bundle_executable(app),
+            None => Err(anyhow!("no .app bundle found in {}", dir.display())),
+        };
+    }
+    let found = entries.iter().find(|p| {
+        let name = p.file_name().unwrap_or_default().to_string_lossy();
+        p.is_file()
+            && name.starts_with("Godot")
+            && !name.contains("console")
+            && p.extension().is_none_or(|ext| ext != "pck")
+    });
+    let dirs: Vec<_> = entries.iter().filter(|p| p.is_dir()).collect();
+    match (found, dirs.as_slice()) {
+        (Some(exe), _) => Ok(exe.clone()),
+        (None, [wrapper]) if wrapper.file_name().is_some_and(|n| n != "GodotSharp") => {
+            find_binary(wrapper, platform)
+        }
+        _ => Err(anyhow!("no Godot executable found in {}", dir.display())),
+    }
+}
+
+/// The executable of the macOS bundle `app`: the one its `Info.plist`
+/// names, or else the first in `Contents/MacOS`.
+fn bundle_executable(app: &Path) -> Result<PathBuf> {
+    let contents = app.join("Contents");
+    let named = fs::read_to_string(contents.join("Info.plist"))
+        .ok()
+        .and_then(|plist| plist_string(&plist, "CFBundleExecutable"));
+    let macos = contents.join("MacOS");
+    if let Some(name) = named {
+        return Ok(macos.join(name));
+    }
+    let mut entries = fs::read_dir(&macos)
+        .with_context(|| format!("Couldn't read {}", macos.display()))?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    entries
+        .into_iter()
+        .next()
+        .with_context(|| format!("no executable in {}", macos.display()))
+}
+
+/// The string `key` is set to in an XML property list.
+fn plist_string(plist: &str, key: &str) -> Option<String> {
+    let (_, after) = plist.split_once(&format!("<key>{}</key>", key))?;
+    let (value, _) = after
+        .trim_start()
+        .strip_prefix("<string>")?
+        .split_once("</string>")?;
+    Some(value.trim().to_string()).filter(|value| !value.is_empty())
+}
+
+/// Makes the Godot binary of a `platform` build in `dir` executable, for
+/// archives that don't record unix modes. It is found by name, or else as
+/// the largest native executable at the top of `dir`. The binary, if it had
+/// to be made executable.
+#[cfg(unix)]
+pub(crate) fn ensure_executable(dir: &Path, platform: godot::Platform) -> Result<Option<PathBuf>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let found = find_binary(dir, platform).ok();
+    let Some(binary) = found.or_else(|| largest_binary(dir)) else {
+        return Ok(None);
+    };
+    let mode = fs::metadata(&binary)?.permissions().mode();
+    if mode & 0o111 != 0 {
+        return Ok(None);
+    }
+    tracing::debug!(path = %binary.display(), "made executable");
+    fs::set_permissions(&binary, fs::Permissions::from_mode(0o755))
+        .with_context(|| format!("Couldn't make {} executable", binary.display()))?;
+    Ok(Some(binary))
+}
+
+/// The largest ELF or Mach-O file directly in `dir`.
+#[cfg(unix)]
+fn largest_binary(dir: &Path) -> Option<PathBuf> {
+    const MAGIC: [[u8; 4]; 5] = [
+        *b"\x7fELF",
+        [0xfe, 0xed, 0xfa, 0xce],
+        [0xfe, 0xed, 0xfa, 0xcf],
+        [0xcf, 0xfa, 0xed, 0xfe],
+        [0xca, 0xfe, 0xba, 0xbe],
+    ];
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let mut magic = [0; 4];
+            fs::File::open(&path).ok()?.read_exact(&mut magic).ok()?;
+            let size = path.metadata().ok().filter(|m| m.is_file())?.len();
+            MAGIC.contains(&magic).then_some((size, path))
+        })
+        .max()
+        .map(|(_, path)| path)
+}
+
+/// [`extract`]s `archive` into a staging directory next to `dir`, readies
+/// the install there with `prepare`, which is given the extracted files, and
+/// only then renames it to `dir`. Whatever fails, `dir` never holds a
+/// partial install.
+pub(crate) async fn install_staged<T>(
+    archive: &Path,
+    dir: &Path,
+    flatten: bool,
+    workers: usize,
+    cancel: &CancellationToken,
+    progress: &Arc<dyn ProgressReporter>,
+    prepare: impl FnOnce(&Path, Vec<ManifestFile>) -> Result<T>,
+) -> Result<T> {
+    let staging = staging_dir(dir);
+    if staging.exists() {
+        fs::remove_dir_all(&staging)
+            .with_context(|| format!("Couldn't remove {}", staging.display()))?;
+    }
+    let files = extract(archive, &staging, flatten, workers, cancel, progress).await?;
+    let prepared = prepare(&staging, files).and_then(|prepared| {
+        if cancel.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        publish(&staging, dir)?;
+        Ok(prepared)
+    });
+    if prepared.is_err() {
+        let _ = fs::remove_dir_all(&staging);
+    }
+    prepared
+}
+
+/// Whether to go on installing `version` into `dir`, by `on_existing` if it
+/// is already installed there.
+pub(crate) fn reinstall(
+    dir: &Path,
+    version: &godot::Version,
+    on_existing: OnExisting,
+) -> Result<bool> {
+    if !dir.exists() {
+        return Ok(true);
+    }
+    match on_existing {
+        OnExisting::Fail => Err(anyhow!(
+            "{} is already installed in {}; use --reinstall to replace it",
+            version.short(),
+            dir.display()
+        )),
+        OnExisting::Replace => Ok(true),
+        OnExisting::Skip => Ok(false),
+    }
+}
+
+/// Prefixes of what installs leave next to version directories while they
+/// run, followed by the version's name and the installing process's id.
+const STAGING_PREFIXES: [&str; 2] = [".staging-", ".replaced-"];
+
+/// Where the install for `dir` is extracted and readied.
+fn staging_dir(dir: &Path) -> PathBuf {
+    aside(dir, STAGING_PREFIXES[0])
+}
+
+/// `dir` renamed with `prefix` and this process's id.
+pub(crate) fn aside(dir: &Path, prefix: &str) -> PathBuf {
+    let name = dir.file_name().unwrap_or_default().to_string_lossy();
+    dir.with_file_name(format!("{}{}-{}", prefix, name, process::id()))
+}
+
+/// Renames the readied install `staging` to `dir`, replacing any install
+/// there. An install being replaced is only removed once the new one is in
+/// place.
+fn publish(staging: &Path, dir: &Path) -> Result<()> {
+    let replaced = aside(dir, STAGING_PREFIXES[1]);
+    let replacing = dir.exists();
+    if replacing {
+        fs::rename(dir, &replaced)
+            .with_context(|| format!("Couldn't move {} aside", dir.display()))?;
+    }
+    if let Err(err) = fs::rename(staging, dir) {
+        if replacing {
+            let _ = fs::rename(&replaced, dir);
+        }
+        return Err(err)
+            .with_context(|| format!("Couldn't move {} to {}", staging.display(), dir.display()));
+    }
+    if replacing {
+        let _ = fs::remove_dir_all(&replaced);
+    }
+    Ok(())
+}
+
+/// Removes what interrupted installs left in `root`, returning it. What an
+/// install still running left is kept.
+pub(crate) fn remove_stale_staging(root: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut removed = Vec::new();
+    for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if !STAGING_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+        {
+            continue;
+        }
+        let pid = name.rsplit('-').next().and_then(|pid| pid.parse().ok());
+        if pid.is_some_and(|pid| pid == process::id() || process_running(pid, &path)) {
+            continue;
+        }
+        if fs::remove_dir_all(&path).is_ok() {
+            removed.push(path);
+        }
+    }
+    removed
+}
+
+/// Whether the process `pid`, which left `path`, may still be running.
+#[cfg(unix)]
+fn process_running(pid: u32, _path: &Path) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 only checks the process exists.
+    pid > 0
+        && (unsafe { libc::kill(pid, 0) } == 0
+            || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM))
+}
+
+/// Whether the process `pid`, which left `path`, may still be running. Only
+/// guessed from `path` having changed in the last day.
+#[cfg(not(unix))]
+fn process_running(_pid: u32, path: &Path) -> bool {
+    path.metadata()
+        .and_then(|metadata| metadata.modified())
+        .is_ok_and(|modified| {
+            modified.elapsed().unwrap_or_default() < Duration::from_secs(24 * 60 * 60)
+        })
+}
+
+/// Where Godot looks for the export templates called `name`, the contents
+/// of `version.txt` in their archive, e.g. `4.2.1.stable.mono`.
+pub(crate) fn export_templates_dir(name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+        return Err(anyhow!("Invalid export templates version {:?}", name));
+    }
+    let godot = if cfg!(target_os = "linux") {
+        "godot"
+    } else {
+        "Godot"
+    };
+    // Godot 3 keeps them in `templates` instead.
+    let templates = if name.starts_with("3.") {
+        "templates"
+    } else {
+        "export_templates"
+    };
+    Ok(dirs::data_dir()
+        .context("Data dir not found")?
+        .join(godot)
+        .join(templates)
+        .join(name))
+}
+
+/// The name Godot expects the export templates in `archive` under.
+pub(crate) fn templates_name(archive: &Path) -> Result<String> {
+    let mut zip = zip::ZipArchive::new(fs::File::open(archive)?)?;
+    let mut name = String::new();
+    zip.by_name("templates/version.txt")
+        .with_context(|| format!("{} has no templates/version.txt", archive.display()))?
+        .read_to_string(&mut name)?;
+    Ok(name.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    use std::fs;
+    use std::sync::atomic::Ordering as AtomicOrdering;
+
+    use crate::progress::{NoProgress, Phase};
+    use crate::test_util::{synthetic_zip, test_dir};
+    use crate::Config;
+
+    /// Cancels extraction as soon as it writes anything, noting whether the
+    /// install it would replace was touched by then.
+    struct CancelMidway {
+        cancel: CancellationToken,
+        dir: PathBuf,
+        touched: std::sync::atomic::AtomicBool,
+    }
+
+    impl ProgressReporter for CancelMidway {
+        fn phase_changed(&self, _phase: Phase) {}
+        fn started(&self, _total: Option<u64>) {}
+        fn advanced(&self, _bytes: u64) {
+            if !self.dir.join("old").exists() || self.dir.join("Godot").exists() {
+                self.touched.store(true, AtomicOrdering::SeqCst);
+            }
+            self.cancel.cancel();
+        }
+        fn finished(&self) {}
+    }
+
+    #[tokio::test]
+    async fn test_install_staged() {
+        let mut entries = vec!["Godot/".to_string(), "Godot/Godot".to_string()];
+        entries.extend((0..50).map(|i| format!("Godot/data/{}.pck", i)));
+        let entries: Vec<_> = entries.iter().map(String::as_str).collect();
+        let (_tmp, zip) = synthetic_zip("staged", &entries);
+        let root = zip.with_file_name("installs");
+        let dir = root.join("4.2.1-stable-linux64");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("old"), "").unwrap();
+        let leftovers = || {
+            let mut names: Vec<_> = fs::read_dir(&root)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .collect();
+            names.sort();
+            names
+        };
+
+        let cancel = CancellationToken::new();
+        let midway = Arc::new(CancelMidway {
+            cancel: cancel.clone(),
+            dir: dir.clone(),
+            touched: Default::default(),
+        });
+        let progress: Arc<dyn ProgressReporter> = midway.clone();
+        let err = install_staged(&zip, &dir, true, 4, &cancel, &progress, |_, _| Ok(()))
+            .await
+            .unwrap_err();
+        assert!(err.is::<Cancelled>(), "{}", err);
+        assert!(!midway.touched.load(AtomicOrdering::SeqCst));
+        assert!(dir.join("old").exists());
+        assert_eq!(leftovers(), ["4.2.1-stable-linux64"]);
+
+        // Nor is it when readying the install fails.
+        let progress: Arc<dyn ProgressReporter> = Arc::new(NoProgress);
+        let fresh = CancellationToken::new();
+        let err = install_staged(&zip, &dir, true, 4, &fresh, &progress, |staging, _| {
+            assert!(staging.join("Godot").is_file());
+            Err::<(), _>(anyhow!("not ready"))
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(err.to_string(), "not ready");
+        assert!(dir.join("old").exists());
+        assert_eq!(leftovers(), ["4.2.1-stable-linux64"]);
+
+        install_staged(&zip, &dir, true, 4, &fresh, &progress, |_, _| Ok(()))
+            .await
+            .unwrap();
+        assert!(dir.join("Godot").is_file());
+        assert_eq!(fs::read_dir(dir.join("data")).unwrap().count(), 50);
+        assert!(!dir.join("old").exists());
+        assert_eq!(leftovers(), ["4.2.1-stable-linux64"]);
+    }
+
+    #[test]
+    fn test_reinstall() {
+        let tmp = test_dir("reinstall");
+        let dir = tmp.path().join("4.2.1-stable-linux64");
+        let version = godot::Version::parse("4.2.1").unwrap();
+        for policy in [OnExisting::Fail, OnExisting::Replace, OnExisting::Skip] {
+            assert!(reinstall(&dir, &version, policy).unwrap());
+        }
+        fs::create_dir_all(&dir).unwrap();
+        assert_eq!(
+            reinstall(&dir, &version, OnExisting::Fail)
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "4.2.1 is already installed in {}; use --reinstall to replace it",
+                dir.display()
+            )
+        );
+        assert!(reinstall(&dir, &version, OnExisting::Replace).unwrap());
+        assert!(!reinstall(&dir, &version, OnExisting::Skip).unwrap());
+        let config: Config = toml::from_str("on_existing = \"skip\"").unwrap();
+        assert_eq!(config.on_existing, OnExisting::Skip);
+    }
+
+    #[test]
+    fn test_remove_stale_staging() {
+        let tmp = test_dir("stale");
+        let root = tmp.path();
+        let crashed = root.join(".staging-4.2.1-stable-linux64-999999999");
+        let running = root.join(format!(
+            ".replaced-4.2.1-stable-linux64-{}",
+            std::process::id()
+        ));
+        let installed = root.join("4.2.1-stable-linux64");
+        for dir in [&crashed, &running, &installed] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        assert_eq!(remove_stale_staging(root), vec![crashed.clone()]);
+        assert!(!crashed.exists());
+        assert!(running.exists());
+        assert!(installed.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_remove_quarantine() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = test_dir("quarantine");
+        let dir = tmp.path();
+        let xattr = dir.join("xattr");
+        let args = dir.join("args");
+        let script = format!("#!/bin/sh\necho \"$@\" > {}\n", args.display());
+        fs::write(&xattr, script).unwrap();
+        fs::set_permissions(&xattr, fs::Permissions::from_mode(0o755)).unwrap();
+        let app = dir.join("4.2.1-stable-macos");
+        remove_quarantine(&xattr, &app).unwrap();
+        assert_eq!(
+            fs::read_to_string(&args).unwrap().trim(),
+            format!("-dr com.apple.quarantine {}", app.display())
+        );
+
+        fs::write(
+            &xattr,
+            "#!/bin/sh\necho 'Operation not permitted' >&2\nexit 1\n",
+        )
+        .unwrap();
+        let err = remove_quarantine(&xattr, &app).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "could not remove the quarantine attribute from {}: Operation not permitted",
+                app.display()
+            )
+        );
+        let missing = dir.join("missing");
+        let err = remove_quarantine(&missing, &app).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("could not run {}", missing.display())
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_remove_zone_identifier() {
+        let tmp = test_dir("zone");
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("bin")).unwrap();
+        let exe = dir.join("bin").join("Godot_v4.2.1-stable_win64.exe");
+        fs::write(&exe, "MZ").unwrap();
+        let stream = dir
+            .join("bin")
+            .join("Godot_v4.2.1-stable_win64.exe:Zone.Identifier");
+        fs::write(&stream, "[ZoneTransfer]\r\nZoneId=3\r\n").unwrap();
+        assert!(fs::metadata(&stream).is_ok());
+
+        assert!(remove_zone_identifier(&exe).unwrap());
+        assert!(fs::metadata(&stream).is_err());
+        assert_eq!(fs::read_to_string(&exe).unwrap(), "MZ");
+        assert!(!remove_zone_identifier(&exe).unwrap());
+
+        fs::write(&stream, "[ZoneTransfer]\r\nZoneId=3\r\n").unwrap();
+        unblock_executables(dir, &NoProgress);
+        assert!(fs::metadata(&stream).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_install_manifest() {
+        let (_tmp, zip) = synthetic_zip(
+            "manifest",
+            &[
+                "Godot/",
+                "Godot/Godot",
+                "Godot/data/a.pck",
+                "Godot/lib -> data",
+            ],
+        );
+        let dir = zip.with_file_name("4.2.1-stable-linux64");
+        let artifact = godot::Artifact::from_url("https://example.com/Godot.zip".to_string());
+        let progress: Arc<dyn ProgressReporter> = Arc::new(NoProgress);
+        let cancel = CancellationToken::new();
+        install_staged(&zip, &dir, true, 2, &cancel, &progress, |staging, files| {
+            write_manifest(staging, &Manifest::new(&artifact, files))
+        })
+        .await
+        .unwrap();
+
+        let manifest = read_manifest(&dir).unwrap().unwrap();
+        assert_eq!(manifest.format, MANIFEST_FORMAT);
+        assert_eq!(manifest.archive, "Godot.zip");
+        let paths: Vec<_> = manifest.files.iter().map(|file| &*file.path).collect();
+        #[cfg(unix)]
+        assert_eq!(paths, ["Godot", "data/a.pck", "lib"]);
+        #[cfg(not(unix))]
+        assert_eq!(paths, ["Godot", "data/a.pck"]);
+        assert_eq!(
+            manifest.files[0],
+            ManifestFile {
+                path: "Godot".to_string(),
+                size: 11,
+                mode: 0o644,
+                sha256: Some(hex(&Sha256::digest("Godot/Godot"))),
+                link: None,
+            }
+        );
+        #[cfg(unix)]
+        assert_eq!(manifest.files[2].link.as_deref(), Some("data"));
+        assert_eq!(changed_files(&dir, &manifest).unwrap(), []);
+
+        fs::write(dir.join("Godot"), "Godot/GodoT").unwrap();
+        fs::remove_file(dir.join("data/a.pck")).unwrap();
+        assert_eq!(
+            changed_files(&dir, &manifest).unwrap(),
+            [
+                ("Godot".to_string(), FileChange::Modified),
+                ("data/a.pck".to_string(), FileChange::Missing)
+            ]
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            fs::write(dir.join("Godot"), "Godot/Godot").unwrap();
+            fs::set_permissions(dir.join("Godot"), fs::Permissions::from_mode(0o600)).unwrap();
+            assert_eq!(
+                changed_files(&dir, &manifest).unwrap()[0],
+                ("Godot".to_string(), FileChange::ModeChanged)
+            );
+        }
+
+        // Files added since are only removed with force.
+        fs::create_dir(dir.join("mine")).unwrap();
+        fs::write(dir.join("mine/project.godot"), "").unwrap();
+        write_install_record(
+            &dir,
+            &InstallRecord {
+                build: godot::Build {
+                    version: godot::Version::parse("4.2.1").unwrap(),
+                    platform: godot::Platform::Linux64,
+                },
+                verified: None,
+                console: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            untracked_files(&dir, &manifest).unwrap(),
+            ["mine/project.godot"]
+        );
+        let err = remove_install(&dir, false).unwrap_err();
+        assert!(
+            err.to_string().contains("like mine/project.godot"),
+            "{}",
+            err
+        );
+        assert!(dir.join("Godot").exists());
+        fs::remove_file(dir.join("mine/project.godot")).unwrap();
+        remove_install(&dir, false).unwrap();
+        assert!(!dir.exists());
+
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("Godot"), "").unwrap();
+        let err = remove_install(&dir, false).unwrap_err();
+        assert!(err.to_string().contains("no manifest"), "{}", err);
+        remove_install(&dir, true).unwrap();
+        assert!(!dir.exists());
+
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join(MANIFEST), r#"{"format": 2, "files": {}}"#).unwrap();
+        let err = read_manifest(&dir).unwrap_err();
+        assert!(err.to_string().contains("needs a newer godotup"), "{}", err);
+    }
+
+    #[test]
+    fn test_executable_alias() {
+        let tmp = test_dir("alias");
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("GodotSharp")).unwrap();
+        let binary = dir.join("Godot_v4.2.1-stable_mono_win64.exe");
+        fs::write(&binary, "editor").unwrap();
+        fs::write(
+            dir.join("Godot_v4.2.1-stable_mono_win64_console.exe"),
+            "console",
+        )
+        .unwrap();
+        let platform = godot::Platform::Win64;
+        assert_eq!(find_executable(dir, platform).unwrap(), binary);
+
+        let alias = create_executable_alias(dir, platform).unwrap();
+        assert_eq!(alias, dir.join(EXECUTABLE_ALIAS));
+        assert_eq!(fs::read_to_string(&alias).unwrap(), "editor");
+        assert_eq!(find_executable(dir, platform).unwrap(), alias);
+        assert_eq!(find_binary(dir, platform).unwrap(), binary);
+        // Made again over the one there.
+        create_executable_alias(dir, platform).unwrap();
+        // As `switch` links it from the bin directory.
+        let tmp = test_dir("alias-bin");
+        let bin = tmp.path();
+        let link = link_alias(bin, "godot", &alias).unwrap();
+        assert_eq!(fs::read_to_string(&link).unwrap(), "editor");
+        link_alias(bin, "godot", &binary).unwrap();
+
+        assert_eq!(
+            find_console(dir, platform).unwrap(),
+            dir.join("Godot_v4.2.1-stable_mono_win64_console.exe")
+        );
+        let console = create_console_alias(dir, platform).unwrap();
+        assert_eq!(
+            console.as_deref(),
+            Some(Path::new("Godot_v4.2.1-stable_mono_win64_console.exe"))
+        );
+        let console_alias = dir.join(CONSOLE_ALIAS);
+        assert_eq!(find_console(dir, platform).unwrap(), console_alias);
+        assert_eq!(fs::read_to_string(&console_alias).unwrap(), "console");
+        assert_eq!(
+            find_console(dir, godot::Platform::Linux64)
+                .unwrap_err()
+                .to_string(),
+            "only Windows builds have a console wrapper; the linux64 build writes to the terminal itself"
+        );
+        assert_eq!(
+            create_console_alias(dir, godot::Platform::Linux64).unwrap(),
+            None
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            fs::read_link(&alias).unwrap(),
+            Path::new("Godot_v4.2.1-stable_mono_win64.exe")
+        );
+    }
+
+    #[test]
+    fn test_console_alias() {
+        // Left in its wrapper directory, the editor has its console next to it.
+        let tmp = test_dir("console-alias");
+        let dir = tmp.path();
+        let wrapper = dir.join("Godot_v4.2.1-stable_win64");
+        fs::create_dir_all(&wrapper).unwrap();
+        let binary = wrapper.join("Godot_v4.2.1-stable_win64.exe");
+        fs::write(&binary, "editor").unwrap();
+        let console = wrapper.join("Godot_v4.2.1-stable_win64_console.exe");
+        fs::write(&console, "console").unwrap();
+        let platform = godot::Platform::Win64;
+        assert_eq!(find_binary(dir, platform).unwrap(), binary);
+        assert_eq!(find_console(dir, platform).unwrap(), console);
+
+        let relative = create_console_alias(dir, platform).unwrap().unwrap();
+        assert_eq!(
+            relative,
+            Path::new("Godot_v4.2.1-stable_win64/Godot_v4.2.1-stable_win64_console.exe")
+        );
+        let alias = dir.join(CONSOLE_ALIAS);
+        assert_eq!(fs::read_to_string(&alias).unwrap(), "console");
+        assert_eq!(find_console(dir, platform).unwrap(), alias);
+
+        // Without the alias the recorded wrapper is used.
+        fs::remove_file(&alias).unwrap();
+        let record = InstallRecord {
+            build: godot::Build {
+                version: godot::Version::parse("4.2.1").unwrap(),
+                platform,
+            },
+            verified: None,
+            console: Some(PathBuf::from("tools/console.exe")),
+        };
+        write_install_record(dir, &record).unwrap();
+        assert_eq!(
+            find_console(dir, platform).unwrap(),
+            dir.join("tools/console.exe")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_verify_install() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = test_dir("verify");
+        let dir = tmp.path();
+        let exe = dir.join("Godot_v4.2.1-stable_linux.x86_64");
+        fs::write(&exe, "#!/bin/sh\necho 4.2.1.stable.official.b09f793f5\n").unwrap();
+        fs::set_permissions(&exe, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(dir.join("Godot_v4.2.1-stable_linux.x86_64_console"), "").unwrap();
+        assert_eq!(find_executable(dir, godot::Platform::Linux64).unwrap(), exe);
+
+        let build = |s| godot::Build {
+            version: godot::Version::parse(s).unwrap(),
+            platform: godot::Platform::Linux64,
+        };
+        let unverified = |s| InstallRecord {
+            build: build(s),
+            verified: None,
+            console: None,
+        };
+        verify_install(dir, unverified("4.2.1")).unwrap();
+        let record = read_install_record(dir).unwrap().unwrap();
+        assert_eq!(record.build, build("4.2.1"));
+        assert_eq!(record.verified, Some(true));
+
+        let err = verify_install(dir, unverified("4.2.1-mono")).unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("reports version 4.2.1 but 4.2.1 (mono) was expected"),
+            "{:#}",
+            err
+        );
+        assert!(dir.exists());
+        assert_eq!(
+            read_install_record(dir).unwrap().unwrap().verified,
+            Some(false)
+        );
+        assert!(read_install_record(&dir.join("missing")).unwrap().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_switched_build() {
+        let tmp = test_dir("switched");
+        let root = tmp.path();
+        for spec in ["4.1.2", "4.2.1"] {
+            let build = godot::Build {
+                version: godot::Version::parse(spec).unwrap(),
+                platform: godot::Platform::Linux64,
+            };
+            let dir = root.join(build.slug());
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("Godot_v4.x-stable_linux.x86_64"), "").unwrap();
+            let record = InstallRecord {
+                build,
+                verified: Some(true),
+                console: None,
+            };
+            write_install_record(&dir, &record).unwrap();
+        }
+        let installed = installed_builds(root).unwrap();
+        let bin = root.join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        assert_eq!(switched_build(&bin, &installed), None);
+
+        let older = installed.resolve("4.1", godot::Platform::Linux64).unwrap();
+        let executable = older.dir.join("Godot_v4.x-stable_linux.x86_64");
+        std::os::unix::fs::symlink(executable, bin.join("godot4")).unwrap();
+        assert_eq!(
+            switched_build(&bin, &installed).map(godot::Version::short),
+            Some(String::from("4.1.2"))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_check_free_space() {
+        let tmp = test_dir("check_free_space");
+        let dir = tmp.path();
+        let missing = dir.join("not/yet/created");
+        let (_, free) = free_space(&missing).unwrap();
+        assert!(check_free_space(&[(dir.to_path_buf(), 0), (missing.clone(), 1)]).is_ok());
+        // Needs on the same filesystem add up.
+        let half = free / 2 + 1;
+        let err = check_free_space(&[(dir.to_path_buf(), half), (missing, half)])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains(&dir.display().to_string()), "{}", err);
+        assert!(err.contains("short"), "{}", err);
+    }
+
+    #[test]
+    fn test_export_templates_dir() {
+        let dir = export_templates_dir("4.2.1.stable.mono").unwrap();
+        assert!(dir.ends_with("export_templates/4.2.1.stable.mono"));
+        let dir = export_templates_dir("3.5.3.stable").unwrap();
+        assert!(dir.ends_with("templates/3.5.3.stable"));
+        assert!(export_templates_dir("../4.2.1.stable").is_err());
+        assert!(export_templates_dir("").is_err());
+    }
+}
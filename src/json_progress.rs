@@ -0,0 +1,229 @@
+//! Progress as newline-delimited JSON, for tools that drive godotup, as
+//! with `--progress json`.
+//!
+//! Every line on the output is one JSON object whose `event` field says
+//! what it is. Fields may be added to events over time, but the ones
+//! listed here keep their meaning. `version` is the version being
+//! installed, e.g. `4.2.1-stable`, and `file` the archive a download is
+//! for when several run at once; either is left out when unknown.
+//!
+//! - `{"event":"phase","phase":"download","version":..,"file":..}`: a
+//!   phase begins. `phase` is `download`, `verify` (checksumming a
+//!   download), `extract` or `retry`.
+//! - `{"event":"download","version":..,"file":..,"received":1048576,"total":52428800}`:
+//!   progress within a phase, named after it, so also `verify` and
+//!   `extract`. `received` counts the bytes processed so far and `total`
+//!   is `null` when unknown. Sent at most every quarter second, and once
+//!   more as the phase ends.
+//! - `{"event":"retry","version":..,"file":..,"attempt":2,"attempts":5}`:
+//!   a failed download is about to be tried again.
+//! - `{"event":"finished","phase":"download","version":..,"file":..}`:
+//!   a phase completed.
+//! - `{"event":"message","text":".."}`: what the terminal would print
+//!   between progress bars.
+//! - `{"event":"warning","text":".."}`: a problem that didn't stop the
+//!   command, which the terminal would print as `warning: ..`.
+//! - `{"event":"installed","version":..,"downloads":[..]}`: an install
+//!   finished; each download has `filename`, `url`, `size`, `transferred`
+//!   (bytes, without a resumed prefix), `resumed`, `cached` and `seconds`,
+//!   as in [`DownloadReport`](super::DownloadReport), and
+//!   `quarantine_removed` is whether the macOS quarantine attribute was
+//!   removed, or null if that wasn't tried.
+//! - `{"event":"success","version":..}` or `{"event":"failure","version":..,"error":".."}`:
+//!   the last event, once the command is done.
+
+use serde_json::{json, Value};
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{godot, InstallReport, Phase, ProgressReporter};
+
+/// How often progress within a phase is reported at most.
+const INTERVAL: Duration = Duration::from_millis(250);
+
+/// A [`ProgressReporter`] writing the events described in the
+/// [module documentation](self).
+pub struct JsonProgress {
+    out: Arc<Mutex<Box<dyn Write + Send>>>,
+    version: Mutex<Option<String>>,
+    file: Option<String>,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    phase: Option<Phase>,
+    received: u64,
+    total: Option<u64>,
+    reported: Option<Instant>,
+}
+
+fn phase_name(phase: Phase) -> &'static str {
+    match phase {
+        Phase::Downloading => "download",
+        Phase::Checksumming => "verify",
+        Phase::Extracting => "extract",
+        Phase::Retrying { .. } => "retry",
+    }
+}
+
+impl JsonProgress {
+    pub fn new(out: Box<dyn Write + Send>) -> Self {
+        Self {
+            out: Arc::new(Mutex::new(out)),
+            version: Mutex::new(None),
+            file: None,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    pub fn stderr() -> Self {
+        Self::new(Box::new(std::io::stderr()))
+    }
+
+    /// Sends the `installed` event for `report`.
+    pub fn installed(&self, report: &InstallReport) {
+        self.emit(
+            "installed",
+            json!({
+                "version": report.version.slug(),
+                "downloads": report.downloads,
+                "quarantine_removed": report.quarantine_removed,
+            }),
+        );
+    }
+
+    /// Sends the `success` event.
+    pub fn succeeded(&self) {
+        self.emit("success", json!({}));
+    }
+
+    /// Sends the `failure` event for `err`.
+    pub fn failed(&self, err: &anyhow::Error) {
+        self.emit("failure", json!({ "error": format!("{:#}", err) }));
+    }
+
+    fn emit(&self, event: &str, fields: Value) {
+        let mut line = json!({ "event": event });
+        if let Some(version) = &*self.version.lock().unwrap() {
+            line["version"] = json!(version);
+        }
+        if let Some(file) = &self.file {
+            line["file"] = json!(file);
+        }
+        if let (Value::Object(line), Value::Object(fields)) = (&mut line, fields) {
+            line.extend(fields);
+        }
+        let mut out = self.out.lock().unwrap();
+        // Progress must never fail an install.
+        let _ = writeln!(out, "{}", line);
+        let _ = out.flush();
+    }
+
+    fn emit_progress(&self, state: &mut State) {
+        if let Some(phase) = state.phase {
+            self.emit(
+                phase_name(phase),
+                json!({ "received": state.received, "total": state.total }),
+            );
+            state.reported = Some(Instant::now());
+        }
+    }
+}
+
+impl ProgressReporter for JsonProgress {
+    fn phase_changed(&self, phase: Phase) {
+        *self.state.lock().unwrap() = State {
+            phase: Some(phase),
+            ..State::default()
+        };
+        self.emit("phase", json!({ "phase": phase_name(phase) }));
+        if let Phase::Retrying { attempt, attempts } = phase {
+            self.emit("retry", json!({ "attempt": attempt, "attempts": attempts }));
+        }
+    }
+
+    fn started(&self, total: Option<u64>) {
+        let mut state = self.state.lock().unwrap();
+        state.total = total;
+        state.received = 0;
+        self.emit_progress(&mut state);
+    }
+
+    fn advanced(&self, bytes: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.received += bytes;
+        if state.reported.is_none_or(|at| at.elapsed() >= INTERVAL) {
+            self.emit_progress(&mut state);
+        }
+    }
+
+    fn finished(&self) {
+        let mut state = self.state.lock().unwrap();
+        self.emit_progress(&mut state);
+        if let Some(phase) = state.phase {
+            self.emit("finished", json!({ "phase": phase_name(phase) }));
+        }
+    }
+
+    fn message(&self, message: &str) {
+        self.emit("message", json!({ "text": message }));
+    }
+
+    fn warning(&self, message: &str) {
+        self.emit("warning", json!({ "text": message }));
+    }
+
+    fn transfer(&self, label: &str) -> Option<Arc<dyn ProgressReporter>> {
+        Some(Arc::new(JsonProgress {
+            out: self.out.clone(),
+            version: Mutex::new(self.version.lock().unwrap().clone()),
+            file: Some(label.to_string()),
+            state: Mutex::new(State::default()),
+        }))
+    }
+
+    fn installing(&self, version: &godot::Version) {
+        *self.version.lock().unwrap() = Some(version.slug());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::Output;
+
+    #[test]
+    fn test_json_progress() {
+        let output = Output::default();
+        let progress = JsonProgress::new(Box::new(output.clone()));
+        progress.installing(&godot::Version::parse("4.2.1").unwrap());
+        progress.phase_changed(Phase::Downloading);
+        progress.started(Some(10));
+        progress.advanced(4);
+        progress.advanced(6);
+        progress.finished();
+        progress.succeeded();
+        let events: Vec<serde_json::Value> = String::from_utf8(output.0.lock().unwrap().clone())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let version = "4.2.1-stable";
+        assert_eq!(
+            events,
+            [
+                serde_json::json!({"event": "phase", "phase": "download", "version": version}),
+                serde_json::json!({"event": "download", "version": version, "received": 0, "total": 10}),
+                // Chunks arriving within a quarter second aren't reported each.
+                serde_json::json!({"event": "download", "version": version, "received": 10, "total": 10}),
+                serde_json::json!({"event": "finished", "phase": "download", "version": version}),
+                serde_json::json!({"event": "success", "version": version}),
+            ]
+        );
+    }
+}
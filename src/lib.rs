@@ -1,222 +1,1438 @@
 use anyhow::anyhow;
 use anyhow::{Context, Result};
-use indicatif::{ProgressBar, ProgressState, ProgressStyle};
+use indicatif::HumanBytes;
+use minisign_verify::PublicKey;
 use reqwest::{header, Client};
-use std::io::{Read, Write};
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
 use std::{
+    cmp::Reverse,
+    collections::HashSet,
     env,
     path::{Path, PathBuf},
+    process,
+    sync::{atomic::Ordering as AtomicOrdering, Arc},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use std::{fs, io};
+pub use tokio_util::sync::CancellationToken;
 
-use serde::{Deserialize, Serialize};
+mod download;
+mod extract;
+mod github;
+pub mod godot;
+mod install;
+pub mod json_progress;
+pub mod logging;
+mod network;
+mod notes;
+mod progress;
+#[cfg(test)]
+mod test_util;
+mod version_list;
+
+pub use download::parse_rate;
+use download::{
+    cached_archives_in, download_from_mirrors, download_path, etag_path, finish_download,
+    hash_prefix, hex, mirror_urls, part_path, partial_downloads_in, probe_mirror, ranges_path,
+    remove_cached_archive, send_traced, upstream_checksum, verify_checksum, ChecksumMismatch,
+    DownloadSettings, MAX_CONCURRENT_DOWNLOADS, RETRY_BASE_DELAY,
+};
+use extract::{check_archive, extract};
+use github::{fetch_github_version_list, GITHUB_API};
+pub use install::{
+    bin_dir, read_install_record, read_manifest, FileChange, InstallRecord, InstalledBuild,
+    InstalledBuilds, Manifest, ManifestFile, Verification, MANIFEST_FORMAT,
+};
+use install::{
+    changed_files, check_free_space, create_console_alias, create_executable_alias,
+    export_templates_dir, find_console, find_executable, godot_version_dir, has_console,
+    install_root, install_staged, installed_version_list, link_alias, reinstall, remove_install,
+    remove_quarantine, remove_stale_staging, switched_build, templates_name, verify_install,
+    write_install_record, write_manifest, EXTRACTED_SIZE_FACTOR, XATTR,
+};
+#[cfg(unix)]
+use install::{ensure_executable, manifest_path};
+#[cfg(windows)]
+use install::{unblock, unblock_executables};
+pub use network::explain_error;
+use network::{build_client, load_certificates, TLS_ROOTS};
+pub use notes::markdown_to_text;
+use notes::{fetch_github_release_notes, fetch_notes};
+use progress::{download_style, DownloadTally};
+pub use progress::{NoProgress, Phase, ProgressReporter, TerminalProgress};
+use version_list::{
+    crawl_mirror, is_stale, load_version_list, resolve_or_refresh, update_from_sources,
+    version_list_path, ListCacheMeta,
+};
 
-#[derive(Default)]
 pub struct CliApp {
     config: Config,
+    /// Used for every request, see [`build_client`].
+    client: Client,
+    progress: Arc<dyn ProgressReporter>,
+    /// Warnings from loading the config, given to the reporter installed by
+    /// [`CliApp::with_progress`] so they come out in its format.
+    warnings: Vec<String>,
+}
+
+impl Default for CliApp {
+    fn default() -> Self {
+        let config = Config::default();
+        let client = build_client(&config).expect("the default config makes a valid client");
+        Self {
+            config,
+            client,
+            progress: Arc::new(NoProgress),
+            warnings: Vec::new(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
-    version_list_proxy_url: String,
-    download_proxy_url: String,
+    /// Version lists to merge, as URLs or local paths. Earlier sources win
+    /// when several list the same build.
+    version_list_sources: Vec<String>,
+    /// Download mirrors to try in order. Relative artifact URLs are resolved
+    /// against them, and URLs on one of them can be fetched from the others.
+    download_mirrors: Vec<String>,
+    /// Have `switch` link `godot` in the bin directory to the version.
     set_godot_bin: bool,
+    /// Have `switch` also link `godot4` there when switching to Godot 4.
     set_godot4_bin: bool,
+    verify_install: bool,
+    version_list_source: VersionListSource,
+    github_repos: Vec<String>,
+    /// Token for GitHub API requests, which raises the rate limit for
+    /// anonymous ones. `GODOTUP_GITHUB_TOKEN` or `GITHUB_TOKEN` otherwise.
+    github_token: Option<String>,
+    version_list_ttl_secs: u64,
+    /// Minisign public key, base64 encoded, that remote version lists must
+    /// be signed with. Lists aren't checked without one.
+    version_list_public_key: Option<String>,
+    /// Update the version list once and retry when a requested version
+    /// isn't in it.
+    auto_refresh_on_miss: bool,
+    /// Refuse to install artifacts the version list has no checksum for.
+    require_checksums: bool,
+    /// Occasionally mention newer stable releases after a command.
+    notify_new_releases: bool,
+    /// Minimum time between two such notices.
+    notify_interval_hours: u64,
+    /// How many times to try a download before giving up.
+    download_attempts: u32,
+    /// Give up on a download attempt after this long without receiving data.
+    download_stall_timeout_secs: u64,
+    /// Connections to download a file over when the server supports ranges.
+    download_connections: u32,
+    /// Combined download speed limit in bytes per second, 0 for none.
+    max_download_rate: u64,
+    /// Proxy for all requests, instead of `HTTPS_PROXY`/`HTTP_PROXY`.
+    /// `NO_PROXY` still applies.
+    proxy_url: Option<String>,
+    proxy_username: Option<String>,
+    proxy_password: Option<String>,
+    /// PEM file with more root certificates to trust, e.g. the one of a
+    /// TLS-intercepting proxy. May hold several.
+    extra_ca_cert: Option<PathBuf>,
+    /// Don't check certificates at all. Only for when nothing else works.
+    danger_accept_invalid_certs: bool,
+    /// Only connect over IPv4, for networks where IPv6 is broken.
+    ipv4_only: bool,
+    /// Redirects to follow for one request before giving up.
+    max_redirects: usize,
+    /// Give up on connecting to a server after this long. Stalled transfers
+    /// are covered by `download_stall_timeout_secs`.
+    connect_timeout_secs: u64,
+    /// Appended to the `User-Agent` of every request, e.g. to tag the
+    /// traffic of an organization.
+    user_agent_suffix: Option<String>,
+    /// Show no progress and only final status lines, like `--quiet`.
+    quiet: bool,
+    /// indicatif template for download bars, e.g. `{bar} {bytes_per_sec}`.
+    progress_template: Option<String>,
+    /// Characters to fill download bars with, e.g. `=> `.
+    progress_chars: Option<String>,
+    /// Where downloaded archives are kept, by default `cache` in the godotup
+    /// data dir.
+    cache_dir: Option<PathBuf>,
+    /// Keep archives in the cache after installing them, so installing the
+    /// same build again needs no download.
+    keep_archives: bool,
+    /// How many files to extract at once, 0 for one per CPU.
+    extract_workers: usize,
+    /// Strip the directory mono archives wrap everything in, so the
+    /// executable is at the top of every install.
+    flatten_archives: bool,
+    /// On macOS, remove the quarantine attribute from installed editors so
+    /// Gatekeeper doesn't block their first launch.
+    remove_quarantine: bool,
+    /// On Windows, remove the mark Windows puts on downloads from the
+    /// archives and the executables installed from them, so SmartScreen
+    /// doesn't ask before every launch.
+    unblock_downloads: bool,
+    /// What to do when the version is already installed.
+    on_existing: OnExisting,
+    /// The single version list source of older configs, read as the first
+    /// of `version_list_sources`.
+    #[serde(skip_serializing)]
+    version_list_proxy_url: Option<String>,
+    /// The single download mirror of older configs, read as the first of
+    /// `download_mirrors`.
+    #[serde(skip_serializing)]
+    download_proxy_url: Option<String>,
+}
+
+/// What [`CliApp::install_godot`] installed and downloaded for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstallReport {
+    pub version: godot::Version,
+    /// The editor first, then the export templates if installed.
+    pub downloads: Vec<DownloadReport>,
+    /// Whether the macOS quarantine attribute was removed from the editor,
+    /// if that was tried.
+    pub quarantine_removed: Option<bool>,
+}
+
+/// How one archive of an install was downloaded.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DownloadReport {
+    pub filename: String,
+    /// Where the download finished.
+    pub url: String,
+    /// Size of the archive.
+    pub size: u64,
+    /// Bytes downloaded by this install, without what an earlier one had.
+    pub transferred: u64,
+    /// Whether this install continued an earlier one's partial download.
+    pub resumed: bool,
+    /// Whether the archive was already in the cache, at `url`.
+    pub cached: bool,
+    /// Time from the first request to a complete download.
+    pub seconds: f64,
+}
+
+impl DownloadReport {
+    /// Average bytes per second transferred.
+    pub fn speed(&self) -> u64 {
+        (self.transferred as f64 / self.seconds.max(1e-3)) as u64
+    }
+}
+
+impl std::fmt::Display for DownloadReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.cached {
+            return write!(
+                f,
+                "Using cached {} ({}) from {}",
+                self.filename,
+                HumanBytes(self.size),
+                self.url
+            );
+        }
+        write!(
+            f,
+            "Downloaded {} ({}) in {:.1}s ({}/s) from {}",
+            self.filename,
+            HumanBytes(self.size),
+            self.seconds,
+            HumanBytes(self.speed()),
+            self.url
+        )?;
+        if self.resumed {
+            write!(f, ", resumed with {} left", HumanBytes(self.transferred))?;
+        }
+        Ok(())
+    }
+}
+
+/// An archive in the download cache, see [`CliApp::cached_archives`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedArchive {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Whether this is an interrupted download, see
+    /// [`CliApp::partial_downloads`].
+    pub partial: bool,
+    pub modified: SystemTime,
+}
+
+/// Where `update` gets the list of available versions from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionListSource {
+    /// The curated lists in `version_list_sources`.
+    Static,
+    /// The release assets of `github_repos`, queried through the GitHub API.
+    GitHub,
+}
+
+/// What to do when installing a version that is already installed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnExisting {
+    /// Stop with an error.
+    #[default]
+    Fail,
+    /// Install it again, replacing the old install once the new one is
+    /// ready.
+    Replace,
+    /// Leave the old install be and succeed.
+    Skip,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
-            version_list_proxy_url: String::from(
+            version_list_sources: vec![String::from(
                 "https://raw.githubusercontent.com/Hapenia-Lans/godotup/main/versions.yml",
-            ),
-            download_proxy_url: String::from("https://downloads.tuxfamily.org/godotengine/"),
+            )],
+            download_mirrors: vec![String::from("https://downloads.tuxfamily.org/godotengine/")],
             set_godot_bin: true,
             set_godot4_bin: true,
+            verify_install: true,
+            version_list_source: VersionListSource::Static,
+            github_repos: vec![
+                String::from("godotengine/godot-builds"),
+                String::from("godotengine/godot"),
+            ],
+            github_token: None,
+            version_list_ttl_secs: 24 * 60 * 60,
+            version_list_public_key: None,
+            auto_refresh_on_miss: true,
+            require_checksums: false,
+            notify_new_releases: true,
+            notify_interval_hours: 24,
+            download_attempts: 5,
+            download_stall_timeout_secs: 30,
+            download_connections: 4,
+            max_download_rate: 0,
+            proxy_url: None,
+            proxy_username: None,
+            proxy_password: None,
+            extra_ca_cert: None,
+            danger_accept_invalid_certs: false,
+            ipv4_only: false,
+            max_redirects: 10,
+            connect_timeout_secs: 30,
+            user_agent_suffix: None,
+            quiet: false,
+            progress_template: None,
+            progress_chars: None,
+            cache_dir: None,
+            keep_archives: true,
+            extract_workers: 0,
+            flatten_archives: true,
+            remove_quarantine: true,
+            unblock_downloads: true,
+            on_existing: OnExisting::Fail,
+            version_list_proxy_url: None,
+            download_proxy_url: None,
+        }
+    }
+}
+
+/// How fast a mirror served the probe in [`CliApp::test_mirrors`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MirrorSpeed {
+    /// Time until the response headers arrived.
+    pub latency: Duration,
+    pub bytes: u64,
+    /// Time until the whole probe arrived.
+    pub elapsed: Duration,
+}
+
+impl MirrorSpeed {
+    /// Bytes per second.
+    pub fn throughput(&self) -> u64 {
+        (self.bytes as f64 / self.elapsed.as_secs_f64().max(1e-3)) as u64
+    }
+}
+
+/// An installed version with a newer release available, see
+/// [`CliApp::outdated`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outdated {
+    pub installed: godot::Version,
+    pub available: godot::Version,
+}
+
+/// A version shown by [`CliApp::list`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListEntry {
+    pub version: godot::Version,
+    /// When it was released (`YYYY-MM-DD`), if known.
+    pub released: Option<String>,
+    /// Whether it came from the local overlay, `versions.local.yml`.
+    pub local: bool,
+    /// The version list source its artifact was merged from, if recorded.
+    pub source: Option<String>,
+    /// Whether it is installed.
+    pub installed: bool,
+    /// Whether the installed binary reported the right version, `None` if
+    /// it is not installed or wasn't checked.
+    pub verified: Option<bool>,
+}
+
+/// Which versions [`CliApp::list`] shows and in what order.
+#[derive(Debug, Clone)]
+pub struct ListOptions {
+    /// Only installed versions, including prereleases and those no longer
+    /// in the version list.
+    pub installed_only: bool,
+    /// Only versions released on or after this date (`YYYY-MM-DD`).
+    pub since: Option<String>,
+    /// Only versions on this channel.
+    pub channel: Option<godot::Channel>,
+    /// Also alpha, beta, rc and dev releases, which are left out unless a
+    /// `channel` is given.
+    pub include_prerelease: bool,
+    /// Most recently released first instead of newest version first.
+    pub by_date: bool,
+    /// Update the version list first if it is older than the configured
+    /// TTL. Never done for `installed_only`, which doesn't need the list to
+    /// be current.
+    pub refresh: bool,
+}
+
+impl Default for ListOptions {
+    fn default() -> Self {
+        Self {
+            installed_only: false,
+            since: None,
+            channel: None,
+            include_prerelease: false,
+            by_date: false,
+            refresh: true,
         }
     }
 }
 
-pub mod godot {
-    use std::{collections::HashMap, env, fmt::Display};
+impl ListOptions {
+    /// Whether [`CliApp::list`] updates a stale version list first.
+    fn refreshes(&self) -> bool {
+        self.refresh && !self.installed_only
+    }
+}
+
+/// The version [`CliApp::switch`] switched to and the links it made.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Switched {
+    pub version: godot::Version,
+    pub executable: PathBuf,
+    /// Links in [`bin_dir`] pointing at `executable`, such as `godot`.
+    pub links: Vec<PathBuf>,
+}
+
+/// How `install_godot` should behave beyond which version to install.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstallOptions {
+    /// Run the installed binary to check its version, unless disabled in the
+    /// config.
+    pub verify: bool,
+    /// Update the version list first if it is older than the configured TTL,
+    /// or doesn't have the requested version yet. When unset, godotup stays
+    /// offline until the download itself.
+    pub refresh: bool,
+    /// Download speed limit in bytes per second, overriding the config.
+    /// 0 means no limit.
+    pub max_rate: Option<u64>,
+    /// Also install the export templates, downloading them alongside the
+    /// editor.
+    pub templates: bool,
+    /// Install even if there doesn't look to be enough free disk space.
+    pub force: bool,
+    /// Discard any cached or partial download of the archives and download
+    /// them from scratch.
+    pub force_download: bool,
+    /// Extract several files at once, see `extract_workers` in the config.
+    pub parallel_extraction: bool,
+    /// What to do when the version is already installed, overriding the
+    /// config.
+    pub on_existing: Option<OnExisting>,
+    /// Treat prereleases like stable releases when resolving the version,
+    /// see [`godot::VersionList::resolve_with`].
+    pub include_prerelease: bool,
+}
 
-    use serde::{Deserialize, Serialize};
+impl Default for InstallOptions {
+    fn default() -> Self {
+        Self {
+            verify: true,
+            refresh: true,
+            max_rate: None,
+            templates: false,
+            force: false,
+            force_download: false,
+            parallel_extraction: true,
+            on_existing: None,
+            include_prerelease: false,
+        }
+    }
+}
 
-    #[derive(Serialize, Deserialize)]
-    pub struct VersionList {
-        versions: HashMap<Version, String>,
+impl Config {
+    /// Folds keys of older configs into the ones replacing them, returning
+    /// each key found and its replacement.
+    fn migrate(&mut self) -> Vec<(&'static str, &'static str)> {
+        let mut found = Vec::new();
+        if let Some(url) = self.version_list_proxy_url.take() {
+            found.push(("version_list_proxy_url", "version_list_sources"));
+            if !self.version_list_sources.contains(&url) {
+                self.version_list_sources.insert(0, url);
+            }
+        }
+        if let Some(url) = self.download_proxy_url.take() {
+            found.push(("download_proxy_url", "download_mirrors"));
+            if !self.download_mirrors.contains(&url) {
+                self.download_mirrors.insert(0, url);
+            }
+        }
+        found
     }
+}
 
-    impl VersionList {
-        pub fn find_url(&self, vers: &Version) -> Option<&String> {
-            self.versions.get(vers)
+impl CliApp {
+    /// Loads the configuration, writing the defaults if there is none yet.
+    /// `ipv4_only` restricts connections to IPv4 even if the config doesn't.
+    pub fn load(ipv4_only: bool) -> Result<Self> {
+        let mut config: Config =
+            confy::load("godotup", None).context("Couldn't load the configuration")?;
+        config.ipv4_only |= ipv4_only;
+        let mut warnings = Vec::new();
+        for (old, new) in config.migrate() {
+            warnings.push(format!(
+                "`{}` in {} is deprecated, use `{}` instead",
+                old,
+                confy::get_configuration_file_path("godotup", None)?.display(),
+                new
+            ));
+        }
+        if let Err(err) = download_style(
+            config.progress_template.as_deref(),
+            config.progress_chars.as_deref(),
+        ) {
+            warnings.push(format!("{:#}; using the default progress bar", err));
+            config.progress_template = None;
+            config.progress_chars = None;
         }
+        if config.danger_accept_invalid_certs {
+            warnings.push(String::from(
+                "danger_accept_invalid_certs is set, so certificates aren't checked \
+                 and anyone on the network can tamper with downloads",
+            ));
+        }
+        let client = build_client(&config)?;
+        Ok(Self {
+            config,
+            client,
+            progress: Arc::new(NoProgress),
+            warnings,
+        })
     }
 
-    #[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
-    pub enum Platform {
-        Win32,
-        Win64,
-        Linux32,
-        Linux64,
-        Macos,
+    /// Whether the config asks for quiet output.
+    pub fn quiet(&self) -> bool {
+        self.config.quiet
     }
 
-    #[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
-    pub enum Suffix {
-        Stable,
-        Alpha(u8),
-        Beta(u8),
-        Rc(u8),
+    /// Terminal progress with the configured bar style.
+    pub fn terminal_progress(&self, quiet: bool) -> TerminalProgress {
+        TerminalProgress::new(quiet).with_style(
+            self.config.progress_template.clone(),
+            self.config.progress_chars.clone(),
+        )
     }
-    #[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
-    pub struct Version {
-        pub major: u8,
-        pub minor: u8,
-        pub patch: u8,
-        pub suffix: Suffix,
-        pub is_mono: bool,
-        pub platform: Platform,
+
+    /// Reports the progress of installs to `progress` instead of nowhere.
+    /// Warnings from loading are reported to it straight away.
+    pub fn with_progress(self, progress: Arc<dyn ProgressReporter>) -> Self {
+        for warning in &self.warnings {
+            progress.warning(warning);
+        }
+        Self {
+            progress,
+            warnings: Vec::new(),
+            ..self
+        }
     }
 
-    impl Display for Version {
-        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            let suffix_str = match self.suffix {
-                Suffix::Alpha(x) => format!("-alpha{}", x),
-                Suffix::Beta(x) => format!("-beta{}", x),
-                Suffix::Rc(x) => format!("-rc{}", x),
-                Suffix::Stable => format!("-stable"),
-            };
-            let mono_str = if self.is_mono { "_mono" } else { "" };
-            write!(
-                f,
-                "Godot_v{}.{}.{}{}{}",
-                self.major, self.minor, self.patch, suffix_str, mono_str
+    /// Fetches a fresh version list and returns which releases it added or
+    /// dropped. The cached list is only replaced once the new one has been
+    /// fully downloaded and parsed, so a failed update leaves the previous
+    /// list untouched.
+    ///
+    /// With a `version_list_public_key` configured, remote lists must come
+    /// with a valid minisign signature (`<url>.minisig`) unless
+    /// `allow_unsigned` is set.
+    pub async fn update_version_list(
+        &self,
+        allow_unsigned: bool,
+    ) -> Result<godot::VersionListDiff> {
+        let version_list = version_list_path()?;
+        let read = || {
+            fs::read_to_string(&version_list)
+                .ok()
+                .and_then(|text| godot::VersionList::parse(&text).ok())
+                .unwrap_or_default()
+        };
+        let old = read();
+        let key = match &self.config.version_list_public_key {
+            Some(key) if !allow_unsigned => Some(
+                PublicKey::from_base64(key)
+                    .map_err(|err| anyhow!("invalid version_list_public_key: {}", err))?,
+            ),
+            _ => None,
+        };
+        self.fetch_version_list(&version_list, key.as_ref()).await?;
+        Ok(old.diff(&read()))
+    }
+
+    async fn fetch_version_list(&self, version_list: &Path, key: Option<&PublicKey>) -> Result<()> {
+        match self.config.version_list_source {
+            VersionListSource::Static => {
+                let failures = update_from_sources(
+                    &self.client,
+                    &self.config.version_list_sources,
+                    version_list,
+                    key,
+                )
+                .await?;
+                for failure in failures {
+                    self.progress.warning(&failure);
+                }
+                Ok(())
+            }
+            VersionListSource::GitHub => {
+                let list = fetch_github_version_list(
+                    &self.client,
+                    &self.config.github_repos,
+                    self.github_token().as_deref(),
+                )
+                .await?;
+                write_atomically(version_list, list.to_yaml()?.as_bytes())?;
+                ListCacheMeta {
+                    fetched_at: Some(unix_now()),
+                    ..Default::default()
+                }
+                .store(version_list)
+            }
+        }
+    }
+
+    /// The cached version list, updated first if `refresh` is set and it is
+    /// older than the configured TTL. When that update fails the stale copy
+    /// is used with a warning.
+    async fn version_list(&self, refresh: bool) -> Result<godot::VersionList> {
+        let path = version_list_path()?;
+        let ttl = Duration::from_secs(self.config.version_list_ttl_secs);
+        if refresh && is_stale(&path, ttl, unix_now()) {
+            self.progress
+                .message("Version list is out of date, updating...");
+            match self.update_version_list(false).await {
+                Ok(diff) if !diff.is_empty() => self.progress.message(&diff.to_string()),
+                Ok(_) => {}
+                Err(err) => self
+                    .progress
+                    .warning(&format!("{:#}; using the cached version list", err)),
+            }
+        }
+        load_version_list(&*self.progress)
+    }
+
+    /// Installs the newest version matching `spec` (see
+    /// [`godot::VersionList::resolve`]) and reports what was installed.
+    ///
+    /// Unless verification is turned off in `options` or the config, the
+    /// extracted binary is run to check it reports the expected version. A
+    /// failed check leaves the install in place but records it as
+    /// unverified.
+    ///
+    /// Cancelling `cancel` stops the download or extraction with a
+    /// [`Cancelled`] error. A partial download is kept to resume next time.
+    pub async fn install_godot(
+        &self,
+        spec: &str,
+        options: InstallOptions,
+        cancel: Option<CancellationToken>,
+    ) -> Result<InstallReport> {
+        let cancel = cancel.unwrap_or_default();
+        let platform = godot::Platform::host()?;
+        for stale in remove_stale_staging(&install_root()) {
+            self.progress.message(&format!(
+                "Removed {}, left by an interrupted install",
+                stale.display()
+            ));
+        }
+        let vcs_list = self.version_list(options.refresh).await?;
+        let refresh = (options.refresh && self.config.auto_refresh_on_miss).then_some(|| async {
+            self.progress.message(&format!(
+                "`{}` is not in the version list, updating...",
+                spec
+            ));
+            self.update_version_list(false)
+                .await
+                .and_then(|_| load_version_list(&*self.progress))
+                .inspect_err(|err| self.progress.warning(&format!("{:#}", err)))
+        });
+        let (vcs_list, version) = resolve_or_refresh(
+            vcs_list,
+            spec,
+            platform,
+            options.include_prerelease,
+            refresh,
+        )
+        .await?;
+        self.progress.message(&format!(
+            "Installing {} (resolved from `{}`)",
+            version.short(),
+            spec
+        ));
+        self.progress.installing(&version);
+        let artifact = vcs_list
+            .find_artifact(&version, platform)
+            .context(format!("Version {} not found", &version))?;
+        let build = godot::Build {
+            version: version.clone(),
+            platform,
+        };
+        let dir = godot_version_dir(&build);
+        let on_existing = options.on_existing.unwrap_or(self.config.on_existing);
+        let replacing = dir.exists();
+        if !reinstall(&dir, &version, on_existing)? {
+            self.progress.message(&format!(
+                "{} is already installed in {}, skipping",
+                version.short(),
+                dir.display()
+            ));
+            return Ok(InstallReport {
+                version,
+                downloads: Vec::new(),
+                quarantine_removed: None,
+            });
+        }
+        let templates = if options.templates {
+            Some(
+                vcs_list
+                    .find_templates(&version)
+                    .with_context(|| format!("No export templates listed for {}", version))?,
             )
+        } else {
+            None
+        };
+        let artifacts: Vec<_> = [Some(artifact), templates].into_iter().flatten().collect();
+        if !options.force {
+            self.check_install_space(&artifacts, &build).await?;
+        }
+        let started = Instant::now();
+        let (archives, downloads): (Vec<_>, Vec<_>) = self
+            .fetch_artifacts(&artifacts, &options, &cancel, spec)
+            .await?
+            .into_iter()
+            .unzip();
+        if archives.len() > 1 {
+            self.progress.message(&format!(
+                "Downloaded {} files ({}) in {:.1?}",
+                archives.len(),
+                HumanBytes(downloads.iter().map(|download| download.size).sum()),
+                started.elapsed()
+            ));
         }
+        for (artifact, archive) in artifacts.iter().zip(&archives) {
+            // Cached archives too, which may have been cut short or replaced.
+            check_archive(archive, artifact.size)?;
+        }
+        #[cfg(windows)]
+        if self.config.unblock_downloads {
+            // So what is extracted from them isn't marked in turn.
+            archives
+                .iter()
+                .for_each(|archive| unblock(archive, &*self.progress));
+        }
+        let workers = self.extract_workers(&options);
+        let flatten = self.config.flatten_archives;
+        let verify = options.verify && self.config.verify_install;
+        let verified = install_staged(
+            &archives[0],
+            &dir,
+            flatten,
+            workers,
+            &cancel,
+            &self.progress,
+            |staging, files| {
+                #[cfg(unix)]
+                let files = {
+                    let made = ensure_executable(staging, platform)?
+                        .map(|binary| manifest_path(staging, &binary));
+                    files
+                        .into_iter()
+                        .map(|file| match made.as_deref() == Some(&*file.path) {
+                            true => ManifestFile {
+                                mode: 0o755,
+                                ..file
+                            },
+                            false => file,
+                        })
+                        .collect()
+                };
+                write_manifest(staging, &Manifest::new(artifact, files))?;
+                #[cfg(windows)]
+                if self.config.unblock_downloads {
+                    unblock_executables(staging, &*self.progress);
+                }
+                create_executable_alias(staging, platform)?;
+                let record = InstallRecord {
+                    build: build.clone(),
+                    verified: None,
+                    console: create_console_alias(staging, platform)?,
+                };
+                if verify && replacing {
+                    // Never swap a working install for a broken one.
+                    verify_install(staging, record).with_context(|| {
+                        format!(
+                            "verification of {} failed; the existing install in {} was kept",
+                            build.version.short(),
+                            dir.display()
+                        )
+                    })?;
+                    return Ok(Ok(()));
+                }
+                if verify {
+                    return Ok(verify_install(staging, record));
+                }
+                write_install_record(staging, &record)?;
+                Ok(Ok(()))
+            },
+        )
+        .await?;
+        let quarantine_removed = (cfg!(target_os = "macos")
+            && platform.is_app_bundle()
+            && self.config.remove_quarantine)
+            .then(|| match remove_quarantine(Path::new(XATTR), &dir) {
+                Ok(()) => {
+                    self.progress.message(&format!(
+                        "Removed the quarantine attribute from {}",
+                        dir.display()
+                    ));
+                    true
+                }
+                Err(err) => {
+                    self.progress.warning(&format!(
+                        "{:#}; macOS may block the editor's first launch",
+                        err
+                    ));
+                    false
+                }
+            });
+        if let Some(archive) = archives.get(1) {
+            // Godot only finds templates outside their `templates` directory.
+            let dir = export_templates_dir(&templates_name(archive)?)?;
+            // Templates are shared between installs, so aren't in the manifest.
+            extract(archive, &dir, true, workers, &cancel, &self.progress).await?;
+            #[cfg(windows)]
+            if self.config.unblock_downloads {
+                unblock_executables(&dir, &*self.progress);
+            }
+            self.progress
+                .message(&format!("Installed export templates to {}", dir.display()));
+        }
+        for (artifact, archive) in artifacts.iter().zip(&archives) {
+            // Without a checksum the archive sits in a directory keyed by
+            // this process, which no later run looks in, so it isn't kept.
+            if artifact.sha512.is_none() || !self.config.keep_archives {
+                let _ = fs::remove_file(archive);
+                let _ = fs::remove_dir(archive.parent().unwrap());
+            }
+        }
+        verified.with_context(|| {
+            format!(
+                "verification of {} failed; it was left unverified in {} (use --no-verify to skip)",
+                version.short(),
+                dir.display()
+            )
+        })?;
+        Ok(InstallReport {
+            version,
+            downloads,
+            quarantine_removed,
+        })
     }
 
-    impl Version {
-        pub fn versnum_to_str(&self) -> String {
-            format!("{}.{}.{}", self.major, self.minor, self.patch)
+    /// Fails early if the downloads of `artifacts`, or what they extract to,
+    /// won't fit on disk. Artifacts of unknown size are not counted.
+    async fn check_install_space(
+        &self,
+        artifacts: &[&godot::Artifact],
+        build: &godot::Build,
+    ) -> Result<()> {
+        let mut needs = Vec::new();
+        for (index, artifact) in artifacts.iter().enumerate() {
+            let Some(size) = self.artifact_size(artifact).await else {
+                continue;
+            };
+            let path = download_path(
+                &self.cache_dir()?,
+                &artifact.download_name(),
+                artifact.sha512.as_deref(),
+            );
+            // What is already on disk won't be downloaded again.
+            let existing = [part_path(&path), path.clone()]
+                .iter()
+                .filter_map(|path| path.metadata().ok())
+                .map(|metadata| metadata.len())
+                .max()
+                .unwrap_or(0);
+            needs.push((self.cache_dir()?, size.saturating_sub(existing)));
+            let target = match index {
+                0 => godot_version_dir(build),
+                _ => dirs::data_dir().context("Data dir not found")?,
+            };
+            needs.push((target, size * EXTRACTED_SIZE_FACTOR));
         }
-        pub fn to_filename(&self) -> String {
-            format!("{}{}.zip", self, get_platform_suffix())
+        check_free_space(&needs)
+    }
+
+    /// The size of `artifact` from the version list, or else from what its
+    /// first mirror reports.
+    async fn artifact_size(&self, artifact: &godot::Artifact) -> Option<u64> {
+        if artifact.size.is_some() {
+            return artifact.size;
         }
+        let url = mirror_urls(&artifact.url, &self.config.download_mirrors)
+            .into_iter()
+            .next()?;
+        let request = self
+            .client
+            .head(&url)
+            .timeout(self.download_settings().stall_timeout);
+        let response = send_traced(request).await.ok()?.error_for_status().ok()?;
+        // The body of a HEAD response is empty, so its length says nothing.
+        response
+            .headers()
+            .get(header::CONTENT_LENGTH)?
+            .to_str()
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    /// Downloads `artifacts` at once, at most [`MAX_CONCURRENT_DOWNLOADS`] at
+    /// a time and each with its own progress, and returns where each
+    /// complete and checked archive is and how it was downloaded. All
+    /// downloads run to the end even if
+    /// one fails, so the others leave resumable state behind.
+    async fn fetch_artifacts(
+        &self,
+        artifacts: &[&godot::Artifact],
+        options: &InstallOptions,
+        cancel: &CancellationToken,
+        spec: &str,
+    ) -> Result<Vec<(PathBuf, DownloadReport)>> {
+        let slots = tokio::sync::Semaphore::new(MAX_CONCURRENT_DOWNLOADS);
+        let fetches = artifacts.iter().map(|artifact| async {
+            let _slot = slots.acquire().await?;
+            let progress = match artifacts.len() {
+                1 => None,
+                _ => self.progress.transfer(&artifact_label(artifact)),
+            };
+            let progress = progress.unwrap_or_else(|| self.progress.clone());
+            self.fetch_artifact(artifact, options, cancel, spec, &progress)
+                .await
+        });
+        futures_util::future::join_all(fetches)
+            .await
+            .into_iter()
+            .collect()
     }
 
-    fn get_platform_suffix() -> String {
-        match env::consts::OS {
-            "linux" => format!("linux.{}", get_arch()),
-            "windows" => match get_arch() {
-                "x86_32" => "win32.exe",
-                "x86_64" => "win64.exe",
-                _ => unreachable!(),
+    /// Downloads `artifact` from the first mirror that has it and checks its
+    /// checksum, unless the cache already has it. Returns where the complete
+    /// archive is and how it was downloaded.
+    async fn fetch_artifact(
+        &self,
+        artifact: &godot::Artifact,
+        options: &InstallOptions,
+        cancel: &CancellationToken,
+        spec: &str,
+        progress: &Arc<dyn ProgressReporter>,
+    ) -> Result<(PathBuf, DownloadReport)> {
+        let tmp_path = download_path(
+            &self.cache_dir()?,
+            &artifact.download_name(),
+            artifact.sha512.as_deref(),
+        );
+        let started = Instant::now();
+        if options.force_download {
+            match fs::remove_dir_all(tmp_path.parent().unwrap()) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
             }
-            .to_string(),
-            _ => {
-                unimplemented!("godotup is not available in your system currently.")
+        }
+        if let (Some(expected), Ok(metadata)) = (&artifact.sha512, tmp_path.metadata()) {
+            let actual = hash_prefix(&tmp_path, metadata.len(), progress).await?;
+            if hex(&actual.finalize()).eq_ignore_ascii_case(expected.trim()) {
+                let report = DownloadReport {
+                    filename: artifact.filename.clone(),
+                    url: tmp_path.display().to_string(),
+                    size: metadata.len(),
+                    transferred: 0,
+                    resumed: false,
+                    cached: true,
+                    seconds: started.elapsed().as_secs_f64(),
+                };
+                progress.message(&report.to_string());
+                return Ok((tmp_path, report));
             }
+            progress.message(&format!(
+                "Cached {} doesn't match its checksum, downloading it again",
+                tmp_path.display()
+            ));
+            fs::remove_file(&tmp_path)?;
         }
+        fs::create_dir_all(tmp_path.parent().unwrap())?;
+        let part = part_path(&tmp_path);
+        // A download that doesn't match its checksum, say from resuming onto
+        // bytes that changed, gets one more try from scratch.
+        let mut from_scratch = false;
+        let report = loop {
+            let tally = Arc::new(DownloadTally::new(progress.clone()));
+            let tallied: Arc<dyn ProgressReporter> = tally.clone();
+            let (url, sha512) = download_from_mirrors(
+                &self.client,
+                &mirror_urls(&artifact.url, &self.config.download_mirrors),
+                &part,
+                DownloadSettings {
+                    max_rate: options.max_rate.unwrap_or(self.config.max_download_rate),
+                    ..self.download_settings()
+                },
+                cancel,
+                &tallied,
+            )
+            .await
+            .inspect_err(|err| {
+                if !is_cancelled(err) {
+                    return;
+                }
+                match artifact.sha512 {
+                    Some(_) => self.progress.message(&format!(
+                        "Kept the partial download {}; installing {} again resumes it",
+                        part.display(),
+                        spec
+                    )),
+                    // Without a checksum it was downloaded to a directory keyed by
+                    // this process, where no later run looks for it.
+                    None => {
+                        let _ = fs::remove_dir_all(tmp_path.parent().unwrap());
+                    }
+                }
+            })?;
+            let report = DownloadReport {
+                filename: artifact.filename.clone(),
+                size: part.metadata()?.len(),
+                transferred: tally.transferred.load(AtomicOrdering::SeqCst),
+                resumed: tally.resumed.load(AtomicOrdering::SeqCst),
+                cached: false,
+                seconds: started.elapsed().as_secs_f64(),
+                url,
+            };
+            tracing::debug!(
+                file = %report.filename,
+                url = %report.url,
+                size = report.size,
+                transferred = report.transferred,
+                resumed = report.resumed,
+                seconds = report.seconds,
+                "download complete"
+            );
+            progress.message(&report.to_string());
+            let checksum = match &artifact.sha512 {
+                Some(sha512) => Some(sha512.clone()),
+                None => {
+                    upstream_checksum(
+                        &self.client,
+                        &report.url,
+                        &artifact.filename,
+                        progress.as_ref(),
+                    )
+                    .await
+                }
+            };
+            match verify_checksum(
+                &part,
+                &sha512,
+                checksum.as_deref(),
+                self.config.require_checksums,
+                progress.as_ref(),
+            ) {
+                Err(err) if !from_scratch && err.is::<ChecksumMismatch>() => {
+                    progress.message(&format!("{:#}, downloading it again from scratch", err));
+                    let _ = fs::remove_file(etag_path(&part));
+                    let _ = fs::remove_file(ranges_path(&part));
+                    from_scratch = true;
+                }
+                result => {
+                    result?;
+                    break report;
+                }
+            }
+        };
+        finish_download(&part, &tmp_path)?;
+        Ok((tmp_path, report))
     }
 
-    const fn get_arch() -> &'static str {
-        let _result = "unknown";
-        #[cfg(target_arch = "x86")]
-        let _result = "x86_32";
-        #[cfg(target_arch = "x86_64")]
-        let _result = "x86_64";
-        _result
+    /// The GitHub API token from the config, or else the environment.
+    fn github_token(&self) -> Option<String> {
+        self.config
+            .github_token
+            .clone()
+            .or_else(|| env::var("GODOTUP_GITHUB_TOKEN").ok())
+            .or_else(|| env::var("GITHUB_TOKEN").ok())
+            .filter(|token| !token.trim().is_empty())
     }
 
-    #[test]
-    fn test_display_version() {
-        let vcs = Version {
-            major: 4,
-            minor: 0,
-            patch: 3,
-            suffix: Suffix::Stable,
+    fn download_settings(&self) -> DownloadSettings {
+        DownloadSettings {
+            attempts: self.config.download_attempts,
+            retry_delay: RETRY_BASE_DELAY,
+            stall_timeout: Duration::from_secs(self.config.download_stall_timeout_secs),
+            connections: self.config.download_connections,
+            max_rate: self.config.max_download_rate,
+        }
+    }
+
+    /// Downloads that were interrupted before completing. Installing the
+    /// same build again resumes them.
+    pub fn partial_downloads(&self) -> Result<Vec<PathBuf>> {
+        partial_downloads_in(&self.cache_dir()?)
+    }
+
+    /// How many files to extract at once, see `extract_workers` in the
+    /// config.
+    fn extract_workers(&self, options: &InstallOptions) -> usize {
+        match self.config.extract_workers {
+            _ if !options.parallel_extraction => 1,
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        }
+    }
+
+    /// Where downloads are kept, see `cache_dir` in the config.
+    fn cache_dir(&self) -> Result<PathBuf> {
+        match &self.config.cache_dir {
+            Some(dir) => Ok(dir.clone()),
+            None => Ok(appdata_dir()?.join("cache")),
+        }
+    }
+
+    /// The complete and partial archives in the download cache, oldest
+    /// first.
+    pub fn cached_archives(&self) -> Result<Vec<CachedArchive>> {
+        cached_archives_in(&self.cache_dir()?)
+    }
+
+    /// Removes the oldest archives from the download cache until the rest
+    /// take at most `max_size` bytes, and returns those removed.
+    pub fn trim_cache(&self, max_size: u64) -> Result<Vec<CachedArchive>> {
+        let archives = self.cached_archives()?;
+        let mut size: u64 = archives.iter().map(|archive| archive.size).sum();
+        let mut removed = Vec::new();
+        for archive in archives {
+            if size <= max_size {
+                break;
+            }
+            remove_cached_archive(&archive.path)?;
+            size -= archive.size;
+            removed.push(archive);
+        }
+        Ok(removed)
+    }
+
+    /// Downloads the start of the latest stable build from every configured
+    /// mirror at once and returns the mirrors fastest first, followed by
+    /// those that failed.
+    pub async fn test_mirrors(&self) -> Result<Vec<(String, Result<MirrorSpeed>)>> {
+        let platform = godot::Platform::host()?;
+        let list = self.version_list(false).await?;
+        let version = list.resolve("latest", platform)?;
+        let artifact = list
+            .find_artifact(&version, platform)
+            .context(format!("Version {} not found", &version))?;
+        let urls = mirror_urls(&artifact.url, &self.config.download_mirrors);
+        if urls.len() != self.config.download_mirrors.len() {
+            return Err(anyhow!("{} isn't on the configured mirrors", artifact.url));
+        }
+        let stall_timeout = Duration::from_secs(self.config.download_stall_timeout_secs);
+        let probes: Vec<_> = urls
+            .into_iter()
+            .map(|url| {
+                let client = self.client.clone();
+                tokio::spawn(async move { probe_mirror(&client, &url, stall_timeout).await })
+            })
+            .collect();
+        let mut results = Vec::new();
+        for (mirror, probe) in self.config.download_mirrors.iter().zip(probes) {
+            results.push((mirror.clone(), probe.await?));
+        }
+        results.sort_by_key(|(_, speed)| match speed {
+            Ok(speed) => (false, std::cmp::Reverse(speed.throughput())),
+            Err(_) => (true, std::cmp::Reverse(0)),
+        });
+        Ok(results)
+    }
+
+    /// Which certificates HTTPS connections are checked against.
+    pub fn tls_summary(&self) -> String {
+        if self.config.danger_accept_invalid_certs {
+            return String::from("certificates are NOT checked (danger_accept_invalid_certs)");
+        }
+        match &self.config.extra_ca_cert {
+            None => String::from(TLS_ROOTS),
+            Some(path) => match load_certificates(path) {
+                Ok(certs) => format!("{} and {} from {}", TLS_ROOTS, certs.len(), path.display()),
+                Err(err) => format!("{}; {:#}", TLS_ROOTS, err),
+            },
+        }
+    }
+
+    /// Moves `mirror` to the front of the configured mirrors and saves the
+    /// configuration.
+    pub fn set_preferred_mirror(&mut self, mirror: &str) -> Result<()> {
+        let mirrors = &mut self.config.download_mirrors;
+        mirrors.retain(|m| m != mirror);
+        mirrors.insert(0, mirror.to_string());
+        confy::store("godotup", None, &self.config).context("Couldn't save the configuration")
+    }
+
+    /// Release notes of `version` as markdown, from the list's `notes_url`
+    /// or else the matching GitHub release. Notes are cached, so looking
+    /// them up again works offline. `None` if the release has no notes.
+    pub async fn release_notes(&self, version: &godot::Version) -> Result<Option<String>> {
+        let tag = version.release_tag();
+        let cache = appdata_dir()?.join("notes").join(format!("{}.md", tag));
+        if let Ok(notes) = fs::read_to_string(&cache) {
+            return Ok(Some(notes));
+        }
+        let list = load_version_list(&*self.progress)?;
+        let standard = godot::Version {
             is_mono: false,
-            platform: Platform::Win32,
-        };
-        assert_eq!(vcs.to_string(), "Godot_v4.0.3-stable");
-        let vcs = Version {
-            suffix: Suffix::Rc(3),
-            ..vcs
-        };
-        assert_eq!(vcs.to_string(), "Godot_v4.0.3-rc3");
-        let vcs = Version {
-            suffix: Suffix::Stable,
-            is_mono: true,
-            ..vcs
+            ..version.clone()
         };
-        assert_eq!(vcs.to_string(), "Godot_v4.0.3-stable_mono");
-        let vcs = Version {
-            suffix: Suffix::Alpha(11),
-            ..vcs
+        let notes_url = [version, &standard]
+            .into_iter()
+            .find_map(|v| list.release_info(v)?.notes_url.clone());
+        let notes = match notes_url {
+            Some(url) => fetch_notes(&self.client, &url).await?,
+            None => {
+                fetch_github_release_notes(
+                    &self.client,
+                    GITHUB_API,
+                    &self.config.github_repos,
+                    self.github_token().as_deref(),
+                    &tag,
+                )
+                .await?
+            }
         };
-        assert_eq!(vcs.to_string(), "Godot_v4.0.3-alpha11_mono");
+        if let Some(notes) = &notes {
+            fs::create_dir_all(cache.parent().unwrap())?;
+            fs::write(&cache, notes)?;
+        }
+        Ok(notes)
     }
 
-    #[test]
-    fn test_serde_versionlist() {
-        let mut versions = HashMap::new();
-        versions.insert(
-            Version {
-                major: 4,
-                minor: 0,
-                patch: 3,
-                suffix: Suffix::Stable,
-                is_mono: false,
-                platform: Platform::Linux32,
-            },
-            format!("https:sss"),
-        );
-        versions.insert(
-            Version {
-                major: 4,
-                minor: 0,
-                patch: 0,
-                suffix: Suffix::Alpha(8),
-                is_mono: false,
-                platform: Platform::Linux64,
-            },
-            format!("https:sss"),
-        );
-        let list = VersionList { versions };
-        println!("{}", serde_yaml::to_string(&list).unwrap());
+    /// Crawls the download `mirror` (see [`crawl_mirror`]) into a version
+    /// list. With `append_to`, the result is merged into that list, whose
+    /// existing entries win. The list is written to `output`, else back to
+    /// `append_to`, else to stdout.
+    pub async fn generate_version_list(
+        &self,
+        mirror: &str,
+        output: Option<&Path>,
+        append_to: Option<&Path>,
+    ) -> Result<()> {
+        let mut list = match append_to {
+            Some(path) => {
+                let text = fs::read_to_string(path)
+                    .with_context(|| format!("could not read version list {}", path.display()))?;
+                godot::VersionList::parse(&text)?
+            }
+            None => godot::VersionList::default(),
+        };
+        list.merge(crawl_mirror(&self.client, mirror, &*self.progress).await?);
+        let yaml = list.to_yaml()?;
+        match output.or(append_to) {
+            Some(path) => fs::write(path, yaml)
+                .with_context(|| format!("could not write version list {}", path.display()))?,
+            None => print!("{}", yaml),
+        }
+        Ok(())
     }
-}
 
-impl CliApp {
-    pub async fn update_version_list(&self) -> Result<()> {
-        let version_list = version_list_path()?;
-        if version_list.exists() {
-            println!("Removing old version list..");
-            fs::remove_file(&version_list)?;
+    /// Writes the current version list as `format` to `output`, or stdout.
+    pub fn export_version_list(
+        &self,
+        format: godot::ListFormat,
+        output: Option<&Path>,
+    ) -> Result<()> {
+        let text = load_version_list(&*self.progress)?.to_format(format)?;
+        match output {
+            Some(path) => fs::write(path, text)
+                .with_context(|| format!("could not write version list {}", path.display()))?,
+            None => print!("{}", text),
         }
-        download_from_url(&self.config.download_proxy_url, &version_list).await?;
         Ok(())
     }
 
-    pub async fn install_godot(&self, version: &godot::Version) -> Result<()> {
-        let vcs_list = load_version_list()?;
-        let url = vcs_list
-            .find_url(&version)
-            .context(format!("Version {} not found", &version))?;
-        let tmp_path = env::temp_dir().join(&format!("{}.zip", version));
-        download_from_url(url, &tmp_path).await?;
-        unzip(&tmp_path, &godot_version_dir(&version))?;
+    /// Rewrites the version list at `from` (any supported schema) to `to`
+    /// in the current schema, for migrating published lists.
+    pub fn convert_version_list(&self, from: &Path, to: &Path) -> Result<()> {
+        let text = fs::read_to_string(from)
+            .with_context(|| format!("could not read version list {}", from.display()))?;
+        let list = godot::VersionList::parse(&text)?;
+        fs::write(to, list.to_yaml()?)
+            .with_context(|| format!("could not write version list {}", to.display()))?;
         Ok(())
     }
 
-    pub fn switch(&self, version: &godot::Version) -> Result<()> {
-        // find godot executable position
-        // set GODOT_HOME
-        // set GODOT_BIN
-        // set GODOT4_BIN (optional)
-        // add shortcuts
-        todo!()
+    /// For each installed minor series and flavor, the newest installed
+    /// version and what it can be upgraded to, newest series first. Series
+    /// that are up to date are left out. With `refresh` a stale version list
+    /// is updated first.
+    pub async fn outdated(&self, refresh: bool) -> Result<Vec<Outdated>> {
+        let platform = godot::Platform::host()?;
+        let available = self.version_list(refresh).await?;
+        Ok(find_outdated(
+            &installed_version_list()?,
+            &available,
+            platform,
+        ))
+    }
+
+    /// The versions available or installed for this platform, newest first,
+    /// see [`ListOptions`].
+    pub async fn list(&self, options: &ListOptions) -> Result<Vec<ListEntry>> {
+        let platform = godot::Platform::host()?;
+        let installed = installed_version_list()?;
+        list_entries(
+            &self.version_list(options.refreshes()).await?,
+            &installed,
+            platform,
+            options,
+        )
+    }
+
+    /// A notice like `Godot 4.2.2 is available (you have 4.2.1)` if a
+    /// stable release newer than the active version is out. Given at most
+    /// once per `notify_interval_hours`, only looks at a version list that
+    /// is still fresh and never fails.
+    pub fn new_release_notice(&self) -> Option<String> {
+        if !self.config.notify_new_releases {
+            return None;
+        }
+        let notice = || -> Result<Option<String>> {
+            let now = unix_now();
+            let ttl = Duration::from_secs(self.config.version_list_ttl_secs);
+            if is_stale(&version_list_path()?, ttl, now) {
+                return Ok(None);
+            }
+            let interval = Duration::from_secs(self.config.notify_interval_hours * 60 * 60);
+            if !notice_due(&appdata_dir()?.join("notify.json"), interval, now)? {
+                return Ok(None);
+            }
+            let installed = installed_version_list()?;
+            Ok(release_notice(
+                &load_version_list(&*self.progress)?,
+                &installed,
+                switched_build(&bin_dir(), &installed),
+                godot::Platform::host()?,
+            ))
+        };
+        notice().ok().flatten()
+    }
+
+    /// The executable of the newest installed version matching `spec`, or
+    /// with `console` its console wrapper, which only Windows builds have.
+    pub fn which(&self, spec: &str, console: bool) -> Result<PathBuf> {
+        let platform = godot::Platform::host()?;
+        if console {
+            has_console(platform)?;
+        }
+        let installed = installed_version_list()?;
+        let dir = &installed.resolve(spec, platform)?.dir;
+        match console {
+            true => find_console(dir, platform),
+            false => find_executable(dir, platform),
+        }
+    }
+
+    /// Runs [`CliApp::which`] with `args` and waits for it to exit.
+    pub fn run(&self, spec: &str, console: bool, args: &[String]) -> Result<process::ExitStatus> {
+        let exe = self.which(spec, console)?;
+        process::Command::new(&exe)
+            .args(args)
+            .status()
+            .with_context(|| format!("could not run {}", exe.display()))
+    }
+
+    /// Uninstalls the newest installed version matching `spec` and returns
+    /// what was removed. Only the files its manifest lists are removed,
+    /// unless `force` also allows removing files added since, or an install
+    /// without a manifest.
+    pub fn uninstall(&self, spec: &str, force: bool) -> Result<InstalledBuild> {
+        let platform = godot::Platform::host()?;
+        let installed = installed_version_list()?;
+        let installed = installed.resolve(spec, platform)?;
+        remove_install(&installed.dir, force)?;
+        Ok(installed.clone())
+    }
+
+    /// Checks the files of the newest installed version matching `spec`
+    /// against its manifest.
+    pub fn verify(&self, spec: &str) -> Result<Verification> {
+        let platform = godot::Platform::host()?;
+        let installed = installed_version_list()?;
+        let installed = installed.resolve(spec, platform)?;
+        let dir = &installed.dir;
+        let manifest = read_manifest(dir)?.with_context(|| {
+            format!(
+                "{} has no manifest to check against; reinstall it with --reinstall to record one",
+                dir.display()
+            )
+        })?;
+        Ok(Verification {
+            version: installed.record.build.version.clone(),
+            files: manifest.files.len(),
+            changes: changed_files(dir, &manifest)?,
+        })
+    }
+
+    /// Switches to the newest installed version matching `spec` by linking
+    /// `godot`, and `godot4` for Godot 4, in [`bin_dir`] to its executable,
+    /// as enabled by `set_godot_bin` and `set_godot4_bin`.
+    pub fn switch(&self, spec: &str) -> Result<Switched> {
+        let platform = godot::Platform::host()?;
+        let installed = installed_version_list()?;
+        let installed = installed.resolve(spec, platform)?;
+        let version = installed.record.build.version.clone();
+        let executable = find_executable(&installed.dir, platform)?;
+        let names = [
+            ("godot", self.config.set_godot_bin),
+            ("godot4", self.config.set_godot4_bin && version.major == 4),
+        ];
+        let bin = bin_dir();
+        fs::create_dir_all(&bin).with_context(|| format!("Couldn't create {}", bin.display()))?;
+        let mut links = Vec::new();
+        for (name, _) in names.iter().filter(|(_, enabled)| *enabled) {
+            let name = format!("{}{}", name, env::consts::EXE_SUFFIX);
+            links.push(link_alias(&bin, &name, &executable)?);
+        }
+        Ok(Switched {
+            version,
+            executable,
+            links,
+        })
     }
 }
 
@@ -231,120 +1447,719 @@ fn appdata_dir() -> Result<PathBuf> {
     Ok(dir)
 }
 
-async fn download_from_url(url: &str, path: &Path) -> Result<()> {
-    println!("Downloading {} to {:?}...", url, path);
-    let client = Client::new();
-    let total_size = {
-        let resp = client.head(url).send().await?;
-        if resp.status().is_success() {
-            resp.headers()
-                .get(header::CONTENT_LENGTH)
-                .and_then(|ct_len| ct_len.to_str().ok())
-                .and_then(|ct_len| ct_len.parse().ok())
-                .unwrap_or(0)
-        } else {
-            return Err(anyhow!(
-                "Couldn't download URL: {}. Error: {:?}",
-                url,
-                resp.status(),
-            ));
-        }
+/// An operation stopped through its [`CancellationToken`].
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Interrupted")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Whether `err` comes from a cancelled [`CancellationToken`].
+pub fn is_cancelled(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| cause.is::<Cancelled>())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Replaces `dest` with `contents` without leaving a partial file behind.
+fn write_atomically(dest: &Path, contents: &[u8]) -> Result<()> {
+    let part = dest.with_extension("part");
+    fs::write(&part, contents)?;
+    fs::rename(&part, dest)?;
+    Ok(())
+}
+
+fn find_outdated(
+    installed: &InstalledBuilds,
+    available: &godot::VersionList,
+    platform: godot::Platform,
+) -> Vec<Outdated> {
+    let mut seen = HashSet::new();
+    installed
+        .iter(platform)
+        .filter(|v| seen.insert((v.major, v.minor, v.is_mono)))
+        .filter_map(|v| {
+            Some(Outdated {
+                installed: v.clone(),
+                available: available.upgrade_for(v, platform)?.clone(),
+            })
+        })
+        .collect()
+}
+
+fn list_entries(
+    list: &godot::VersionList,
+    installed: &InstalledBuilds,
+    platform: godot::Platform,
+    options: &ListOptions,
+) -> Result<Vec<ListEntry>> {
+    let mut versions = match options.installed_only {
+        true => installed.iter(platform).collect::<Vec<_>>(),
+        false => list.iter(platform).collect(),
     };
-    let client = Client::new();
-    let mut request = client.get(url);
-    let pb = ProgressBar::new(total_size);
-    pb.set_style(ProgressStyle::default_bar()
-        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")?
-        .with_key("eta", |state: &ProgressState, w: &mut dyn std::fmt::Write| {
-            write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap();
+    if let Some(date) = &options.since {
+        let since = list.released_since(date, platform)?;
+        versions.retain(|v| since.contains(v));
+    }
+    match options.channel {
+        // Installs need not be in the version list any more.
+        Some(channel) if options.installed_only => versions.retain(|v| v.channel() == channel),
+        Some(channel) => {
+            let on_channel = list
+                .filter_channel(channel)
+                .filter(|build| build.platform == platform)
+                .map(|build| &build.version)
+                .collect::<HashSet<_>>();
+            versions.retain(|v| on_channel.contains(v));
+        }
+        None if options.include_prerelease || options.installed_only => {}
+        None => versions.retain(|v| !v.is_prerelease()),
+    }
+    if options.by_date {
+        // Stable, so versions released the same day stay newest first.
+        versions.sort_by_key(|v| {
+            let released = list.release_date(v);
+            (released.is_none(), Reverse(released))
+        });
+    }
+    Ok(versions
+        .into_iter()
+        .map(|version| {
+            let build = godot::Build {
+                version: version.clone(),
+                platform,
+            };
+            let install = installed.get(&build);
+            ListEntry {
+                installed: install.is_some(),
+                verified: install.and_then(|install| install.record.verified),
+                released: list.release_date(version).map(str::to_string),
+                local: list.is_local(&build),
+                source: list
+                    .find_artifact(version, platform)
+                    .and_then(|artifact| artifact.source.clone()),
+                version: build.version,
+            }
         })
-        .progress_chars("#>-"));
-
-    if path.exists() {
-        let size = path.metadata()?.len().saturating_sub(1);
-        request = request.header(header::RANGE, format!("bytes={}-", size));
-        pb.inc(size);
-    }
-    let mut source = request.send().await?;
-    let mut dest = fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&path)?;
-    while let Some(chunk) = source.chunk().await? {
-        dest.write_all(&chunk)?;
-        pb.inc(chunk.len() as u64);
-    }
-    println!("Completed!");
-    Ok(())
+        .collect())
 }
 
-fn version_list_path() -> Result<PathBuf> {
-    Ok(appdata_dir()?.join("versions.yml"))
+/// When the last new-release notice was checked for.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct NotifyState {
+    checked_at: Option<u64>,
+}
+
+/// Whether more than `interval` has passed since the last check recorded in
+/// `state`, recording `now` as the last check if so.
+fn notice_due(state: &Path, interval: Duration, now: u64) -> Result<bool> {
+    let checked_at = fs::read_to_string(state)
+        .ok()
+        .and_then(|s| serde_json::from_str::<NotifyState>(&s).ok())
+        .and_then(|s| s.checked_at);
+    if checked_at.is_some_and(|t| now.saturating_sub(t) < interval.as_secs()) {
+        return Ok(false);
+    }
+    let state_json = serde_json::to_string(&NotifyState {
+        checked_at: Some(now),
+    })?;
+    fs::write(state, state_json)?;
+    Ok(true)
 }
 
-fn load_version_list() -> Result<godot::VersionList> {
-    let path = version_list_path()?;
-    let mut file = fs::File::open(&path)?;
-    let mut str = String::new();
-    file.read_to_string(&mut str)?;
-    Ok(serde_yaml::from_str::<godot::VersionList>(&str)?)
+/// A notice about the newest stable release of the flavor of `active`, the
+/// version [`CliApp::switch`] switched to, if it is newer. Without an active
+/// version the newest installed stable build is compared instead.
+fn release_notice(
+    available: &godot::VersionList,
+    installed: &InstalledBuilds,
+    active: Option<&godot::Version>,
+    platform: godot::Platform,
+) -> Option<String> {
+    let current = match active {
+        Some(active) => active,
+        None => installed.iter(platform).find(|v| !v.is_prerelease())?,
+    };
+    let latest = available
+        .iter(platform)
+        .find(|v| !v.is_prerelease() && v.is_mono == current.is_mono)?;
+    (latest > current).then(|| {
+        format!(
+            "Godot {} is available (you have {})",
+            latest.short(),
+            current.short()
+        )
+    })
 }
 
-fn godot_version_dir(vcs: &godot::Version) -> PathBuf {
-    dirs::home_dir()
-        .unwrap()
-        .join(".godotup")
-        .join(&format!("{}", vcs))
+/// Name of a download's progress next to others: the file and its size.
+fn artifact_label(artifact: &godot::Artifact) -> String {
+    match artifact.size {
+        Some(size) => format!("{} ({})", artifact.filename, HumanBytes(size)),
+        None => artifact.filename.clone(),
+    }
 }
 
-use zip;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-fn unzip(from: &Path, to: &Path) -> Result<()> {
-    let file = fs::File::open(from)?;
-    let mut archive = zip::ZipArchive::new(file).unwrap();
+    use sha2::Sha512;
 
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i).unwrap();
-        let outpath = match file.enclosed_name() {
-            Some(path) => to.join(path),
-            None => continue,
-        };
+    use crate::install::installed_builds;
+    use crate::test_util::{no_progress, serve_ranges, test_dir};
 
-        {
-            let comment = file.comment();
-            if !comment.is_empty() {
-                println!("File {i} comment: {comment}");
-            }
+    #[test]
+    fn test_switch_suggests_installed_versions() {
+        let tmp = test_dir("installed");
+        let root = tmp.path();
+        for spec in ["4.1.2", "4.2.1", "4.2.1-mono"] {
+            let build = godot::Build {
+                version: godot::Version::parse(spec).unwrap(),
+                platform: godot::Platform::Linux64,
+            };
+            let dir = root.join(build.slug());
+            fs::create_dir_all(&dir).unwrap();
+            let record = InstallRecord {
+                build,
+                verified: Some(true),
+                console: None,
+            };
+            write_install_record(&dir, &record).unwrap();
         }
+        fs::create_dir_all(root.join("stray")).unwrap();
 
-        if (*file.name()).ends_with('/') {
-            println!("File {} extracted to \"{}\"", i, outpath.display());
-            fs::create_dir_all(&outpath).unwrap();
-        } else {
-            println!(
-                "File {} extracted to \"{}\" ({} bytes)",
-                i,
-                outpath.display(),
-                file.size()
-            );
-            if let Some(p) = outpath.parent() {
-                if !p.exists() {
-                    fs::create_dir_all(p).unwrap();
+        let installed = installed_builds(root).unwrap();
+        let found = installed.resolve("4.2", godot::Platform::Linux64).unwrap();
+        assert_eq!(found.record.build.version.short(), "4.2.1");
+        assert_eq!(found.dir, root.join(found.record.build.slug()));
+        let err = installed
+            .resolve("4.2.2", godot::Platform::Linux64)
+            .unwrap_err();
+        let err = err.downcast_ref::<godot::VersionNotFound>().unwrap();
+        let suggestions = err
+            .suggestions
+            .iter()
+            .map(|v| v.short())
+            .collect::<Vec<_>>();
+        assert_eq!(suggestions, ["4.2.1", "4.1.2", "4.2.1 (mono)"]);
+        assert!(installed_builds(&root.join("missing"))
+            .unwrap()
+            .resolve("4", godot::Platform::Linux64)
+            .is_err());
+    }
+
+    fn installed_list(specs: &[&str]) -> InstalledBuilds {
+        specs
+            .iter()
+            .map(|spec| {
+                let build = godot::Build {
+                    version: godot::Version::parse(spec).unwrap(),
+                    platform: godot::Platform::Linux64,
+                };
+                InstalledBuild {
+                    dir: PathBuf::from(build.slug()),
+                    record: InstallRecord {
+                        build,
+                        verified: Some(true),
+                        console: None,
+                    },
                 }
-            }
-            let mut outfile = fs::File::create(&outpath).unwrap();
-            io::copy(&mut file, &mut outfile).unwrap();
-        }
+            })
+            .collect()
+    }
 
-        // Get and Set permissions
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
+    #[test]
+    fn test_list_entries() {
+        use godot::Platform::Linux64;
 
-            if let Some(mode) = file.unix_mode() {
-                fs::set_permissions(&outpath, fs::Permissions::from_mode(mode)).unwrap();
-            }
-        }
+        let mut list = godot::VersionList::from_yaml(
+            r#"
+schema: 2
+releases:
+- { major: 4, minor: 1, patch: 3, suffix: stable, is_mono: false, released: 2024-02-29,
+    artifacts: { linux64: { url: a, filename: a } } }
+- { major: 4, minor: 2, patch: 1, suffix: stable, is_mono: false, released: 2023-12-12,
+    artifacts: { linux64: { url: b, filename: b } } }
+- { major: 4, minor: 2, patch: 2, suffix: stable, is_mono: false,
+    artifacts: { linux64: { url: c, filename: c } } }
+- { major: 4, minor: 3, patch: 0, suffix: dev1, is_mono: false,
+    artifacts: { linux64: { url: d, filename: d } } }
+- { major: 3, minor: 5, patch: 0, suffix: stable, is_mono: false, released: 2022-08-05,
+    artifacts: { linux64: { url: e, filename: e } } }
+"#,
+        )
+        .unwrap();
+        list.set_source("https://example.org/versions.yml");
+        list.overlay(
+            godot::VersionList::from_yaml(
+                r#"
+schema: 2
+releases:
+- { major: 4, minor: 2, patch: 2, suffix: stable, is_mono: false,
+    artifacts: { linux64: { url: f, filename: f } } }
+"#,
+            )
+            .unwrap(),
+        );
+        let installed = installed_list(&["4.1.3"]);
+        let lines = |options: &ListOptions| {
+            list_entries(&list, &installed, Linux64, options)
+                .unwrap()
+                .into_iter()
+                .map(|entry| {
+                    let released = entry.released.unwrap_or_else(|| "-".to_string());
+                    let local = if entry.local { " local" } else { "" };
+                    format!("{} {}{}", entry.version.short(), released, local)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            lines(&ListOptions::default()),
+            [
+                "4.2.2 - local",
+                "4.2.1 2023-12-12",
+                "4.1.3 2024-02-29",
+                "3.5 2022-08-05"
+            ]
+        );
+        let since = ListOptions {
+            since: Some("2023-01-01".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(lines(&since), ["4.2.1 2023-12-12", "4.1.3 2024-02-29"]);
+        let by_date = ListOptions {
+            by_date: true,
+            ..since.clone()
+        };
+        assert_eq!(lines(&by_date), ["4.1.3 2024-02-29", "4.2.1 2023-12-12"]);
+        let by_date = ListOptions {
+            by_date: true,
+            ..Default::default()
+        };
+        assert_eq!(lines(&by_date)[3..], ["4.2.2 - local"]);
+        let prereleases = ListOptions {
+            include_prerelease: true,
+            ..by_date
+        };
+        assert_eq!(lines(&prereleases)[3..], ["4.3-dev1 -", "4.2.2 - local"]);
+        let dev = ListOptions {
+            channel: Some(godot::Channel::Dev),
+            ..Default::default()
+        };
+        assert_eq!(lines(&dev), ["4.3-dev1 -"]);
+        let stable = ListOptions {
+            channel: Some("Stable".parse().unwrap()),
+            ..since.clone()
+        };
+        assert_eq!(lines(&stable), ["4.2.1 2023-12-12", "4.1.3 2024-02-29"]);
+        assert!("nightly".parse::<godot::Channel>().is_err());
+        let sources = list_entries(&list, &installed, Linux64, &ListOptions::default())
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.source)
+            .collect::<Vec<_>>();
+        let entry = |spec: &str| {
+            list_entries(&list, &installed, Linux64, &ListOptions::default())
+                .unwrap()
+                .into_iter()
+                .find(|entry| entry.version.short() == spec)
+                .unwrap()
+        };
+        assert!(entry("4.1.3").installed);
+        assert_eq!(entry("4.1.3").verified, Some(true));
+        assert!(!entry("4.2.1").installed);
+        assert_eq!(entry("4.2.1").verified, None);
+        let shuffled = installed_list(&[
+            "4.2.1-mono",
+            "4.3-dev1",
+            "3.5",
+            "4.2.1",
+            "4.2.1-rc1",
+            "4.10",
+            "4.2",
+        ]);
+        let installed_only = ListOptions {
+            installed_only: true,
+            ..Default::default()
+        };
+        let shorts = list_entries(&list, &shuffled, Linux64, &installed_only)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.version.short())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            shorts,
+            [
+                "4.10",
+                "4.3-dev1",
+                "4.2.1 (mono)",
+                "4.2.1",
+                "4.2.1-rc1",
+                "4.2",
+                "3.5"
+            ]
+        );
+        assert_eq!(sources[0], None);
+        assert_eq!(
+            sources[1].as_deref(),
+            Some("https://example.org/versions.yml")
+        );
+        let bad = ListOptions {
+            since: Some("yesterday".to_string()),
+            ..Default::default()
+        };
+        assert!(list_entries(&list, &installed, Linux64, &bad).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cached_archive() {
+        let tmp = test_dir("cached-archive");
+        let dir = tmp.path();
+        let body = b"cached archive";
+        let sha512 = hex(&Sha512::digest(body));
+        let artifact = godot::Artifact {
+            sha512: Some(sha512.clone()),
+            ..godot::Artifact::from_url(String::from(
+                "http://127.0.0.1:9/Godot_v4.2.1-stable_linux.x86_64.zip",
+            ))
+        };
+        let config = Config {
+            cache_dir: Some(dir.to_path_buf()),
+            download_attempts: 1,
+            ..Config::default()
+        };
+        let app = CliApp {
+            client: build_client(&config).unwrap(),
+            config,
+            progress: Arc::new(NoProgress),
+            warnings: Vec::new(),
+        };
+        let path = download_path(dir, &artifact.filename, Some(&sha512));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, body).unwrap();
+        // Served from the cache without a request to the unreachable URL.
+        let (found, report) = app
+            .fetch_artifact(
+                &artifact,
+                &InstallOptions::default(),
+                &CancellationToken::new(),
+                "4.2.1",
+                &no_progress(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(found, path);
+        assert!(report.cached);
+        assert_eq!(report.transferred, 0);
+        assert!(report.to_string().starts_with("Using cached"), "{}", report);
+        // Unless asked to download it again, which discards it first.
+        let forced = InstallOptions {
+            force_download: true,
+            ..InstallOptions::default()
+        };
+        let err = app
+            .fetch_artifact(
+                &artifact,
+                &forced,
+                &CancellationToken::new(),
+                "4.2.1",
+                &no_progress(),
+            )
+            .await;
+        assert!(err.is_err());
+        assert!(!path.exists());
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, body).unwrap();
+
+        let part = dir.join("pid-1/Godot_v4.3-stable_linux.x86_64.zip.part");
+        fs::create_dir_all(part.parent().unwrap()).unwrap();
+        fs::write(&part, "partial").unwrap();
+        fs::write(dir.join("pid-1/unrelated.txt"), "").unwrap();
+        let archives = app.cached_archives().unwrap();
+        assert_eq!(archives.len(), 2);
+        assert_eq!(app.partial_downloads().unwrap(), vec![part.clone()]);
+        let removed = app.trim_cache(body.len() as u64).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert!(!removed[0].path.exists());
+        assert_eq!(app.cached_archives().unwrap().len(), 1);
+        assert_eq!(app.trim_cache(0).unwrap().len(), 1);
+        assert!(app.cached_archives().unwrap().is_empty());
+        assert!(!path.parent().unwrap().exists());
+    }
+
+    #[tokio::test]
+    async fn test_force_download_discards_partial() {
+        const BODY: &str = "0123456789";
+        let tmp = test_dir("force-download");
+        let dir = tmp.path();
+        let requested = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sha512 = hex(&Sha512::digest(BODY));
+        let artifact = godot::Artifact {
+            sha512: Some(sha512.clone()),
+            ..godot::Artifact::from_url(serve_ranges(BODY, true, None, requested.clone()))
+        };
+        let config = Config {
+            cache_dir: Some(dir.to_path_buf()),
+            download_attempts: 1,
+            download_connections: 1,
+            ..Config::default()
+        };
+        let app = CliApp {
+            client: build_client(&config).unwrap(),
+            config,
+            progress: Arc::new(NoProgress),
+            warnings: Vec::new(),
+        };
+        // A good start of the archive, which would otherwise be resumed.
+        let path = download_path(dir, &artifact.filename, Some(&sha512));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(part_path(&path), &BODY[..4]).unwrap();
+        let forced = InstallOptions {
+            force_download: true,
+            ..InstallOptions::default()
+        };
+        let (found, report) = app
+            .fetch_artifact(
+                &artifact,
+                &forced,
+                &CancellationToken::new(),
+                "4.2.1",
+                &no_progress(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(found).unwrap(), BODY);
+        assert!(!report.resumed);
+        assert_eq!(report.transferred, BODY.len() as u64);
+        assert_eq!(*requested.lock().unwrap(), [""]);
+    }
+
+    #[tokio::test]
+    async fn test_mismatch_downloads_from_scratch() {
+        const BODY: &str = "0123456789";
+        let tmp = test_dir("mismatch-from-scratch");
+        let dir = tmp.path();
+        let requested = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sha512 = hex(&Sha512::digest(BODY));
+        let artifact = godot::Artifact {
+            sha512: Some(sha512.clone()),
+            ..godot::Artifact::from_url(serve_ranges(BODY, true, None, requested.clone()))
+        };
+        let config = Config {
+            cache_dir: Some(dir.to_path_buf()),
+            download_attempts: 1,
+            download_connections: 1,
+            ..Config::default()
+        };
+        let app = CliApp {
+            client: build_client(&config).unwrap(),
+            config,
+            progress: Arc::new(NoProgress),
+            warnings: Vec::new(),
+        };
+        // Left by an earlier download without a validator to resume it safely.
+        let path = download_path(dir, &artifact.filename, Some(&sha512));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(part_path(&path), "abcd").unwrap();
+        let (found, report) = app
+            .fetch_artifact(
+                &artifact,
+                &InstallOptions::default(),
+                &CancellationToken::new(),
+                "4.2.1",
+                &no_progress(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(found).unwrap(), BODY);
+        assert!(!report.resumed);
+        assert_eq!(*requested.lock().unwrap(), ["4-", ""]);
+    }
+
+    #[test]
+    fn test_find_outdated() {
+        use godot::Platform::Linux64;
+
+        let list = |specs: &[&str]| -> godot::VersionList {
+            specs
+                .iter()
+                .map(|spec| {
+                    let version = godot::Version::parse(spec).unwrap();
+                    let platform = Linux64;
+                    (godot::Build { version, platform }, spec.to_string())
+                })
+                .collect()
+        };
+        let available = list(&[
+            "3.5.3",
+            "4.1.3",
+            "4.1.4-rc1",
+            "4.2",
+            "4.2.1",
+            "4.2.2",
+            "4.2.2-mono",
+            "4.3-dev6",
+            "4.3-beta1",
+            "4.3-beta2",
+            "4.4-dev1",
+            "4.4-dev2",
+        ]);
+        let installed = installed_list(&[
+            "3.5.3",
+            "4.1.3",
+            "4.2",
+            "4.2.1",
+            "4.2.1-mono",
+            "4.2.2-rc1",
+            "4.3-beta1",
+            "4.4-dev1",
+        ]);
+        let outdated = find_outdated(&installed, &available, Linux64)
+            .into_iter()
+            .map(|o| (o.installed.short(), o.available.short()))
+            .collect::<Vec<_>>();
+        let pair = |a: &str, b: &str| (a.to_string(), b.to_string());
+        assert_eq!(
+            outdated,
+            [
+                pair("4.4-dev1", "4.4-dev2"),
+                pair("4.3-beta1", "4.3-beta2"),
+                pair("4.2.2-rc1", "4.2.2"),
+                pair("4.2.1 (mono)", "4.2.2 (mono)"),
+            ]
+        );
+
+        let v = |s| godot::Version::parse(s).unwrap();
+        let newer = |s| {
+            available
+                .newer_than(&v(s), Linux64)
+                .iter()
+                .map(|v| v.short())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(newer("4.2"), ["4.2.2", "4.2.1"]);
+        assert_eq!(newer("4.3-beta1"), ["4.3-beta2"]);
+        assert_eq!(newer("4.3-dev5"), ["4.3-beta2", "4.3-beta1", "4.3-dev6"]);
+        assert!(newer("4.1.3").contains(&"4.1.4-rc1".to_string()));
+        assert_eq!(available.upgrade_for(&v("4.1.3"), Linux64), None);
+    }
+
+    #[test]
+    fn test_release_notice() {
+        use godot::Platform::Linux64;
+
+        let list = |specs: &[&str]| -> godot::VersionList {
+            specs
+                .iter()
+                .map(|spec| {
+                    let version = godot::Version::parse(spec).unwrap();
+                    let platform = Linux64;
+                    (godot::Build { version, platform }, spec.to_string())
+                })
+                .collect()
+        };
+        let available = list(&["4.2.1", "4.2.2", "4.2.2-mono", "4.3-beta1"]);
+        let notice = |installed: &[&str]| {
+            release_notice(&available, &installed_list(installed), None, Linux64)
+        };
+        assert_eq!(
+            notice(&["4.1.3", "4.2.1"]).as_deref(),
+            Some("Godot 4.2.2 is available (you have 4.2.1)")
+        );
+        assert_eq!(
+            notice(&["4.2.1-mono", "4.3-dev1"]).as_deref(),
+            Some("Godot 4.2.2 (mono) is available (you have 4.2.1 (mono))")
+        );
+        assert_eq!(notice(&["4.2.2"]), None);
+        assert_eq!(notice(&["4.3-beta1"]), None);
+        assert_eq!(notice(&[]), None);
+        // The active version wins over the newest installed one.
+        let active = |spec| {
+            let active = godot::Version::parse(spec).unwrap();
+            let installed = installed_list(&["4.2.1", "4.2.2", "4.3-beta1"]);
+            release_notice(&available, &installed, Some(&active), Linux64)
+        };
+        assert_eq!(
+            active("4.2.1").as_deref(),
+            Some("Godot 4.2.2 is available (you have 4.2.1)")
+        );
+        assert_eq!(active("4.3-beta1"), None);
+
+        let tmp = test_dir("notify");
+        let state = tmp.path().join("notify.json");
+        let day = Duration::from_secs(24 * 60 * 60);
+        assert!(notice_due(&state, day, 1_000_000).unwrap());
+        assert!(!notice_due(&state, day, 1_000_000 + 60).unwrap());
+        assert!(notice_due(&state, day, 1_000_000 + day.as_secs()).unwrap());
+        fs::write(&state, "garbage").unwrap();
+        assert!(notice_due(&state, day, 1_000_000).unwrap());
+    }
+
+    #[test]
+    fn test_config_legacy_keys() {
+        let mut config: Config =
+            toml::from_str("version_list_proxy_url = \"https://example.org/versions.yml\"")
+                .unwrap();
+        assert_eq!(
+            config.migrate(),
+            [("version_list_proxy_url", "version_list_sources")]
+        );
+        assert_eq!(
+            config.version_list_sources,
+            [
+                "https://example.org/versions.yml",
+                "https://raw.githubusercontent.com/Hapenia-Lans/godotup/main/versions.yml"
+            ]
+        );
+        assert!(config.migrate().is_empty());
+        assert!(!toml::to_string(&config)
+            .unwrap()
+            .contains("version_list_proxy_url"));
+
+        let mut config: Config = toml::from_str(
+            "version_list_proxy_url = \"https://a/versions.yml\"\nversion_list_sources = [\"https://a/versions.yml\"]",
+        )
+        .unwrap();
+        config.migrate();
+        assert_eq!(config.version_list_sources, ["https://a/versions.yml"]);
+
+        let mut config: Config =
+            toml::from_str("download_proxy_url = \"https://mirror.example.org/godot/\"").unwrap();
+        assert_eq!(
+            config.migrate(),
+            [("download_proxy_url", "download_mirrors")]
+        );
+        assert_eq!(
+            config.download_mirrors,
+            [
+                "https://mirror.example.org/godot/",
+                "https://downloads.tuxfamily.org/godotengine/"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_list_installed_never_refreshes() {
+        assert!(ListOptions::default().refreshes());
+        let installed = ListOptions {
+            installed_only: true,
+            ..Default::default()
+        };
+        assert!(!installed.refreshes());
+        let cached = ListOptions {
+            refresh: false,
+            ..Default::default()
+        };
+        assert!(!cached.refreshes());
     }
-    Ok(())
 }
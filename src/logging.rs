@@ -0,0 +1,71 @@
+//! Diagnostic logging to stderr, for `--verbose` or `GODOTUP_LOG`.
+//!
+//! godotup logs with [`tracing`]: each request with its final URL, status
+//! and timing, retries, and extraction. They are printed by
+//! [`tracing_subscriber`]'s formatter, one line each.
+
+use tracing_subscriber::EnvFilter;
+
+/// Which events to log: those `GODOTUP_LOG` selects, in
+/// [`EnvFilter`] syntax such as `debug` or `godotup=trace`, or all up
+/// to debug level with `verbose`. `None` if nothing is to be logged.
+fn filter(verbose: bool) -> Option<EnvFilter> {
+    match std::env::var("GODOTUP_LOG") {
+        Ok(directives) => Some(EnvFilter::builder().parse_lossy(directives)),
+        Err(_) if verbose => Some(EnvFilter::new("debug")),
+        Err(_) => None,
+    }
+}
+
+/// Starts logging the events [`filter`] selects to stderr.
+pub fn init(verbose: bool) {
+    if let Some(filter) = filter(verbose) {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(filter)
+            .with_writer(std::io::stderr)
+            .try_init();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header;
+
+    use crate::download::send_traced;
+    use crate::test_util::{serve_with, test_client, Output};
+
+    #[tokio::test]
+    async fn test_logging() {
+        let output = Output::default();
+        let writer = output.clone();
+        let logger = tracing_subscriber::fmt()
+            .with_env_filter("godotup=debug")
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .without_time()
+            .finish();
+        let _guard = tracing::subscriber::set_default(logger);
+        let url = serve_with(|_| {
+            String::from("HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 2\r\n\r\nok")
+        });
+        let request = test_client()
+            .get(&url)
+            .header(header::RANGE, "bytes=0-")
+            .bearer_auth("secret");
+        send_traced(request).await.unwrap();
+        tracing::trace!("too detailed");
+        let log = output.text();
+        let lines: Vec<_> = log.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", log);
+        assert!(lines[0].starts_with("DEBUG godotup"), "{}", log);
+        assert!(lines[0].ends_with(&format!(
+            "request method=GET url={} range=\"bytes=0-\"",
+            url
+        )));
+        assert!(lines[1].contains(&format!(
+            "response method=GET url={} status=200 content_length=\"2\" etag=\"\\\"v1\\\"\" elapsed_ms=",
+            url
+        )));
+        assert!(!log.contains("secret"));
+    }
+}
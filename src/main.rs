@@ -1,5 +1,561 @@
-use godotup;
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use godotup::{
+    bin_dir, explain_error, godot, is_cancelled, json_progress::JsonProgress, logging,
+    markdown_to_text, parse_rate, CancellationToken, CliApp, InstallOptions, ListOptions,
+    OnExisting, ProgressReporter,
+};
+use indicatif::HumanBytes;
+use std::sync::Arc;
+use std::{env, path::Path};
 
-fn main() {
-    println!("Hello, world!");
+fn cli() -> Command {
+    let version_arg = Arg::new("version").required(true).help(
+        "Version to use, e.g. 4.2.1, 4.1, ^4.1, 4.3-dev, mono@4.2, latest, latest-prerelease, latest-dev or latest-mono",
+    );
+    let mono_arg = Arg::new("mono")
+        .long("mono")
+        .action(ArgAction::SetTrue)
+        .help("Use the mono (C#) build, same as a mono@ prefix");
+    let prerelease_arg = Arg::new("include-prerelease")
+        .long("include-prerelease")
+        .action(ArgAction::SetTrue)
+        .help("Consider alpha, beta and rc releases as well as stable ones");
+    let console_arg = Arg::new("console")
+        .long("console")
+        .action(ArgAction::SetTrue)
+        .help("Use the console wrapper of Windows builds, which shows godot's output");
+    Command::new("godotup")
+        .about("A command line tool to easily switch between different godot versions.")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .arg(
+            Arg::new("ipv4")
+                .long("ipv4")
+                .short('4')
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Only connect over IPv4"),
+        )
+        .arg(
+            Arg::new("quiet")
+                .long("quiet")
+                .short('q')
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Only print what was asked for, no progress, summaries or notices"),
+        )
+        .arg(
+            Arg::new("verbose")
+                .long("verbose")
+                .short('v')
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Log requests, retries and extraction to stderr, same as GODOTUP_LOG=debug"),
+        )
+        .arg(
+            Arg::new("progress")
+                .long("progress")
+                .global(true)
+                .value_parser(["auto", "json"])
+                .default_value("auto")
+                .help("How to show progress: bars, or lines when not on a terminal (auto), or JSON events on stderr (json)"),
+        )
+        .subcommand(
+            Command::new("update")
+                .about("Update the list of available versions")
+                .arg(
+                    Arg::new("allow-unsigned")
+                        .long("allow-unsigned")
+                        .action(ArgAction::SetTrue)
+                        .help("Accept version lists without a valid signature"),
+                ),
+        )
+        .subcommand(
+            Command::new("convert-list")
+                .about("Convert a version list to the current schema")
+                .arg(
+                    Arg::new("input")
+                        .required(true)
+                        .help("Version list to read"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .required(true)
+                        .help("Where to write the converted list"),
+                ),
+        )
+        .subcommand(
+            Command::new("export-list")
+                .about("Write the version list as YAML, JSON or TOML")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_parser(["yaml", "json", "toml"])
+                        .help("Output format, by default taken from the output's extension or YAML"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .help("Where to write the list instead of stdout"),
+                ),
+        )
+        .subcommand(
+            Command::new("generate-list")
+                .about("Generate a version list by crawling a download mirror")
+                .hide(true)
+                .arg(
+                    Arg::new("mirror")
+                        .required(true)
+                        .help("Base URL of the mirror, e.g. https://downloads.tuxfamily.org/godotengine/"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .help("Where to write the list instead of stdout"),
+                )
+                .arg(
+                    Arg::new("append-to")
+                        .long("append-to")
+                        .help("Merge into this existing list, keeping its entries"),
+                ),
+        )
+        .subcommand(
+            Command::new("install")
+                .about("Download and install a godot version")
+                .arg(version_arg.clone())
+                .arg(mono_arg.clone())
+                .arg(prerelease_arg.clone())
+                .arg(
+                    Arg::new("no-verify")
+                        .long("no-verify")
+                        .action(ArgAction::SetTrue)
+                        .help("Skip running the installed binary to check its version"),
+                )
+                .arg(
+                    Arg::new("no-refresh")
+                        .long("no-refresh")
+                        .action(ArgAction::SetTrue)
+                        .help("Use the cached version list even if it is out of date or lacks the version"),
+                )
+                .arg(
+                    Arg::new("limit-rate")
+                        .long("limit-rate")
+                        .value_name("RATE")
+                        .value_parser(|rate: &str| parse_rate(rate).map_err(|err| err.to_string()))
+                        .help("Limit the download speed in bytes per second, e.g. 500K or 2M; 0 for no limit"),
+                )
+                .arg(
+                    Arg::new("with-templates")
+                        .long("with-templates")
+                        .action(ArgAction::SetTrue)
+                        .help("Also install the export templates, downloading them alongside the editor"),
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .action(ArgAction::SetTrue)
+                        .help("Install even if there doesn't look to be enough free disk space"),
+                )
+                .arg(
+                    Arg::new("force-download")
+                        .long("force-download")
+                        .action(ArgAction::SetTrue)
+                        .help("Discard cached and partial downloads and download from scratch"),
+                )
+                .arg(
+                    Arg::new("reinstall")
+                        .long("reinstall")
+                        .action(ArgAction::SetTrue)
+                        .help("Replace the version if it is already installed, once the new install is ready"),
+                )
+                .arg(
+                    Arg::new("no-parallel")
+                        .long("no-parallel")
+                        .action(ArgAction::SetTrue)
+                        .help("Extract one file at a time"),
+                ),
+        )
+        .subcommand(
+            Command::new("mirrors")
+                .about("Manage download mirrors")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("test")
+                        .about("Measure how fast each configured mirror is")
+                        .arg(
+                            Arg::new("set-fastest")
+                                .long("set-fastest")
+                                .action(ArgAction::SetTrue)
+                                .help("Try the fastest mirror first from now on"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Show how godotup connects, to diagnose certificate errors"),
+        )
+        .subcommand(
+            Command::new("notes")
+                .about("Show the release notes of a godot version")
+                .arg(
+                    Arg::new("version")
+                        .required(true)
+                        .help("Version to show, e.g. 4.2.1 or 4.3-beta1"),
+                )
+                .arg(
+                    Arg::new("plain")
+                        .long("plain")
+                        .action(ArgAction::SetTrue)
+                        .help("Render the markdown as plain text"),
+                ),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("List the godot versions available for this platform")
+                .arg(
+                    Arg::new("installed")
+                        .long("installed")
+                        .action(ArgAction::SetTrue)
+                        .help("Only installed versions"),
+                )
+                .arg(
+                    Arg::new("since")
+                        .long("since")
+                        .value_name("DATE")
+                        .help("Only versions released on or after this date, e.g. 2024-01-01"),
+                )
+                .arg(
+                    Arg::new("channel")
+                        .long("channel")
+                        .value_parser(["stable", "rc", "beta", "alpha", "dev"])
+                        .help("Only versions on this channel"),
+                )
+                .arg(prerelease_arg)
+                .arg(
+                    Arg::new("by-date")
+                        .long("by-date")
+                        .action(ArgAction::SetTrue)
+                        .help("Sort by release date instead of by version"),
+                )
+                .arg(
+                    Arg::new("no-refresh")
+                        .long("no-refresh")
+                        .action(ArgAction::SetTrue)
+                        .help("Use the cached version list even if it is out of date"),
+                ),
+        )
+        .subcommand(
+            Command::new("switch")
+                .about("Switch to an installed godot version")
+                .arg(version_arg.clone())
+                .arg(mono_arg.clone()),
+        )
+        .subcommand(
+            Command::new("which")
+                .about("Print the path of an installed godot executable")
+                .arg(version_arg.clone())
+                .arg(mono_arg.clone())
+                .arg(console_arg.clone()),
+        )
+        .subcommand(
+            Command::new("run")
+                .about("Run an installed godot version")
+                .arg(version_arg.clone())
+                .arg(mono_arg.clone())
+                .arg(console_arg)
+                .arg(
+                    Arg::new("args")
+                        .num_args(0..)
+                        .trailing_var_arg(true)
+                        .allow_hyphen_values(true)
+                        .help("Arguments passed on to godot"),
+                ),
+        )
+        .subcommand(
+            Command::new("uninstall")
+                .about("Remove an installed godot version")
+                .arg(version_arg.clone())
+                .arg(mono_arg.clone())
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .action(ArgAction::SetTrue)
+                        .help("Also remove files added since it was installed"),
+                ),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Check an installed godot version for changed or missing files")
+                .arg(version_arg)
+                .arg(mono_arg),
+        )
+}
+
+fn version(matches: &ArgMatches) -> String {
+    let spec = matches.get_one::<String>("version").unwrap();
+    if matches.get_flag("mono") && !spec.to_ascii_lowercase().starts_with("mono@") {
+        format!("mono@{}", spec)
+    } else {
+        spec.clone()
+    }
+}
+
+/// Exit code after Ctrl-C, as shells report a process killed by SIGINT.
+const EXIT_INTERRUPTED: i32 = 130;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let matches = cli().get_matches();
+    logging::init(matches.get_flag("verbose"));
+    // Installs stop cleanly on the first Ctrl-C; anything else just quits.
+    let cancel = CancellationToken::new();
+    let cancellable = matches.subcommand_name() == Some("install");
+    let on_ctrl_c = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            if !cancellable {
+                std::process::exit(EXIT_INTERRUPTED);
+            }
+            eprintln!("\nInterrupted, cleaning up (press Ctrl-C again to quit now)...");
+            on_ctrl_c.cancel();
+        }
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(EXIT_INTERRUPTED);
+        }
+    });
+    let app = CliApp::load(matches.get_flag("ipv4"))?;
+    let quiet = matches.get_flag("quiet") || app.quiet();
+    let json = (matches.get_one::<String>("progress").unwrap() == "json")
+        .then(|| Arc::new(JsonProgress::stderr()));
+    let progress: Arc<dyn ProgressReporter> = match &json {
+        Some(json) => json.clone(),
+        None => Arc::new(app.terminal_progress(quiet)),
+    };
+    let mut app = app.with_progress(progress);
+    let result = async {
+        match matches.subcommand() {
+            Some(("update", sub)) => {
+                let diff = app
+                    .update_version_list(sub.get_flag("allow-unsigned"))
+                    .await?;
+                if !quiet {
+                    println!("{}", diff);
+                }
+                Ok(())
+            }
+            Some(("convert-list", sub)) => app.convert_version_list(
+                Path::new(sub.get_one::<String>("input").unwrap()),
+                Path::new(sub.get_one::<String>("output").unwrap()),
+            ),
+            Some(("export-list", sub)) => {
+                let output = sub.get_one::<String>("output").map(Path::new);
+                let format = match sub.get_one::<String>("format") {
+                    Some(format) => format.parse()?,
+                    None => output
+                        .and_then(godot::ListFormat::from_path)
+                        .unwrap_or(godot::ListFormat::Yaml),
+                };
+                app.export_version_list(format, output)
+            }
+            Some(("generate-list", sub)) => {
+                app.generate_version_list(
+                    sub.get_one::<String>("mirror").unwrap(),
+                    sub.get_one::<String>("output").map(Path::new),
+                    sub.get_one::<String>("append-to").map(Path::new),
+                )
+                .await
+            }
+            Some(("install", sub)) => {
+                let options = InstallOptions {
+                    verify: !sub.get_flag("no-verify"),
+                    refresh: !sub.get_flag("no-refresh"),
+                    max_rate: sub.get_one::<u64>("limit-rate").copied(),
+                    include_prerelease: sub.get_flag("include-prerelease"),
+                    templates: sub.get_flag("with-templates"),
+                    force: sub.get_flag("force"),
+                    force_download: sub.get_flag("force-download"),
+                    parallel_extraction: !sub.get_flag("no-parallel"),
+                    on_existing: sub.get_flag("reinstall").then_some(OnExisting::Replace),
+                };
+                let report = app
+                    .install_godot(&version(sub), options, Some(cancel.clone()))
+                    .await?;
+                if let Some(json) = &json {
+                    json.installed(&report);
+                }
+                Ok(())
+            }
+            Some(("mirrors", sub)) => match sub.subcommand() {
+                Some(("test", sub)) => {
+                    let results = app.test_mirrors().await?;
+                    for (rank, (mirror, speed)) in results.iter().enumerate() {
+                        match speed {
+                            Ok(speed) => println!(
+                                "{:>2}. {:<50} {:>6} ms {:>12}/s",
+                                rank + 1,
+                                mirror,
+                                speed.latency.as_millis(),
+                                HumanBytes(speed.throughput()).to_string()
+                            ),
+                            Err(err) => println!(" -. {:<50} {:#}", mirror, err),
+                        }
+                    }
+                    match results.first() {
+                        Some((fastest, Ok(_))) if sub.get_flag("set-fastest") => {
+                            app.set_preferred_mirror(fastest)?;
+                            if !quiet {
+                                println!("Using {} first from now on", fastest);
+                            }
+                        }
+                        _ => {}
+                    }
+                    Ok(())
+                }
+                _ => unreachable!(),
+            },
+            Some(("doctor", _)) => {
+                println!("Certificates: {}", app.tls_summary());
+                Ok(())
+            }
+            Some(("notes", sub)) => {
+                let version = godot::Version::parse(sub.get_one::<String>("version").unwrap())?;
+                match app.release_notes(&version).await? {
+                    Some(notes) if sub.get_flag("plain") => print!("{}", markdown_to_text(&notes)),
+                    Some(notes) => println!("{}", notes),
+                    None => println!("No release notes for {}", version.short()),
+                }
+                Ok(())
+            }
+            Some(("list", sub)) => {
+                let options = ListOptions {
+                    installed_only: sub.get_flag("installed"),
+                    since: sub.get_one::<String>("since").cloned(),
+                    channel: sub
+                        .get_one::<String>("channel")
+                        .map(|channel| channel.parse())
+                        .transpose()?,
+                    include_prerelease: sub.get_flag("include-prerelease"),
+                    by_date: sub.get_flag("by-date"),
+                    refresh: !sub.get_flag("no-refresh"),
+                };
+                for entry in app.list(&options).await? {
+                    let mut markers = Vec::new();
+                    if entry.version.channel() == godot::Channel::Dev {
+                        markers.push("[dev]");
+                    }
+                    if entry.local {
+                        markers.push("[local]");
+                    }
+                    match (entry.installed, entry.verified) {
+                        (true, Some(true)) => markers.extend(["[installed]", "[verified]"]),
+                        (true, Some(false)) => markers.extend(["[installed]", "[unverified]"]),
+                        (true, None) => markers.push("[installed]"),
+                        (false, _) => {}
+                    }
+                    if let Some(source) = &entry.source {
+                        markers.push(source);
+                    }
+                    let line = format!(
+                        "{:<20} {:<10} {}",
+                        entry.version.short(),
+                        entry.released.as_deref().unwrap_or("-"),
+                        markers.join(" ")
+                    );
+                    println!("{}", line.trim_end());
+                }
+                Ok(())
+            }
+            Some(("switch", sub)) => {
+                let spec = version(sub);
+                let switched = app.switch(&spec)?;
+                println!(
+                    "Switched to {} (resolved from `{}`)",
+                    switched.version.short(),
+                    spec
+                );
+                for link in &switched.links {
+                    println!("{} -> {}", link.display(), switched.executable.display());
+                }
+                let bin = bin_dir();
+                let on_path = env::var_os("PATH")
+                    .is_some_and(|path| env::split_paths(&path).any(|dir| dir == bin));
+                if !switched.links.is_empty() && !on_path {
+                    println!("Add {} to your PATH to run it as `godot`", bin.display());
+                }
+                Ok(())
+            }
+            Some(("which", sub)) => {
+                let exe = app.which(&version(sub), sub.get_flag("console"))?;
+                println!("{}", exe.display());
+                Ok(())
+            }
+            Some(("run", sub)) => {
+                let args: Vec<String> = sub
+                    .get_many::<String>("args")
+                    .unwrap_or_default()
+                    .cloned()
+                    .collect();
+                let status = app.run(&version(sub), sub.get_flag("console"), &args)?;
+                if !status.success() {
+                    std::process::exit(status.code().unwrap_or(1));
+                }
+                Ok(())
+            }
+            Some(("uninstall", sub)) => {
+                let removed = app.uninstall(&version(sub), sub.get_flag("force"))?;
+                println!(
+                    "Uninstalled {} from {}",
+                    removed.record.build.version.short(),
+                    removed.dir.display()
+                );
+                Ok(())
+            }
+            Some(("verify", sub)) => {
+                let verification = app.verify(&version(sub))?;
+                for (path, change) in &verification.changes {
+                    println!("{}: {}", path, change);
+                }
+                if !verification.changes.is_empty() {
+                    return Err(anyhow!(
+                        "{} of {} files of {} changed since it was installed; reinstall it with --reinstall",
+                        verification.changes.len(),
+                        verification.files,
+                        verification.version.short()
+                    ));
+                }
+                println!(
+                    "All {} files of {} are as installed",
+                    verification.files,
+                    verification.version.short()
+                );
+                Ok(())
+            }
+            _ => unreachable!(),
+        }
+    }
+    .await
+    .map_err(explain_error);
+    if let Some(json) = &json {
+        match &result {
+            Ok(()) => json.succeeded(),
+            Err(err) => json.failed(err),
+        }
+    }
+    if let Err(err) = &result {
+        if is_cancelled(err) {
+            eprintln!("Interrupted");
+            std::process::exit(EXIT_INTERRUPTED);
+        }
+    }
+    if result.is_ok() && !quiet && json.is_none() {
+        if let Some(notice) = app.new_release_notice() {
+            // On stderr to stay out of piped output.
+            eprintln!("{}", notice);
+        }
+    }
+    result
 }
@@ -0,0 +1,670 @@
+//! HTTP requests and the errors they fail with.
+
+use anyhow::anyhow;
+use anyhow::{Context, Result};
+use reqwest::{header, Client, StatusCode};
+use std::{
+    env,
+    net::{IpAddr, Ipv4Addr},
+    path::Path,
+    time::Duration,
+};
+use std::{fs, io};
+
+use crate::download::{ChecksumMismatch, Stalled};
+use crate::github::{GithubRateLimited, GITHUB_API};
+use crate::Config;
+
+pub(crate) fn http_error(url: &str, response: &reqwest::Response) -> anyhow::Error {
+    let retry_after = response
+        .headers()
+        .get(header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok()?.trim().parse().ok())
+        .map(Duration::from_secs);
+    let final_url = response.url().as_str();
+    HttpError {
+        url: url.to_string(),
+        redirected_to: (final_url != url).then(|| final_url.to_string()),
+        status: response.status(),
+        retry_after,
+    }
+    .into()
+}
+
+#[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+compile_error!("enable the `rustls` or the `native-tls` feature for HTTPS support");
+
+/// The root certificates of the TLS backend, trusted besides `extra_ca_cert`.
+#[cfg(feature = "native-tls")]
+pub(crate) const TLS_ROOTS: &str = "system root certificates";
+
+#[cfg(not(feature = "native-tls"))]
+pub(crate) const TLS_ROOTS: &str = "bundled Mozilla root certificates";
+
+/// A client using the proxy from `config` if it has one, and the
+/// `HTTPS_PROXY`, `HTTP_PROXY` and `NO_PROXY` environment variables
+/// otherwise. Proxy and certificate options work the same with either TLS
+/// backend; `native-tls` wins if both features are enabled.
+pub(crate) fn build_client(config: &Config) -> Result<Client> {
+    let mut builder = Client::builder();
+    #[cfg(feature = "native-tls")]
+    {
+        builder = builder.use_native_tls();
+    }
+    #[cfg(all(feature = "rustls", not(feature = "native-tls")))]
+    {
+        builder = builder.use_rustls_tls();
+    }
+    if let Some(url) = &config.proxy_url {
+        let mut proxy = reqwest::Proxy::all(url)
+            .with_context(|| format!("Invalid proxy_url `{}`", url))?
+            .no_proxy(reqwest::NoProxy::from_env());
+        if let Some(username) = &config.proxy_username {
+            proxy = proxy.basic_auth(username, config.proxy_password.as_deref().unwrap_or(""));
+        }
+        builder = builder.proxy(proxy);
+    }
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    if let Some(path) = &config.extra_ca_cert {
+        for cert in load_certificates(path)? {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    let agent = user_agent(config.user_agent_suffix.as_deref());
+    let agent = header::HeaderValue::from_str(&agent)
+        .with_context(|| format!("Invalid user_agent_suffix in `{}`", agent))?;
+    builder = builder
+        .user_agent(agent)
+        .redirect(redirect_policy(config.max_redirects))
+        .connect_timeout(Duration::from_secs(config.connect_timeout_secs));
+    if config.ipv4_only {
+        // Bound to an IPv4 address, the socket can't reach IPv6 ones.
+        builder = builder.local_address(IpAddr::from(Ipv4Addr::UNSPECIFIED));
+    }
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    if config.danger_accept_invalid_certs {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    Ok(builder.build()?)
+}
+
+/// `godotup/<version> (<os>; <arch>)`, followed by `suffix` if any.
+fn user_agent(suffix: Option<&str>) -> String {
+    let agent = format!(
+        "godotup/{} ({}; {})",
+        env!("CARGO_PKG_VERSION"),
+        env::consts::OS,
+        env::consts::ARCH
+    );
+    match suffix.map(str::trim).filter(|suffix| !suffix.is_empty()) {
+        Some(suffix) => format!("{} {}", agent, suffix),
+        None => agent,
+    }
+}
+
+/// Follows up to `max` redirects, but no loops and nothing from HTTPS back
+/// to HTTP. Errors name every hop. Credentials are dropped by reqwest itself
+/// when a redirect leaves the host, while other headers such as `Range`
+/// are kept.
+fn redirect_policy(max: usize) -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(move |attempt| {
+        match redirect_error(attempt.previous(), attempt.url(), max) {
+            Some(error) => attempt.error(error),
+            None => attempt.follow(),
+        }
+    })
+}
+
+/// Why [`redirect_policy`] refuses to follow the redirect from the last of
+/// `previous` to `url`, if it does.
+fn redirect_error(previous: &[reqwest::Url], url: &reqwest::Url, max: usize) -> Option<String> {
+    let chain = || {
+        let hops: Vec<_> = previous.iter().chain([url]).map(|u| u.as_str()).collect();
+        hops.join(" -> ")
+    };
+    if previous.contains(url) {
+        Some(format!("redirect loop: {}", chain()))
+    } else if previous.last().is_some_and(|p| p.scheme() == "https") && url.scheme() == "http" {
+        Some(format!(
+            "refusing to follow a redirect from HTTPS to HTTP: {}",
+            chain()
+        ))
+    } else if previous.len() > max {
+        Some(format!("more than {} redirects: {}", max, chain()))
+    } else {
+        None
+    }
+}
+
+/// Every certificate in the PEM file at `path`.
+pub(crate) fn load_certificates(path: &Path) -> Result<Vec<reqwest::Certificate>> {
+    let pem = fs::read_to_string(path)
+        .with_context(|| format!("Couldn't read extra_ca_cert {}", path.display()))?;
+    const END: &str = "-----END CERTIFICATE-----";
+    let certs = pem
+        .split_inclusive(END)
+        .filter_map(|block| Some(&block[block.find("-----BEGIN CERTIFICATE-----")?..]))
+        .map(|block| reqwest::Certificate::from_pem(block.as_bytes()))
+        .collect::<reqwest::Result<Vec<_>>>()
+        .with_context(|| format!("Invalid certificate in {}", path.display()))?;
+    if certs.is_empty() {
+        return Err(anyhow!("No certificates in {}", path.display()));
+    }
+    Ok(certs)
+}
+
+/// The HTTP status and URL of a failed response, if that is what `cause`
+/// is.
+fn http_failure<'a>(cause: &'a (dyn std::error::Error + 'static)) -> Option<(StatusCode, &'a str)> {
+    match cause.downcast_ref::<HttpError>() {
+        Some(err) => Some((err.status, &err.url)),
+        None => {
+            let err = cause.downcast_ref::<reqwest::Error>()?;
+            Some((err.status()?, err.url().map_or("", |url| url.as_str())))
+        }
+    }
+}
+
+/// Whether `err` came from the proxy rather than the server behind it.
+pub(crate) fn proxy_rejected(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        http_failure(cause).map(|(status, _)| status)
+            == Some(StatusCode::PROXY_AUTHENTICATION_REQUIRED)
+            || matches!(
+                cause.to_string().as_str(),
+                "proxy authentication required" | "unsuccessful tunnel"
+            )
+    })
+}
+
+/// The kinds of network failure [`explain_error`] has advice for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NetworkFailure {
+    ProxyRejected,
+    /// GitHub refused an API request, most likely for its rate limit.
+    GithubForbidden,
+    NotFound,
+    Tls,
+    Dns,
+    Connect,
+    Timeout,
+}
+
+impl NetworkFailure {
+    /// What `err` failed on, if it is a network error.
+    fn of(err: &anyhow::Error) -> Option<NetworkFailure> {
+        if proxy_rejected(err) {
+            return Some(NetworkFailure::ProxyRejected);
+        }
+        if err.chain().any(|cause| cause.is::<GithubRateLimited>()) {
+            // Explains itself.
+            return None;
+        }
+        for cause in err.chain() {
+            match http_failure(cause) {
+                Some((StatusCode::FORBIDDEN, url)) if url.starts_with(GITHUB_API) => {
+                    return Some(NetworkFailure::GithubForbidden)
+                }
+                Some((StatusCode::NOT_FOUND, _)) => return Some(NetworkFailure::NotFound),
+                _ => {}
+            }
+            if cause.is::<Stalled>() {
+                return Some(NetworkFailure::Timeout);
+            }
+            let Some(err) = cause.downcast_ref::<reqwest::Error>() else {
+                continue;
+            };
+            if err.is_timeout() {
+                return Some(NetworkFailure::Timeout);
+            }
+            if err.is_connect() {
+                return Some(connect_failure(err));
+            }
+        }
+        None
+    }
+
+    /// What to do about it.
+    fn hint(self) -> &'static str {
+        match self {
+            NetworkFailure::ProxyRejected => "The proxy refused the request; check proxy_url, proxy_username and proxy_password in the config, or the HTTPS_PROXY and HTTP_PROXY variables",
+            NetworkFailure::GithubForbidden => "GitHub refused the request, most likely for its rate limit; set github_token in the config, or GODOTUP_GITHUB_TOKEN or GITHUB_TOKEN",
+            NetworkFailure::NotFound => "The file is gone from the server; the version list may be outdated, run `godotup update`",
+            NetworkFailure::Tls => "The secure connection failed; behind a corporate proxy that inspects TLS, point extra_ca_cert in the config to its CA bundle",
+            NetworkFailure::Dns => "The host name couldn't be resolved; check the mirror URLs in download_mirrors and the proxy settings",
+            NetworkFailure::Connect => "The server couldn't be reached; check the mirror URLs in download_mirrors and the proxy settings",
+            NetworkFailure::Timeout => "The server took too long to answer; try again, or another mirror with `godotup mirrors test`",
+        }
+    }
+}
+
+/// Whether a connection failed on name resolution, the TLS handshake, or
+/// otherwise.
+fn connect_failure(err: &reqwest::Error) -> NetworkFailure {
+    let mut source = std::error::Error::source(err);
+    while let Some(cause) = source {
+        let message = cause.to_string().to_ascii_lowercase();
+        if message.contains("dns error") || message.contains("failed to lookup address") {
+            return NetworkFailure::Dns;
+        }
+        // TLS backends report handshake failures as invalid data.
+        let invalid_data = cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|err| err.kind() == io::ErrorKind::InvalidData);
+        if invalid_data || message.contains("certificate") || message.contains("tls") {
+            return NetworkFailure::Tls;
+        }
+        source = cause.source();
+    }
+    NetworkFailure::Connect
+}
+
+/// Adds advice on what to do about network failures and corrupt
+/// downloads; anything else is left alone.
+pub fn explain_error(err: anyhow::Error) -> anyhow::Error {
+    if let Some(failure) = NetworkFailure::of(&err) {
+        err.context(failure.hint())
+    } else if err.chain().any(|cause| cause.is::<ChecksumMismatch>()) {
+        err.context(
+            "The download is corrupt; run again with --force-download to start from scratch",
+        )
+    } else {
+        err
+    }
+}
+
+/// A download answered with an error status.
+#[derive(Debug)]
+pub(crate) struct HttpError {
+    pub(crate) url: String,
+    /// Where redirects ended up, if anywhere else.
+    redirected_to: Option<String>,
+    pub(crate) status: StatusCode,
+    pub(crate) retry_after: Option<Duration>,
+}
+
+impl std::fmt::Display for HttpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Couldn't download URL: {}", self.url)?;
+        if let Some(redirected_to) = &self.redirected_to {
+            write!(f, " (redirected to {})", redirected_to)?;
+        }
+        write!(f, ": {}", self.status)
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{path::PathBuf, sync::Arc};
+    use tokio_util::sync::CancellationToken;
+
+    use crate::download::{download_from_url, retry_delay};
+    use crate::github::github_request;
+    use crate::progress::NoProgress;
+    use crate::test_util::{
+        no_progress, serve, serve_with, test_client, test_dir, STALL, UNLIMITED,
+    };
+    use crate::CliApp;
+
+    #[tokio::test]
+    async fn test_proxy() {
+        // A proxy that wants `user:secret`, and answers in place of the server.
+        let proxy = serve_with(|head| {
+            let head = head.to_ascii_lowercase();
+            assert!(
+                head.starts_with("get http://godot.invalid/versions.yml"),
+                "{}",
+                head
+            );
+            if head.contains("proxy-authorization: basic dxnlcjpzzwnyzxq=") {
+                String::from("HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nproxy")
+            } else {
+                String::from(
+                    "HTTP/1.1 407 Proxy Authentication Required\r\nContent-Length: 0\r\n\r\n",
+                )
+            }
+        });
+        let proxy = proxy.trim_end_matches("/versions.yml");
+        let config = |password: &str| Config {
+            proxy_url: Some(proxy.to_string()),
+            proxy_username: Some(String::from("user")),
+            proxy_password: Some(password.to_string()),
+            ..Config::default()
+        };
+        let get = |config: Config| async move {
+            let client = build_client(&config)?;
+            let resp = client
+                .get("http://godot.invalid/versions.yml")
+                .send()
+                .await?;
+            Ok::<_, anyhow::Error>(resp.error_for_status()?.text().await?)
+        };
+
+        assert_eq!(get(config("secret")).await.unwrap(), "proxy");
+        let err = get(config("wrong")).await.unwrap_err();
+        assert!(proxy_rejected(&err));
+        assert!(explain_error(err)
+            .to_string()
+            .starts_with("The proxy refused"));
+        assert!(retry_delay(&anyhow::Error::new(HttpError {
+            url: String::new(),
+            redirected_to: None,
+            status: StatusCode::PROXY_AUTHENTICATION_REQUIRED,
+            retry_after: None,
+        }))
+        .is_none());
+
+        // Errors from the server itself aren't blamed on it.
+        let err = anyhow::Error::new(HttpError {
+            url: String::new(),
+            redirected_to: None,
+            status: StatusCode::NOT_FOUND,
+            retry_after: None,
+        });
+        assert!(!explain_error(err).to_string().contains("proxy"));
+        assert!(build_client(&Config {
+            proxy_url: Some(String::from("not a url")),
+            ..Config::default()
+        })
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_redirects() {
+        let tmp = test_dir("redirects");
+        let dir = tmp.path();
+        let path = dir.join("godot.zip");
+        let redirect = |to: String| {
+            move |_: &str| {
+                format!(
+                    "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\n\r\n",
+                    to
+                )
+            }
+        };
+
+        // The range survives the hop to another host; credentials don't.
+        let cdn = serve_with(|head| {
+            let head = head.to_ascii_lowercase();
+            assert!(head.contains("range: bytes=4-"), "{}", head);
+            assert!(!head.contains("authorization"), "{}", head);
+            String::from("HTTP/1.1 206 Partial Content\r\nContent-Length: 6\r\n\r\n456789")
+        });
+        let mirror = serve_with(redirect(cdn));
+        fs::write(&path, "0123").unwrap();
+        let resp = test_client()
+            .get(&mirror)
+            .header(header::RANGE, "bytes=4-")
+            .header(header::AUTHORIZATION, "Bearer secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        download_from_url(
+            &test_client(),
+            &mirror,
+            &path,
+            STALL,
+            &UNLIMITED,
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "0123456789");
+
+        // Failures name where the redirects led.
+        let gone = serve("404 Not Found", "");
+        let mirror = serve_with(redirect(gone.clone()));
+        let err = download_from_url(
+            &test_client(),
+            &mirror,
+            &path,
+            STALL,
+            &UNLIMITED,
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Couldn't download URL: {} (redirected to {}): 404 Not Found",
+                mirror, gone
+            )
+        );
+
+        // A loop is reported with its hops.
+        let looping = serve_with(|head| {
+            let path = head.split_whitespace().nth(1).unwrap();
+            let next = if path == "/a" { "/b" } else { "/a" };
+            format!(
+                "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\n\r\n",
+                next
+            )
+        });
+        let looping = looping.replace("/versions.yml", "/a");
+        let err = download_from_url(
+            &test_client(),
+            &looping,
+            &path,
+            STALL,
+            &UNLIMITED,
+            &CancellationToken::new(),
+            &no_progress(),
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            format!("{:#}", err).contains(&format!(
+                "redirect loop: {0} -> {1} -> {0}",
+                looping,
+                looping.replace("/a", "/b")
+            )),
+            "{:#}",
+            err
+        );
+
+        // No downgrades, and no endless chains.
+        let url = |u: &str| reqwest::Url::parse(u).unwrap();
+        let secure = [url("https://example.org/godot.zip")];
+        let err = redirect_error(&secure, &url("http://cdn.example.org/godot.zip"), 10).unwrap();
+        assert_eq!(
+            err,
+            "refusing to follow a redirect from HTTPS to HTTP: https://example.org/godot.zip -> http://cdn.example.org/godot.zip"
+        );
+        assert_eq!(
+            redirect_error(&secure, &url("https://cdn.example.org/godot.zip"), 10),
+            None
+        );
+        let hops: Vec<_> = (0..3)
+            .map(|i| url(&format!("https://{}.example.org/", i)))
+            .collect();
+        assert!(redirect_error(&hops, &url("https://3.example.org/"), 2)
+            .unwrap()
+            .starts_with("more than 2 redirects"));
+    }
+
+    #[tokio::test]
+    async fn test_ipv4_only() {
+        use std::net::TcpListener;
+
+        let client = build_client(&Config {
+            ipv4_only: true,
+            ..Config::default()
+        })
+        .unwrap();
+        let url = serve("200 OK", "v4");
+        let resp = client.get(&url).send().await.unwrap();
+        assert_eq!(resp.text().await.unwrap(), "v4");
+
+        // Where IPv6 works at all, it's refused.
+        let Ok(listener) = TcpListener::bind("[::1]:0") else {
+            return;
+        };
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let request = client.get(&url).send();
+        let refused = tokio::time::timeout(Duration::from_secs(5), request).await;
+        assert!(matches!(refused, Ok(Err(_))));
+    }
+
+    #[tokio::test]
+    async fn test_user_agent() {
+        let agents = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = agents.clone();
+        let url = serve_with(move |head| {
+            let agent = head.lines().find_map(|line| {
+                line.to_ascii_lowercase()
+                    .starts_with("user-agent:")
+                    .then(|| line[11..].trim().to_string())
+            });
+            seen.lock().unwrap().push(agent.unwrap_or_default());
+            String::from("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+        });
+        let config = Config {
+            user_agent_suffix: Some(String::from("acme-ci/1")),
+            ..Config::default()
+        };
+        let client = build_client(&config).unwrap();
+        client.head(&url).send().await.unwrap();
+        client.get(&url).send().await.unwrap();
+        github_request(&client, &url, None).send().await.unwrap();
+        let expected = format!(
+            "godotup/{} ({}; {}) acme-ci/1",
+            env!("CARGO_PKG_VERSION"),
+            env::consts::OS,
+            env::consts::ARCH
+        );
+        assert_eq!(*agents.lock().unwrap(), vec![expected; 3]);
+        assert_eq!(user_agent(Some(" ")), user_agent(None));
+
+        let config = Config {
+            user_agent_suffix: Some(String::from("bad\nagent")),
+            ..Config::default()
+        };
+        assert!(build_client(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_network_failures() {
+        let http = |url: &str, status| {
+            anyhow::Error::new(HttpError {
+                url: url.to_string(),
+                redirected_to: None,
+                status,
+                retry_after: None,
+            })
+        };
+        let of = |err: &anyhow::Error| NetworkFailure::of(err);
+        assert_eq!(
+            of(&http(
+                "https://example.org/godot.zip",
+                StatusCode::NOT_FOUND
+            )),
+            Some(NetworkFailure::NotFound)
+        );
+        assert_eq!(
+            of(&http(
+                "https://api.github.com/repos/godotengine/godot/releases",
+                StatusCode::FORBIDDEN
+            )),
+            Some(NetworkFailure::GithubForbidden)
+        );
+        assert_eq!(
+            of(&http(
+                "https://example.org/godot.zip",
+                StatusCode::FORBIDDEN
+            )),
+            None
+        );
+        let limited = anyhow::Error::new(GithubRateLimited {
+            reset: None,
+            authenticated: false,
+        });
+        assert_eq!(of(&limited), None);
+        let stalled = anyhow::Error::new(Stalled {
+            url: String::new(),
+            timeout: STALL,
+        })
+        .context("Download failed");
+        assert_eq!(of(&stalled), Some(NetworkFailure::Timeout));
+
+        let client = test_client();
+        let get = |url: String| {
+            let client = client.clone();
+            async move { anyhow::Error::new(client.get(url).send().await.unwrap_err()) }
+        };
+        let refused = get(String::from("http://127.0.0.1:9/versions.yml")).await;
+        assert_eq!(of(&refused), Some(NetworkFailure::Connect));
+        let unresolved = get(String::from("http://godot.invalid/versions.yml")).await;
+        assert_eq!(of(&unresolved), Some(NetworkFailure::Dns));
+        // A handshake with a server that doesn't speak TLS.
+        let plain = serve_with(|_| String::from("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"));
+        let tls = get(plain.replacen("http://", "https://", 1)).await;
+        assert_eq!(of(&tls), Some(NetworkFailure::Tls));
+        let timeout = client
+            .get(serve_with(|_| {
+                std::thread::sleep(Duration::from_secs(2));
+                String::new()
+            }))
+            .timeout(Duration::from_millis(50))
+            .send()
+            .await
+            .unwrap_err();
+        assert_eq!(
+            of(&anyhow::Error::new(timeout)),
+            Some(NetworkFailure::Timeout)
+        );
+
+        assert!(explain_error(tls).to_string().contains("extra_ca_cert"));
+        assert!(explain_error(http("", StatusCode::NOT_FOUND))
+            .to_string()
+            .contains("godotup update"));
+        assert_eq!(explain_error(anyhow!("other")).to_string(), "other");
+    }
+
+    #[test]
+    fn test_extra_ca_cert() {
+        let bundle = PathBuf::from("tests/fixtures/ca-bundle.pem");
+        assert_eq!(load_certificates(&bundle).unwrap().len(), 2);
+        let config = Config {
+            extra_ca_cert: Some(bundle.clone()),
+            ..Config::default()
+        };
+        let app = CliApp {
+            client: build_client(&config).unwrap(),
+            config,
+            progress: Arc::new(NoProgress),
+            warnings: Vec::new(),
+        };
+        assert_eq!(
+            app.tls_summary(),
+            format!("{} and 2 from tests/fixtures/ca-bundle.pem", TLS_ROOTS)
+        );
+
+        let tmp = test_dir("extra-ca-cert");
+        let dir = tmp.path();
+        let empty = dir.join("empty.pem");
+        fs::write(&empty, "no certificates here").unwrap();
+        let err = load_certificates(&empty).unwrap_err();
+        assert!(err.to_string().starts_with("No certificates"), "{}", err);
+        assert!(load_certificates(&dir.join("missing.pem")).is_err());
+        let broken = dir.join("broken.pem");
+        fs::write(
+            &broken,
+            "-----BEGIN CERTIFICATE-----\nnot base64\n-----END CERTIFICATE-----\n",
+        )
+        .unwrap();
+        // native-tls rejects it right away, rustls once building the client.
+        assert!(build_client(&Config {
+            extra_ca_cert: Some(broken),
+            ..Config::default()
+        })
+        .is_err());
+    }
+}
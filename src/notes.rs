@@ -0,0 +1,179 @@
+//! Release notes, fetched from GitHub or the version list and shown as
+//! plain text on request.
+
+use anyhow::{Context, Result};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+
+use crate::download::send_traced;
+use crate::github::{github_request, github_response};
+
+#[derive(Deserialize)]
+struct GithubReleaseNotes {
+    body: Option<String>,
+}
+
+/// The body of the release tagged `tag` in the first of `repos` that has
+/// one. Releases that are missing or have an empty body are skipped.
+pub(crate) async fn fetch_github_release_notes(
+    client: &Client,
+    api: &str,
+    repos: &[String],
+    token: Option<&str>,
+    tag: &str,
+) -> Result<Option<String>> {
+    for repo in repos {
+        let url = format!("{}/repos/{}/releases/tags/{}", api, repo, tag);
+        let resp = send_traced(github_request(client, &url, token)).await?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            continue;
+        }
+        let release = github_response(resp, &url, token)?
+            .json::<GithubReleaseNotes>()
+            .await?;
+        if let Some(body) = release.body.filter(|b| !b.trim().is_empty()) {
+            return Ok(Some(body));
+        }
+    }
+    Ok(None)
+}
+
+/// Release notes published at `url`; `None` if there are none.
+pub(crate) async fn fetch_notes(client: &Client, url: &str) -> Result<Option<String>> {
+    let resp = send_traced(client.get(url)).await?;
+    if resp.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let text = resp
+        .error_for_status()
+        .with_context(|| format!("could not fetch release notes from {}", url))?
+        .text()
+        .await?;
+    Ok(Some(text).filter(|t| !t.trim().is_empty()))
+}
+
+/// Rough plain-text rendering of markdown release notes: headings, emphasis
+/// and code markers are dropped and links become `text (url)`.
+pub fn markdown_to_text(markdown: &str) -> String {
+    let mut text = String::new();
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+        let line = if trimmed.starts_with('#') {
+            trimmed.trim_start_matches('#').trim_start().to_string()
+        } else if let Some(item) = trimmed
+            .strip_prefix("* ")
+            .or_else(|| trimmed.strip_prefix("+ "))
+        {
+            format!("{}- {}", indent, item)
+        } else {
+            line.to_string()
+        };
+        text.push_str(&markdown_inline_to_text(&line));
+        text.push('\n');
+    }
+    text
+}
+
+fn markdown_inline_to_text(line: &str) -> String {
+    let mut text = String::new();
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("**") || rest.starts_with("__") {
+            rest = &rest[2..];
+            continue;
+        }
+        if c == '`' {
+            rest = &rest[1..];
+            continue;
+        }
+        let link = rest.strip_prefix('!').unwrap_or(rest);
+        if let Some((label, url, after)) = markdown_link(link) {
+            if link.len() == rest.len() {
+                text.push_str(&format!("{} ({})", label, url));
+            } else {
+                text.push_str(label);
+            }
+            rest = after;
+            continue;
+        }
+        text.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    text
+}
+
+/// Splits `[label](url)rest` into its parts.
+fn markdown_link(s: &str) -> Option<(&str, &str, &str)> {
+    let (label, rest) = s.strip_prefix('[')?.split_once("](")?;
+    let (url, rest) = rest.split_once(')')?;
+    Some((label, url, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::{serve_with, test_client};
+
+    #[tokio::test]
+    async fn test_fetch_github_release_notes() {
+        let url = serve_with(|head| {
+            let (status, body) = if head
+                .starts_with("GET /repos/godotengine/godot-builds/releases/tags/4.2.1-stable ")
+            {
+                ("200 OK", r###"{"body": "## Fixes\n\n* Crash on exit"}"###)
+            } else if head.contains("/tags/4.3-dev1 ") {
+                ("200 OK", r#"{"body": null}"#)
+            } else {
+                ("404 Not Found", "{}")
+            };
+            format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+        });
+        let api = url.trim_end_matches("/versions.yml");
+        let repos = [
+            String::from("godotengine/godot"),
+            String::from("godotengine/godot-builds"),
+        ];
+        let client = test_client();
+        let notes = |tag| fetch_github_release_notes(&client, api, &repos, None, tag);
+        assert_eq!(
+            notes("4.2.1-stable").await.unwrap().as_deref(),
+            Some("## Fixes\n\n* Crash on exit")
+        );
+        assert_eq!(notes("4.3-dev1").await.unwrap(), None);
+        assert_eq!(notes("9.9-stable").await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_markdown_to_text() {
+        let markdown = "\
+# Godot 4.2.1
+
+Maintenance release, see the [blog post](https://godotengine.org/article/x).
+
+## Fixes
+* **Core**: Fix `Object` leak
+  + Nested ![icon](icon.png) item
+- Plain item
+";
+        assert_eq!(
+            markdown_to_text(markdown),
+            "\
+Godot 4.2.1
+
+Maintenance release, see the blog post (https://godotengine.org/article/x).
+
+Fixes
+- Core: Fix Object leak
+  - Nested icon item
+- Plain item
+"
+        );
+    }
+}
@@ -0,0 +1,532 @@
+//! Reporting progress, and the bars showing it on a terminal.
+
+use anyhow::anyhow;
+use anyhow::{Context, Result};
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressState, ProgressStyle};
+use std::io;
+use std::io::IsTerminal;
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicU64, Ordering as AtomicOrdering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::godot;
+
+/// What an install is busy with, see [`ProgressReporter::phase_changed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Downloading,
+    /// Hashing what an earlier attempt downloaded before resuming, or a
+    /// download made over several connections.
+    Checksumming,
+    /// Waiting before the given attempt of a failed download.
+    Retrying {
+        attempt: u32,
+        attempts: u32,
+    },
+    Extracting,
+}
+
+/// Receives the progress of downloads, checksums and extraction. Each phase
+/// is announced, then `started` with the number of bytes it goes through if
+/// known, `advanced` as they are, and `finished` once it succeeds. A failed
+/// phase isn't finished; the next one, if any, is announced instead.
+pub trait ProgressReporter: Send + Sync {
+    fn phase_changed(&self, phase: Phase);
+    fn started(&self, total: Option<u64>);
+    fn advanced(&self, bytes: u64);
+    /// `bytes` of the phase were already done by an earlier attempt, as
+    /// when resuming a download. Counts as advanced unless implemented.
+    fn resumed(&self, bytes: u64) {
+        self.advanced(bytes);
+    }
+    fn finished(&self);
+    /// A line about what's going on, like which file was extracted. Ignored
+    /// unless implemented.
+    fn message(&self, _message: &str) {}
+    /// Something that went wrong without stopping the command, like a
+    /// download without a checksum. Ignored unless implemented.
+    fn warning(&self, _message: &str) {}
+    /// A reporter for one of several downloads running at once, labelled
+    /// `label`. By default they all report here.
+    fn transfer(&self, _label: &str) -> Option<Arc<dyn ProgressReporter>> {
+        None
+    }
+    /// Which version what follows installs. Ignored unless implemented.
+    fn installing(&self, _version: &godot::Version) {}
+}
+
+/// Ignores all progress and messages; what [`CliApp`](crate::CliApp) reports to unless
+/// told otherwise.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl ProgressReporter for NoProgress {
+    fn phase_changed(&self, _phase: Phase) {}
+    fn started(&self, _total: Option<u64>) {}
+    fn advanced(&self, _bytes: u64) {}
+    fn finished(&self) {}
+}
+
+/// Checksums of at least this many bytes show progress in the terminal.
+pub(crate) const HASH_PROGRESS_BYTES: u64 = 64 * 1024 * 1024;
+
+/// How often [`TerminalProgress`] prints a line when it can't draw bars,
+/// unless another tenth of the total arrived first.
+const PROGRESS_LINE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Shows progress in the terminal: indicatif bars on a terminal, or a line
+/// every few seconds or tenth of the way when output goes to a file or
+/// pipe, as in CI logs. Quiet shows neither, nor any messages. Extraction
+/// lists each file as it goes instead. Downloads running at once get a
+/// labelled bar each, stacked.
+pub struct TerminalProgress {
+    quiet: bool,
+    interactive: bool,
+    /// Download bar template and fill characters, if not the defaults.
+    template: Option<String>,
+    chars: Option<String>,
+    multi: MultiProgress,
+    /// Which of several downloads this one is.
+    label: Option<String>,
+    state: std::sync::Mutex<TerminalState>,
+}
+
+#[derive(Default)]
+struct TerminalState {
+    phase: Option<Phase>,
+    bar: Option<ProgressBar>,
+    total: Option<u64>,
+    done: u64,
+    /// When the last line was printed, and how far along.
+    reported: Option<(Instant, u64)>,
+}
+
+impl TerminalProgress {
+    /// Draws bars if stderr is a terminal.
+    pub fn new(quiet: bool) -> Self {
+        Self {
+            quiet,
+            interactive: io::stderr().is_terminal(),
+            template: None,
+            chars: None,
+            multi: MultiProgress::new(),
+            label: None,
+            state: Default::default(),
+        }
+    }
+
+    /// Uses an indicatif `template` and fill `chars` for download bars. An
+    /// invalid style falls back to the default.
+    pub fn with_style(self, template: Option<String>, chars: Option<String>) -> Self {
+        Self {
+            template,
+            chars,
+            ..self
+        }
+    }
+
+    /// The style of download bars, also used for extraction so an install
+    /// looks like one pipeline. `prefixed` bars start with their prefix.
+    fn download_style(&self, prefixed: bool) -> ProgressStyle {
+        let template = self.template.as_deref().unwrap_or(DOWNLOAD_TEMPLATE);
+        let template = match prefixed {
+            true => format!("{{prefix:.bold}} {}", template),
+            false => template.to_string(),
+        };
+        download_style(Some(&template), self.chars.as_deref())
+            .or_else(|_| download_style(None, None))
+            .expect("the default style is valid")
+    }
+
+    fn bar(&self, phase: Option<Phase>, total: Option<u64>) -> ProgressBar {
+        let bar = match (phase, total) {
+            (Some(Phase::Downloading), Some(total)) => {
+                ProgressBar::new(total).with_style(self.download_style(self.label.is_some()))
+            }
+            (Some(Phase::Extracting), Some(total)) => {
+                let bar = ProgressBar::new(total).with_style(self.download_style(true));
+                bar.set_prefix(self.line_name(Some(Phase::Extracting)));
+                return self.multi.add(bar);
+            }
+            // Without a Content-Length there's no total to fill a bar up to.
+            (Some(Phase::Downloading), None) => ProgressBar::new_spinner().with_style(
+                ProgressStyle::default_spinner()
+                    .template("{spinner:.green} [{elapsed_precise}] {bytes} ({bytes_per_sec})")
+                    .unwrap(),
+            ),
+            (Some(Phase::Checksumming), Some(total)) if total >= HASH_PROGRESS_BYTES => {
+                ProgressBar::new(total).with_style(
+                    ProgressStyle::default_bar()
+                        .template("Checking download [{bar:40.cyan/blue}] {bytes}/{total_bytes}")
+                        .unwrap()
+                        .progress_chars("#>-"),
+                )
+            }
+            _ => return ProgressBar::hidden(),
+        };
+        if let Some(label) = &self.label {
+            bar.set_prefix(label.clone());
+        }
+        self.multi.add(bar)
+    }
+
+    /// Whether a phase gets lines at all when bars can't be drawn.
+    fn has_lines(state: &TerminalState) -> bool {
+        match state.phase {
+            Some(Phase::Downloading | Phase::Extracting) => true,
+            Some(Phase::Checksumming) => state.total.unwrap_or(0) >= HASH_PROGRESS_BYTES,
+            _ => false,
+        }
+    }
+
+    /// What lines and extraction bars say is going on.
+    fn line_name(&self, phase: Option<Phase>) -> String {
+        match (phase, &self.label) {
+            (Some(Phase::Checksumming), None) => String::from("Checking download"),
+            (Some(Phase::Checksumming), Some(label)) => format!("Checking {}", label),
+            (Some(Phase::Extracting), None) => String::from("Extracting"),
+            (Some(Phase::Extracting), Some(label)) => format!("Extracting {}", label),
+            (_, None) => String::from("Downloading"),
+            (_, Some(label)) => format!("Downloading {}", label),
+        }
+    }
+
+    fn print_line(&self, state: &TerminalState) {
+        let name = self.line_name(state.phase);
+        match state.total {
+            Some(total) => eprintln!(
+                "{}: {} of {} ({}%)",
+                name,
+                HumanBytes(state.done),
+                HumanBytes(total),
+                state.done * 100 / total.max(1)
+            ),
+            None => eprintln!("{}: {}", name, HumanBytes(state.done)),
+        }
+    }
+}
+
+impl Default for TerminalProgress {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+/// Whether a line is due after `done` bytes: every tenth of `total`, or
+/// every [`PROGRESS_LINE_INTERVAL`] whichever comes first.
+fn progress_line_due(
+    reported: Option<(Instant, u64)>,
+    now: Instant,
+    done: u64,
+    total: Option<u64>,
+) -> bool {
+    let Some((at, was)) = reported else {
+        return true;
+    };
+    let tenth = total.map(|total| done * 10 / total.max(1) > was * 10 / total.max(1));
+    tenth == Some(true) || now.duration_since(at) >= PROGRESS_LINE_INTERVAL
+}
+
+impl ProgressReporter for TerminalProgress {
+    fn phase_changed(&self, phase: Phase) {
+        if self.quiet {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.phase = Some(phase);
+        let bar = match phase {
+            Phase::Retrying { attempt, attempts } if self.interactive => {
+                let spinner = self.multi.add(ProgressBar::new_spinner());
+                spinner.enable_steady_tick(Duration::from_millis(100));
+                spinner.set_message(match &self.label {
+                    Some(label) => format!("{}: retrying ({}/{})…", label, attempt, attempts),
+                    None => format!("retrying ({}/{})…", attempt, attempts),
+                });
+                Some(spinner)
+            }
+            Phase::Retrying { attempt, attempts } => {
+                match &self.label {
+                    Some(label) => eprintln!("{}: retrying ({}/{})…", label, attempt, attempts),
+                    None => eprintln!("Retrying ({}/{})…", attempt, attempts),
+                }
+                None
+            }
+            _ => None,
+        };
+        // A bar left over from a failed phase stays where it stopped.
+        if let Some(old) = std::mem::replace(&mut state.bar, bar) {
+            if old.is_hidden() || old.length().is_none() {
+                old.finish_and_clear();
+            } else {
+                old.abandon();
+            }
+        }
+    }
+
+    fn started(&self, total: Option<u64>) {
+        if self.quiet {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.total = total;
+        state.done = 0;
+        state.reported = None;
+        if !self.interactive {
+            return;
+        }
+        let bar = self.bar(state.phase, total);
+        if let Some(old) = state.bar.replace(bar) {
+            old.finish_and_clear();
+        }
+    }
+
+    fn advanced(&self, bytes: u64) {
+        if self.quiet {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.done += bytes;
+        if let Some(bar) = &state.bar {
+            bar.inc(bytes);
+        }
+        let now = Instant::now();
+        if !self.interactive
+            && Self::has_lines(&state)
+            && progress_line_due(state.reported, now, state.done, state.total)
+        {
+            self.print_line(&state);
+            state.reported = Some((now, state.done));
+        }
+    }
+
+    fn finished(&self) {
+        if self.quiet {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if let Some(bar) = state.bar.take() {
+            bar.finish_and_clear();
+        }
+        // The last line should show where it ended, not the tenth before.
+        if !self.interactive
+            && Self::has_lines(&state)
+            && state.reported.map(|(_, done)| done) != Some(state.done)
+        {
+            self.print_line(&state);
+        }
+    }
+
+    fn message(&self, message: &str) {
+        if self.quiet {
+            return;
+        }
+        self.multi.suspend(|| println!("{}", message));
+    }
+
+    /// Shown even when quiet.
+    fn warning(&self, message: &str) {
+        self.multi.suspend(|| eprintln!("warning: {}", message));
+    }
+
+    fn transfer(&self, label: &str) -> Option<Arc<dyn ProgressReporter>> {
+        Some(Arc::new(TerminalProgress {
+            quiet: self.quiet,
+            interactive: self.interactive,
+            template: self.template.clone(),
+            chars: self.chars.clone(),
+            multi: self.multi.clone(),
+            label: Some(label.to_string()),
+            state: Default::default(),
+        }))
+    }
+}
+
+const DOWNLOAD_TEMPLATE: &str = "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} {bytes_per_sec} ({eta})";
+
+const DOWNLOAD_PROGRESS_CHARS: &str = "#>-";
+
+/// The style of download bars, from an indicatif `template` and the
+/// `chars` to fill the bar with, each defaulting to godotup's own.
+pub(crate) fn download_style(template: Option<&str>, chars: Option<&str>) -> Result<ProgressStyle> {
+    let template = template.unwrap_or(DOWNLOAD_TEMPLATE);
+    let chars = chars.unwrap_or(DOWNLOAD_PROGRESS_CHARS);
+    // indicatif panics on these instead of returning an error.
+    let widths: HashSet<_> = chars
+        .chars()
+        .map(|c| console::measure_text_width(&c.to_string()))
+        .collect();
+    if chars.chars().count() < 2 || widths.len() != 1 {
+        return Err(anyhow!(
+            "progress_chars must have at least 2 characters of the same width, not {:?}",
+            chars
+        ));
+    }
+    Ok(ProgressStyle::default_bar()
+        .template(template)
+        .with_context(|| format!("invalid progress_template {:?}", template))?
+        .with_key(
+            "eta",
+            |state: &ProgressState, w: &mut dyn std::fmt::Write| {
+                write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap();
+            },
+        )
+        .progress_chars(chars))
+}
+
+/// Passes progress on while counting what a download actually transferred,
+/// for its [`DownloadReport`](crate::DownloadReport).
+pub(crate) struct DownloadTally {
+    inner: Arc<dyn ProgressReporter>,
+    downloading: std::sync::atomic::AtomicBool,
+    pub(crate) transferred: AtomicU64,
+    /// Set when the first download attempt continued a partial download.
+    pub(crate) resumed: std::sync::atomic::AtomicBool,
+}
+
+impl DownloadTally {
+    pub(crate) fn new(inner: Arc<dyn ProgressReporter>) -> Self {
+        Self {
+            inner,
+            downloading: Default::default(),
+            transferred: Default::default(),
+            resumed: Default::default(),
+        }
+    }
+}
+
+impl ProgressReporter for DownloadTally {
+    fn phase_changed(&self, phase: Phase) {
+        self.downloading
+            .store(phase == Phase::Downloading, AtomicOrdering::SeqCst);
+        self.inner.phase_changed(phase);
+    }
+
+    fn started(&self, total: Option<u64>) {
+        self.inner.started(total);
+    }
+
+    fn advanced(&self, bytes: u64) {
+        if self.downloading.load(AtomicOrdering::SeqCst) {
+            self.transferred.fetch_add(bytes, AtomicOrdering::SeqCst);
+        }
+        self.inner.advanced(bytes);
+    }
+
+    fn resumed(&self, bytes: u64) {
+        if bytes > 0 && self.transferred.load(AtomicOrdering::SeqCst) == 0 {
+            self.resumed.store(true, AtomicOrdering::SeqCst);
+        }
+        self.inner.resumed(bytes);
+    }
+
+    fn finished(&self) {
+        self.inner.finished();
+    }
+
+    fn message(&self, message: &str) {
+        self.inner.message(message);
+    }
+
+    fn warning(&self, message: &str) {
+        self.inner.warning(message);
+    }
+
+    fn transfer(&self, label: &str) -> Option<Arc<dyn ProgressReporter>> {
+        self.inner.transfer(label)
+    }
+
+    fn installing(&self, version: &godot::Version) {
+        self.inner.installing(version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use tokio_util::sync::CancellationToken;
+
+    use crate::download::download_from_url;
+    use crate::test_util::{no_progress, serve_with, test_client, test_dir, STALL, UNLIMITED};
+    use crate::DownloadReport;
+
+    #[test]
+    fn test_progress_line_due() {
+        let start = Instant::now();
+        let second = Duration::from_secs(1);
+        assert!(progress_line_due(None, start, 0, Some(100)));
+        let reported = Some((start, 5));
+        assert!(!progress_line_due(reported, start + second, 9, Some(100)));
+        assert!(progress_line_due(reported, start + second, 10, Some(100)));
+        assert!(progress_line_due(
+            reported,
+            start + 5 * second,
+            6,
+            Some(100)
+        ));
+        assert!(!progress_line_due(reported, start + second, 1 << 30, None));
+        assert!(progress_line_due(
+            reported,
+            start + 5 * second,
+            1 << 30,
+            None
+        ));
+    }
+
+    #[test]
+    fn test_download_style() {
+        assert!(download_style(None, None).is_ok());
+        assert!(download_style(Some("{bar} {bytes_per_sec}"), Some("=> ")).is_ok());
+        assert!(download_style(Some("{bar:wide}"), None).is_err());
+        assert!(download_style(None, Some("#")).is_err());
+        assert!(download_style(None, Some("＃>-")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_download_tally() {
+        let tmp = test_dir("download-tally");
+        let dir = tmp.path();
+        let path = dir.join("godot.zip");
+        fs::write(&path, "0123").unwrap();
+        let url = serve_with(|_| {
+            String::from("HTTP/1.1 206 Partial Content\r\nContent-Length: 6\r\n\r\n456789")
+        });
+        let tally = Arc::new(DownloadTally::new(no_progress()));
+        let progress: Arc<dyn ProgressReporter> = tally.clone();
+        let cancel = CancellationToken::new();
+        download_from_url(
+            &test_client(),
+            &url,
+            &path,
+            STALL,
+            &UNLIMITED,
+            &cancel,
+            &progress,
+        )
+        .await
+        .unwrap();
+        // Hashing the resumed prefix doesn't count either.
+        assert_eq!(tally.transferred.load(AtomicOrdering::SeqCst), 6);
+        assert!(tally.resumed.load(AtomicOrdering::SeqCst));
+
+        let report = DownloadReport {
+            filename: String::from("Godot_v4.2.1-stable_linux.x86_64.zip"),
+            url: String::from("https://example.org/godot.zip"),
+            size: 10 << 20,
+            transferred: 8 << 20,
+            resumed: true,
+            cached: false,
+            seconds: 2.0,
+        };
+        assert_eq!(
+            report.to_string(),
+            "Downloaded Godot_v4.2.1-stable_linux.x86_64.zip (10.00 MiB) in 2.0s (4.00 MiB/s) from https://example.org/godot.zip, resumed with 8.00 MiB left"
+        );
+    }
+}
@@ -0,0 +1,262 @@
+//! Helpers for the tests of several modules.
+
+use reqwest::Client;
+use std::io::Write;
+use std::{fs, io};
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use crate::download::RateLimit;
+use crate::network::build_client;
+use crate::progress::{NoProgress, Phase, ProgressReporter};
+use crate::Config;
+
+/// A fresh, empty directory for the test `name`, removed once dropped.
+pub(crate) fn test_dir(name: &str) -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix(&format!("godotup-test-{}-", name))
+        .tempdir()
+        .unwrap()
+}
+
+/// A zip of `entries`, each holding its own name, or a symlink for
+/// `<name> -> <target>`.
+pub(crate) fn synthetic_zip(name: &str, entries: &[&str]) -> (tempfile::TempDir, PathBuf) {
+    let dir = test_dir(name);
+    let path = dir.path().join("archive.zip");
+    let mut zip = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+    for entry in entries {
+        let options = zip::write::FileOptions::default();
+        if let Some((link, target)) = entry.split_once(" -> ") {
+            zip.add_symlink(link, target, options).unwrap();
+        } else if entry.ends_with('/') {
+            zip.add_directory(*entry, options).unwrap();
+        } else {
+            zip.start_file(*entry, options).unwrap();
+            zip.write_all(entry.as_bytes()).unwrap();
+        }
+    }
+    zip.finish().unwrap();
+    (dir, path)
+}
+
+/// A tarball of `entries`, written as [`synthetic_zip`] writes them and
+/// compressed by the `extension` of its name. Names are put in the header
+/// as they are, unless too long for it.
+pub(crate) fn synthetic_tar(
+    name: &str,
+    extension: &str,
+    entries: &[&str],
+) -> (tempfile::TempDir, PathBuf) {
+    let mut tar = tar::Builder::new(Vec::new());
+    for entry in entries {
+        let (path, link) = entry.split_once(" -> ").unwrap_or((entry, ""));
+        let mut header = tar::Header::new_ustar();
+        header.set_mtime(1_701_433_800);
+        let data = match (link, path.ends_with('/')) {
+            ("", true) => {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_mode(0o755);
+                ""
+            }
+            ("", false) => {
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_mode(0o644);
+                path
+            }
+            (link, _) => {
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_mode(0o777);
+                let ustar = header.as_ustar_mut().unwrap();
+                ustar.linkname[..link.len()].copy_from_slice(link.as_bytes());
+                ""
+            }
+        };
+        header.set_size(data.len() as u64);
+        if path.len() > 100 {
+            tar.append_data(&mut header, path, data.as_bytes()).unwrap();
+        } else {
+            // Not through `set_path`, which turns away hostile names.
+            let ustar = header.as_ustar_mut().unwrap();
+            ustar.name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_cksum();
+            tar.append(&header, data.as_bytes()).unwrap();
+        }
+    }
+    let tar = tar.into_inner().unwrap();
+
+    let dir = test_dir(name);
+    let path = dir.path().join(format!("archive{}", extension));
+    match extension {
+        ".tar.gz" => {
+            let mut gz = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+            gz.write_all(&tar).unwrap();
+            fs::write(&path, gz.finish().unwrap()).unwrap();
+        }
+        ".tar.xz" => {
+            let mut xz = xz2::write::XzEncoder::new(Vec::new(), 6);
+            xz.write_all(&tar).unwrap();
+            fs::write(&path, xz.finish().unwrap()).unwrap();
+        }
+        _ => fs::write(&path, tar).unwrap(),
+    }
+    (dir, path)
+}
+
+/// Serves every request on a local port with `status` and `body` and
+/// returns the URL to fetch.
+pub(crate) fn serve(status: &'static str, body: &'static str) -> String {
+    serve_with(move |_| {
+        format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+    })
+}
+
+/// Answers every request on a local port with whatever `respond` returns
+/// for the request head, and returns the URL to fetch.
+pub(crate) fn serve_with<F>(respond: F) -> String
+where
+    F: Fn(&str) -> String + Send + 'static,
+{
+    use std::io::BufRead;
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/versions.yml", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = io::BufReader::new(&stream);
+            let mut head = String::new();
+            while reader.read_line(&mut head).unwrap() > 0 && !head.ends_with("\r\n\r\n") {}
+            let response = respond(&head).replacen("\r\n", "\r\nConnection: close\r\n", 1);
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+    url
+}
+
+/// Stall timeout for tests that don't exercise it.
+pub(crate) const STALL: Duration = Duration::from_secs(30);
+
+pub(crate) static UNLIMITED: RateLimit = RateLimit::new(0);
+
+/// Output that can be read back while something else writes to it.
+#[derive(Clone, Default)]
+pub(crate) struct Output(pub(crate) Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Write::write(&mut *self.0.lock().unwrap(), buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Output {
+    pub(crate) fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+pub(crate) fn no_progress() -> Arc<dyn ProgressReporter> {
+    Arc::new(NoProgress)
+}
+
+pub(crate) fn test_client() -> Client {
+    build_client(&Config::default()).unwrap()
+}
+
+/// Serves `body` the way a mirror does, honouring a `Range` header unless
+/// `ranges` is off. Ranges in `fail_from` are answered with a 503.
+pub(crate) fn serve_ranges(
+    body: &'static str,
+    ranges: bool,
+    fail_from: Option<u64>,
+    requested: Arc<std::sync::Mutex<Vec<String>>>,
+) -> String {
+    serve_with(move |head| {
+        let range = head.lines().find_map(|line| {
+            line.to_ascii_lowercase()
+                .strip_prefix("range: bytes=")
+                .map(String::from)
+        });
+        requested
+            .lock()
+            .unwrap()
+            .push(range.clone().unwrap_or_default());
+        let Some((start, end)) = range.filter(|_| ranges).and_then(|range| {
+            let (start, end) = range.split_once('-')?;
+            let start: u64 = start.parse().ok()?;
+            let end = end.parse().unwrap_or(body.len() as u64 - 1);
+            Some((start, end.min(body.len() as u64 - 1)))
+        }) else {
+            return format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+        };
+        if fail_from.is_some_and(|from| start >= from) {
+            return String::from("HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n");
+        }
+        let part = &body[start as usize..=end as usize];
+        format!(
+            "HTTP/1.1 206 Partial Content\r\nETag: \"v1\"\r\nContent-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\n\r\n{}",
+            start,
+            end,
+            body.len(),
+            part.len(),
+            part
+        )
+    })
+}
+
+/// A [`ProgressReporter`] that records what it's told, merging consecutive
+/// `advanced` calls.
+#[derive(Default)]
+pub(crate) struct RecordedProgress(std::sync::Mutex<Vec<String>>);
+
+impl RecordedProgress {
+    fn push(&self, event: String) {
+        self.0.lock().unwrap().push(event);
+    }
+
+    pub(crate) fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+impl ProgressReporter for RecordedProgress {
+    fn phase_changed(&self, phase: Phase) {
+        self.push(format!("{:?}", phase));
+    }
+
+    fn started(&self, total: Option<u64>) {
+        self.push(format!("started {:?}", total));
+    }
+
+    fn advanced(&self, bytes: u64) {
+        let mut events = self.0.lock().unwrap();
+        match events.last_mut().and_then(|e| e.strip_prefix("advanced ")) {
+            Some(sum) => {
+                let sum = sum.parse::<u64>().unwrap() + bytes;
+                *events.last_mut().unwrap() = format!("advanced {}", sum);
+            }
+            None => events.push(format!("advanced {}", bytes)),
+        }
+    }
+
+    fn finished(&self) {
+        self.push(String::from("finished"));
+    }
+
+    fn warning(&self, message: &str) {
+        self.push(format!("warning {}", message));
+    }
+}
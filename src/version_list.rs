@@ -0,0 +1,929 @@
+//! Fetching, caching and loading the version list.
+
+use anyhow::anyhow;
+use anyhow::{Context, Result};
+use minisign_verify::{PublicKey, Signature};
+use reqwest::{header, Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::{fs, io};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::download::send_traced;
+use crate::progress::ProgressReporter;
+use crate::{appdata_dir, godot, unix_now, write_atomically};
+
+/// Validators of the last fetched version list, kept next to it so the
+/// next update can be a conditional request.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ListCacheMeta {
+    pub(crate) etag: Option<String>,
+    pub(crate) last_modified: Option<String>,
+    /// Unix time of the last successful fetch or revalidation.
+    #[serde(default)]
+    pub(crate) fetched_at: Option<u64>,
+}
+
+impl ListCacheMeta {
+    fn path(list: &Path) -> PathBuf {
+        list.with_extension("meta.json")
+    }
+
+    /// The validators stored for `list`, or none at all if the list or its
+    /// sidecar is missing or unreadable.
+    fn load(list: &Path) -> ListCacheMeta {
+        if !list.exists() {
+            return ListCacheMeta::default();
+        }
+        fs::read_to_string(Self::path(list))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn store(&self, list: &Path) -> Result<()> {
+        fs::write(Self::path(list), serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// Resolves `spec` in `list`, see [`godot::VersionList::resolve_with`]. If
+/// nothing matches and a `refresh` is given,
+/// it is run once to get a newer list to retry with; the list the version
+/// was found in is returned alongside it. A failed refresh is up to
+/// `refresh` to report.
+pub(crate) async fn resolve_or_refresh<F, Fut>(
+    list: godot::VersionList,
+    spec: &str,
+    platform: godot::Platform,
+    prereleases: bool,
+    refresh: Option<F>,
+) -> Result<(godot::VersionList, godot::Version)>
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = Result<godot::VersionList>>,
+{
+    let err = match list.resolve_with(spec, platform, prereleases) {
+        Ok(version) => return Ok((list, version)),
+        Err(err) => err,
+    };
+    let refresh = match refresh {
+        Some(refresh) if err.is::<godot::VersionNotFound>() => refresh,
+        _ => return Err(err),
+    };
+    let Ok(list) = refresh().await else {
+        return Err(err);
+    };
+    match list.resolve_with(spec, platform, prereleases) {
+        Ok(version) => Ok((list, version)),
+        Err(err) => Err(err.context("not found even after updating the version list")),
+    }
+}
+
+/// Downloads the version list at `url`, checks that it parses and, given a
+/// `key`, that it is signed, and only then moves it over `dest`. The request
+/// is conditional on the validators of the previous fetch; a
+/// `304 Not Modified` leaves `dest` as it is.
+pub(crate) async fn fetch_version_list(
+    client: &Client,
+    url: &str,
+    dest: &Path,
+    key: Option<&PublicKey>,
+) -> Result<()> {
+    let result: Result<()> = async {
+        let meta = ListCacheMeta::load(dest);
+        let mut request = client.get(url);
+        if let Some(etag) = &meta.etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &meta.last_modified {
+            request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+        }
+        let resp = send_traced(request).await?;
+        if resp.status() == StatusCode::NOT_MODIFIED {
+            if let Some(key) = key {
+                verify_signature(client, url, &fs::read(dest)?, key).await?;
+            }
+            tracing::debug!(url, "version list not modified");
+            return ListCacheMeta {
+                fetched_at: Some(unix_now()),
+                ..meta
+            }
+            .store(dest);
+        }
+        let resp = resp.error_for_status()?;
+        let validator = |name| {
+            resp.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let meta = ListCacheMeta {
+            etag: validator(header::ETAG),
+            last_modified: validator(header::LAST_MODIFIED),
+            fetched_at: Some(unix_now()),
+        };
+        let body = resp.text().await?;
+        godot::VersionList::parse(&body)?;
+        if let Some(key) = key {
+            verify_signature(client, url, body.as_bytes(), key).await?;
+        }
+        write_atomically(dest, body.as_bytes())?;
+        meta.store(dest)
+    }
+    .await;
+    result.with_context(|| format!("could not update the version list from {}", url))
+}
+
+/// Checks `list` against the minisign signature published at `<url>.minisig`.
+async fn verify_signature(client: &Client, url: &str, list: &[u8], key: &PublicKey) -> Result<()> {
+    let sig_url = format!("{}.minisig", url);
+    let resp = send_traced(client.get(&sig_url)).await?;
+    if resp.status() == StatusCode::NOT_FOUND {
+        return Err(anyhow!(
+            "the version list is not signed; pass --allow-unsigned to use it anyway"
+        ));
+    }
+    let text = resp.error_for_status()?.text().await?;
+    let signature = Signature::decode(&text)
+        .map_err(|err| anyhow!("could not read signature {}: {}", sig_url, err))?;
+    key.verify(list, &signature, false)
+        .map_err(|err| anyhow!("bad version list signature: {}", err))
+}
+
+/// Fetches every source into its own cache next to `dest`, merges them with
+/// earlier sources taking precedence and writes the result to `dest`. A
+/// source that fails is returned among the failures and its previous copy,
+/// if any, is used; the update only fails if no source could be fetched.
+/// Remote sources are checked against `key`, see [`fetch_version_list`].
+pub(crate) async fn update_from_sources(
+    client: &Client,
+    sources: &[String],
+    dest: &Path,
+    key: Option<&PublicKey>,
+) -> Result<Vec<String>> {
+    let cache_dir = dest.with_file_name("sources");
+    let mut merged = godot::VersionList::default();
+    let mut failures = Vec::new();
+    for source in sources {
+        let mut list = match fetch_source(client, source, &cache_dir, key).await {
+            Ok(list) => list,
+            Err(err) => {
+                failures.push(format!("{:#}", err));
+                match load_cached_source(source, &cache_dir) {
+                    Some(list) => list,
+                    None => continue,
+                }
+            }
+        };
+        list.set_source(source);
+        merged.merge(list);
+    }
+    if failures.len() == sources.len() {
+        return Err(anyhow!(
+            "could not fetch any version list source: {}",
+            failures.join("; ")
+        ));
+    }
+    write_atomically(dest, merged.to_yaml()?.as_bytes())?;
+    ListCacheMeta {
+        fetched_at: Some(unix_now()),
+        ..Default::default()
+    }
+    .store(dest)?;
+    Ok(failures)
+}
+
+fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+/// Where the last good copy of a remote `source` is kept.
+fn source_cache_path(source: &str, cache_dir: &Path) -> PathBuf {
+    let name = source
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    cache_dir.join(format!("{}.yml", name))
+}
+
+async fn fetch_source(
+    client: &Client,
+    source: &str,
+    cache_dir: &Path,
+    key: Option<&PublicKey>,
+) -> Result<godot::VersionList> {
+    let path = if is_url(source) {
+        let path = source_cache_path(source, cache_dir);
+        fs::create_dir_all(cache_dir)?;
+        fetch_version_list(client, source, &path, key).await?;
+        path
+    } else {
+        PathBuf::from(source)
+    };
+    let text = fs::read_to_string(&path)
+        .with_context(|| format!("could not read version list {}", path.display()))?;
+    godot::VersionList::parse(&text)
+        .with_context(|| format!("could not read version list {}", path.display()))
+}
+
+fn load_cached_source(source: &str, cache_dir: &Path) -> Option<godot::VersionList> {
+    if !is_url(source) {
+        return None;
+    }
+    let text = fs::read_to_string(source_cache_path(source, cache_dir)).ok()?;
+    godot::VersionList::parse(&text).ok()
+}
+
+/// Whether the version list at `list` was last fetched more than `ttl` before
+/// `now`. Lists without a recorded fetch time fall back to their
+/// modification time; a missing list is always stale.
+pub(crate) fn is_stale(list: &Path, ttl: Duration, now: u64) -> bool {
+    let fetched_at = ListCacheMeta::load(list).fetched_at.or_else(|| {
+        let modified = list.metadata().ok()?.modified().ok()?;
+        Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+    });
+    match fetched_at {
+        Some(fetched_at) => now.saturating_sub(fetched_at) >= ttl.as_secs(),
+        None => true,
+    }
+}
+
+/// The release an export templates archive such as
+/// `Godot_v4.2.1-stable_mono_export_templates.tpz` belongs to.
+pub(crate) fn templates_version(name: &str) -> Option<godot::Version> {
+    godot::Version::parse(name.strip_suffix("_export_templates.tpz")?).ok()
+}
+
+/// Builds a version list by crawling the directory listings of a download
+/// mirror laid out like `<mirror>/4.2.1/`, `<mirror>/4.2.1/mono/` and
+/// `<mirror>/4.3/beta1/mono/`. Files that aren't editor archives or export
+/// templates are skipped with a warning.
+pub(crate) async fn crawl_mirror(
+    client: &Client,
+    mirror: &str,
+    progress: &dyn ProgressReporter,
+) -> Result<godot::VersionList> {
+    let mut list = godot::VersionList::default();
+    let mut pending = vec![(format!("{}/", mirror.trim_end_matches('/')), 0)];
+    while let Some((dir, depth)) = pending.pop() {
+        progress.message(&format!("Crawling {}...", dir));
+        let html = send_traced(client.get(&dir))
+            .await?
+            .error_for_status()
+            .with_context(|| format!("could not list {}", dir))?
+            .text()
+            .await?;
+        for link in listing_links(&html) {
+            let url = format!("{}{}", dir, link);
+            if let Some(name) = link.strip_suffix('/') {
+                // Releases at the top, then prerelease and mono folders.
+                let wanted = match depth {
+                    0 => name.starts_with(|c: char| c.is_ascii_digit()),
+                    _ => depth < 3,
+                };
+                if wanted {
+                    pending.push((url, depth + 1));
+                }
+            } else if let Some(build) = godot::Build::from_filename(&link) {
+                list.insert(build, godot::Artifact::from_url(url));
+            } else if let Some(version) = templates_version(&link) {
+                let info = list.release_info_mut(&version);
+                info.export_templates
+                    .get_or_insert_with(|| godot::Artifact::from_url(url));
+            } else {
+                progress.warning(&format!("skipping {}", url));
+            }
+        }
+    }
+    Ok(list)
+}
+
+/// Relative links in a server-generated directory listing, leaving out the
+/// parent directory, sort links and anything pointing elsewhere.
+fn listing_links(html: &str) -> Vec<String> {
+    html.split("href=\"")
+        .skip(1)
+        .filter_map(|rest| rest.split_once('"').map(|(link, _)| link))
+        .filter(|link| {
+            !link.is_empty() && !link.starts_with(['.', '/', '?', '#']) && !link.contains("://")
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// The `rel="next"` target of a GitHub `Link` pagination header.
+pub(crate) fn next_page_link(link: &str) -> Option<String> {
+    link.split(',').find_map(|part| {
+        let (target, rel) = part.split_once(';')?;
+        (rel.trim() == r#"rel="next""#).then(|| {
+            target
+                .trim()
+                .trim_start_matches('<')
+                .trim_end_matches('>')
+                .to_string()
+        })
+    })
+}
+
+pub(crate) fn version_list_path() -> Result<PathBuf> {
+    Ok(appdata_dir()?.join("versions.yml"))
+}
+
+/// Snapshot of the published version list, used until one is downloaded.
+const EMBEDDED_VERSION_LIST: &str = include_str!("../versions.yml");
+
+/// The downloaded version list with the user's `versions.local.yml` from
+/// the same directory merged over it.
+pub(crate) fn load_version_list(progress: &dyn ProgressReporter) -> Result<godot::VersionList> {
+    let path = version_list_path()?;
+    let mut list = load_version_list_from(&path, progress)?;
+    if let Some(local) = load_local_version_list(&path.with_file_name("versions.local.yml"))? {
+        list.overlay(local);
+    }
+    Ok(list)
+}
+
+/// The user-maintained list at `path`, if there is one. Unlike the
+/// downloaded list there is nothing to fall back to, so errors are fatal.
+fn load_local_version_list(path: &Path) -> Result<Option<godot::VersionList>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let text = fs::read_to_string(path)?;
+    godot::VersionList::parse(&text)
+        .map(Some)
+        .with_context(|| format!("could not read local version list {}", path.display()))
+}
+
+/// Reads the version list at `path`, falling back to the embedded snapshot
+/// if it is missing or unparsable. A missing list is seeded with the
+/// snapshot, marked as never fetched so the next refresh replaces it.
+/// Falling back is reported to `progress` as a warning.
+fn load_version_list_from(
+    path: &Path,
+    progress: &dyn ProgressReporter,
+) -> Result<godot::VersionList> {
+    let problem = match fs::read_to_string(path) {
+        Ok(text) => match godot::VersionList::parse(&text) {
+            Ok(list) => return Ok(list),
+            Err(err) => format!("{} is unreadable ({:#})", path.display(), err),
+        },
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            write_atomically(path, EMBEDDED_VERSION_LIST.as_bytes())?;
+            ListCacheMeta {
+                fetched_at: Some(0),
+                ..Default::default()
+            }
+            .store(path)?;
+            format!("no version list found at {}", path.display())
+        }
+        Err(err) => return Err(err.into()),
+    };
+    progress.warning(&format!(
+        "{}; using the built-in list, which may be out of date. \
+         Run `godotup update` to fetch the latest one.",
+        problem
+    ));
+    godot::VersionList::from_yaml(EMBEDDED_VERSION_LIST)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::progress::NoProgress;
+    use crate::test_util::{serve, serve_with, test_client, test_dir, RecordedProgress};
+
+    #[test]
+    fn test_next_page_link() {
+        let link = r#"<https://api.github.com/repositories/1/releases?per_page=100&page=2>; rel="next", <https://api.github.com/repositories/1/releases?per_page=100&page=5>; rel="last""#;
+        assert_eq!(
+            next_page_link(link).as_deref(),
+            Some("https://api.github.com/repositories/1/releases?per_page=100&page=2")
+        );
+        let last = r#"<https://api.github.com/repositories/1/releases?per_page=100&page=4>; rel="prev", <https://api.github.com/repositories/1/releases?per_page=100&page=1>; rel="first""#;
+        assert_eq!(next_page_link(last), None);
+        assert_eq!(next_page_link(""), None);
+    }
+
+    #[tokio::test]
+    async fn test_crawl_mirror() {
+        let url = serve_with(|head| {
+            let path = head.split(' ').nth(1).unwrap_or_default();
+            let fixture = Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/fixtures/mirror")
+                .join(path.trim_start_matches('/'))
+                .join("index.html");
+            match fs::read_to_string(fixture) {
+                Ok(body) => format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                ),
+                Err(_) => String::from("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n"),
+            }
+        });
+        let mirror = url.trim_end_matches("versions.yml");
+        let list = crawl_mirror(&test_client(), mirror, &NoProgress)
+            .await
+            .unwrap();
+
+        let builds = list
+            .builds()
+            .map(|(build, artifact)| {
+                assert_eq!(
+                    artifact
+                        .url
+                        .strip_prefix(mirror)
+                        .unwrap()
+                        .rsplit('/')
+                        .next(),
+                    Some(artifact.filename.as_str())
+                );
+                build.slug()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            builds,
+            [
+                "4.3.0-beta1-mono-win64",
+                "4.3.0-beta1-linux64",
+                "4.3.0-beta1-linuxarm64",
+                "4.2.1-stable-mono-win64",
+                "4.2.1-stable-mono-linux64",
+                "4.2.1-stable-win64",
+                "4.2.1-stable-linux64",
+                "4.2.1-stable-macos",
+            ]
+        );
+        let v = |s| godot::Version::parse(s).unwrap();
+        let artifact = list
+            .find_artifact(&v("4.3-beta1-mono"), godot::Platform::Win64)
+            .unwrap();
+        assert_eq!(
+            artifact.url,
+            format!("{}4.3/beta1/mono/Godot_v4.3-beta1_mono_win64.zip", mirror)
+        );
+        assert_eq!(
+            list.find_templates(&v("4.2.1-mono")).unwrap().url,
+            format!(
+                "{}4.2.1/mono/Godot_v4.2.1-stable_mono_export_templates.tpz",
+                mirror
+            )
+        );
+        assert!(list.find_templates(&v("4.3-beta1")).is_none());
+
+        assert_eq!(
+            listing_links(
+                r#"<a href="../">..</a> <a href="?C=N">n</a> <a href="https://x/">x</a> <a href="4.0/">4.0</a>"#
+            ),
+            ["4.0/"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_or_refresh() {
+        use godot::{Platform, VersionNotFound};
+        use std::cell::Cell;
+
+        let list = |specs: &[&str]| -> godot::VersionList {
+            specs
+                .iter()
+                .map(|spec| {
+                    let version = godot::Version::parse(spec).unwrap();
+                    let platform = Platform::Linux64;
+                    (godot::Build { version, platform }, spec.to_string())
+                })
+                .collect()
+        };
+        let old = || list(&["4.2.1"]);
+        let new = || list(&["4.2.1", "4.2.2"]);
+        let refreshes = Cell::new(0);
+        let refresh = |list: godot::VersionList| {
+            let refreshes = &refreshes;
+            Some(move || async move {
+                refreshes.set(refreshes.get() + 1);
+                Ok(list)
+            })
+        };
+
+        // Found without refreshing.
+        let (_, version) =
+            resolve_or_refresh(old(), "4.2.1", Platform::Linux64, false, refresh(new()))
+                .await
+                .unwrap();
+        assert_eq!(version.short(), "4.2.1");
+        assert_eq!(refreshes.get(), 0);
+
+        // Missing, found after one refresh.
+        let (found_in, version) =
+            resolve_or_refresh(old(), "4.2.2", Platform::Linux64, false, refresh(new()))
+                .await
+                .unwrap();
+        assert_eq!(version.short(), "4.2.2");
+        assert!(found_in
+            .find_artifact(&version, Platform::Linux64)
+            .is_some());
+        assert_eq!(refreshes.get(), 1);
+
+        // Still missing: refreshed once, and the error says so.
+        let err = resolve_or_refresh(old(), "4.3", Platform::Linux64, false, refresh(new()))
+            .await
+            .unwrap_err();
+        assert_eq!(refreshes.get(), 2);
+        assert_eq!(
+            err.to_string(),
+            "not found even after updating the version list"
+        );
+        assert_eq!(err.downcast_ref::<VersionNotFound>().unwrap().spec, "4.3");
+
+        // Invalid specs are not retried.
+        let err = resolve_or_refresh(old(), "4.x.y", Platform::Linux64, false, refresh(new()))
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<VersionNotFound>().is_none());
+        assert_eq!(refreshes.get(), 2);
+
+        // Offline, or a failed refresh: the original error.
+        let none = None::<fn() -> std::future::Ready<Result<godot::VersionList>>>;
+        let err = resolve_or_refresh(old(), "4.2.2", Platform::Linux64, false, none)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().starts_with("no version matching `4.2.2`"));
+        let failing = Some(|| async { Err(anyhow!("offline")) });
+        let err = resolve_or_refresh(old(), "4.2.2", Platform::Linux64, false, failing)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().starts_with("no version matching `4.2.2`"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_signed_version_list() {
+        const LIST: &str = include_str!("../tests/fixtures/signed/versions.yml");
+        const SIGNATURE: &str = include_str!("../tests/fixtures/signed/versions.yml.minisig");
+        let key = PublicKey::decode(include_str!("../tests/fixtures/signed/minisign.pub")).unwrap();
+        let respond = |list: &'static str, signature: Option<&'static str>| {
+            move |head: &str| {
+                let (status, body) = if head.starts_with("GET /versions.yml.minisig ") {
+                    signature.map_or(("404 Not Found", ""), |sig| ("200 OK", sig))
+                } else {
+                    ("200 OK", list)
+                };
+                format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+            }
+        };
+        let tmp = test_dir("fetch-signed-list");
+        let dir = tmp.path();
+        let dest = dir.join("versions.yml");
+
+        let url = serve_with(respond(LIST, Some(SIGNATURE)));
+        fetch_version_list(&test_client(), &url, &dest, Some(&key))
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), LIST);
+
+        // A tampered list, a signature by another key or no signature at all
+        // leave the previous list in place.
+        let tampered: &str = LIST.replacen("4.0.2", "4.0.9", 1).leak();
+        let other_key = include_str!("../tests/fixtures/signed/other-key.minisig");
+        for (respond, reason) in [
+            (
+                respond(tampered, Some(SIGNATURE)),
+                "signature verification failed",
+            ),
+            (respond(LIST, Some(other_key)), "different key"),
+            (respond(LIST, None), "not signed"),
+        ] {
+            let url = serve_with(respond);
+            let err = fetch_version_list(&test_client(), &url, &dest, Some(&key))
+                .await
+                .unwrap_err();
+            assert!(format!("{:#}", err).contains(reason), "{:#}", err);
+            assert_eq!(fs::read_to_string(&dest).unwrap(), LIST);
+        }
+
+        // Unless verification is turned off.
+        let url = serve_with(respond(tampered, None));
+        fetch_version_list(&test_client(), &url, &dest, None)
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), tampered);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_version_list() {
+        let tmp = test_dir("fetch-list");
+        let dir = tmp.path();
+        let dest = dir.join("versions.yml");
+        fs::write(&dest, "old list").unwrap();
+
+        let url = serve("200 OK", "schema: 2\nreleases: []\n");
+        fetch_version_list(&test_client(), &url, &dest, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(&dest).unwrap(),
+            "schema: 2\nreleases: []\n"
+        );
+        assert!(!dir.join("versions.part").exists());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_version_list_conditionally() {
+        let tmp = test_dir("fetch-list-conditional");
+        let dir = tmp.path();
+        let dest = dir.join("versions.yml");
+        let url = serve_with(|head| {
+            let head = head.to_ascii_lowercase();
+            if head.contains("if-none-match: \"v1\"")
+                && head.contains("if-modified-since: tue, 12 dec 2023 15:00:00 gmt")
+            {
+                return String::from("HTTP/1.1 304 Not Modified\r\n\r\n");
+            }
+            let body = "schema: 2\nreleases: []\n";
+            format!(
+                "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nLast-Modified: Tue, 12 Dec 2023 15:00:00 GMT\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        });
+
+        fetch_version_list(&test_client(), &url, &dest, None)
+            .await
+            .unwrap();
+        let meta = ListCacheMeta::load(&dest);
+        assert!(meta.fetched_at.is_some());
+        assert_eq!(meta.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(
+            meta.last_modified.as_deref(),
+            Some("Tue, 12 Dec 2023 15:00:00 GMT")
+        );
+
+        // A 304 must not rewrite the list.
+        fs::write(&dest, "schema: 2\nreleases: [] # cached\n").unwrap();
+        fetch_version_list(&test_client(), &url, &dest, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(&dest).unwrap(),
+            "schema: 2\nreleases: [] # cached\n"
+        );
+
+        // A corrupt sidecar means an unconditional fetch.
+        fs::write(ListCacheMeta::path(&dest), "{not json").unwrap();
+        assert_eq!(ListCacheMeta::load(&dest), ListCacheMeta::default());
+        fetch_version_list(&test_client(), &url, &dest, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(&dest).unwrap(),
+            "schema: 2\nreleases: []\n"
+        );
+        let reloaded = ListCacheMeta::load(&dest);
+        assert_eq!(
+            (reloaded.etag, reloaded.last_modified),
+            (meta.etag, meta.last_modified)
+        );
+
+        // Without the list itself the sidecar is ignored.
+        fs::remove_file(&dest).unwrap();
+        assert_eq!(ListCacheMeta::load(&dest), ListCacheMeta::default());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_version_list_failure_keeps_old_list() {
+        let tmp = test_dir("fetch-list-failure");
+        let dir = tmp.path();
+        let dest = dir.join("versions.yml");
+        fs::write(&dest, "old list").unwrap();
+
+        for url in [
+            serve("404 Not Found", "nope"),
+            serve("200 OK", "<html>not a version list</html>"),
+            String::from("http://127.0.0.1:1/versions.yml"),
+        ] {
+            let err = fetch_version_list(&test_client(), &url, &dest, None)
+                .await
+                .unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("could not update the version list from {}", url)
+            );
+            assert_eq!(fs::read_to_string(&dest).unwrap(), "old list");
+            assert!(!dir.join("versions.part").exists());
+        }
+    }
+
+    #[test]
+    fn test_version_list_staleness() {
+        let tmp = test_dir("staleness");
+        let dir = tmp.path();
+        let list = dir.join("versions.yml");
+        let day = Duration::from_secs(24 * 60 * 60);
+        assert!(is_stale(&list, day, unix_now()));
+
+        fs::write(&list, "schema: 2\nreleases: []\n").unwrap();
+        assert!(!is_stale(&list, day, unix_now()));
+        assert!(is_stale(&list, day, unix_now() + day.as_secs()));
+
+        let meta = ListCacheMeta {
+            fetched_at: Some(1_000_000),
+            ..Default::default()
+        };
+        meta.store(&list).unwrap();
+        assert!(!is_stale(&list, day, 1_000_000 + day.as_secs() - 1));
+        assert!(is_stale(&list, day, 1_000_000 + day.as_secs()));
+        assert!(is_stale(&list, Duration::ZERO, 1_000_000));
+    }
+
+    #[test]
+    fn test_embedded_version_list_fallback() {
+        let tmp = test_dir("embedded-list");
+        let dir = tmp.path();
+        let path = dir.join("versions.yml");
+        let embedded = godot::VersionList::from_yaml(EMBEDDED_VERSION_LIST).unwrap();
+        let platform = godot::Platform::Linux64;
+        let progress = RecordedProgress::default();
+
+        let list = load_version_list_from(&path, &progress).unwrap();
+        assert_eq!(list.len(platform), embedded.len(platform));
+        let warnings = progress.take();
+        assert_eq!(warnings.len(), 1);
+        assert!(
+            warnings[0].starts_with("warning no version list found"),
+            "{:?}",
+            warnings
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), EMBEDDED_VERSION_LIST);
+        assert!(is_stale(
+            &path,
+            Duration::from_secs(24 * 60 * 60),
+            unix_now()
+        ));
+
+        fs::write(&path, "versions: [1, 2]").unwrap();
+        let list = load_version_list_from(&path, &progress).unwrap();
+        assert_eq!(list.len(platform), embedded.len(platform));
+        assert!(progress.take()[0].contains("is unreadable"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "versions: [1, 2]");
+
+        fs::write(&path, "schema: 2\nreleases: []\n").unwrap();
+        assert!(load_version_list_from(&path, &progress)
+            .unwrap()
+            .is_empty(platform));
+        assert!(progress.take().is_empty());
+    }
+
+    #[test]
+    fn test_local_version_list_overlay() {
+        let fixture =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/versions.local.yml");
+        let local = load_local_version_list(&fixture).unwrap().unwrap();
+        let mut list = godot::VersionList::from_yaml(EMBEDDED_VERSION_LIST).unwrap();
+        let before = list.len(godot::Platform::Linux64);
+        list.overlay(local);
+
+        let build = |spec| godot::Build {
+            version: godot::Version::parse(spec).unwrap(),
+            platform: godot::Platform::Linux64,
+        };
+        let url = |spec| {
+            let b = build(spec);
+            list.find_artifact(&b.version, b.platform)
+                .unwrap()
+                .url
+                .clone()
+        };
+        // Override of a published build.
+        assert_eq!(
+            url("4.0.2"),
+            "https://builds.internal.example/godot/Godot_v4.0.2-stable_linux.x86_64.zip"
+        );
+        assert!(list.is_local(&build("4.0.2")));
+        // Addition of a custom build, resolvable with the usual syntax.
+        assert_eq!(
+            list.resolve("4.3-dev", godot::Platform::Linux64)
+                .unwrap()
+                .short(),
+            "4.3-dev1"
+        );
+        assert!(list.is_local(&build("4.3-dev1")));
+        assert_eq!(list.len(godot::Platform::Linux64), before + 1);
+        // Untouched published builds.
+        assert!(!list.is_local(&build("4.0.2-rc1")));
+        assert_eq!(
+            url("4.0.2-rc1"),
+            "4.0.2/rc1/Godot_v4.0.2-rc1_linux_x86_64.zip"
+        );
+
+        let tmp = test_dir("local-list");
+        let dir = tmp.path();
+        assert!(load_local_version_list(&dir.join("versions.local.yml"))
+            .unwrap()
+            .is_none());
+        fs::write(dir.join("versions.local.yml"), "releases: nope").unwrap();
+        let err = load_local_version_list(&dir.join("versions.local.yml")).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("could not read local version list"));
+    }
+
+    #[tokio::test]
+    async fn test_update_from_sources() {
+        let tmp = test_dir("sources");
+        let dir = tmp.path();
+        let dest = dir.join("versions.yml");
+        let list = |url: &str| {
+            format!(
+                "schema: 2\nreleases:\n- {{major: 4, minor: 2, patch: 1, suffix: stable, is_mono: false, \
+             artifacts: {{linux64: {{url: {0}, filename: a.zip}}}}}}\n",
+                url
+            )
+        };
+        let mirror = serve_with(move |_| {
+            let body = "schema: 2\nreleases:\n- {major: 4, minor: 2, patch: 1, suffix: stable, is_mono: false, \
+                    artifacts: {linux64: {url: https://mirror/a.zip, filename: a.zip}}}\n";
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        });
+        let local = dir.join("team.yml");
+        fs::write(
+            &local,
+            list("https://team/a.zip").replace("patch: 1", "patch: 2"),
+        )
+        .unwrap();
+        let public = dir.join("public.yml");
+        fs::write(
+            &public,
+            list("https://public/a.zip").replace("minor: 2", "minor: 1"),
+        )
+        .unwrap();
+        fs::write(&dest, "old list").unwrap();
+
+        let broken = String::from("http://127.0.0.1:1/versions.yml");
+        let local = local.to_string_lossy().into_owned();
+        let public = public.to_string_lossy().into_owned();
+        let sources = [
+            mirror.clone(),
+            broken.clone(),
+            local.clone(),
+            public.clone(),
+        ];
+        update_from_sources(&test_client(), &sources, &dest, None)
+            .await
+            .unwrap();
+
+        let merged = godot::VersionList::from_yaml(&fs::read_to_string(&dest).unwrap()).unwrap();
+        let artifact = |spec| {
+            merged
+                .find_artifact(
+                    &godot::Version::parse(spec).unwrap(),
+                    godot::Platform::Linux64,
+                )
+                .unwrap()
+                .clone()
+        };
+        assert_eq!(artifact("4.2.1").url, "https://mirror/a.zip");
+        assert_eq!(artifact("4.2.1").source.as_deref(), Some(mirror.as_str()));
+        assert_eq!(artifact("4.2.2").source.as_deref(), Some(local.as_str()));
+        assert_eq!(artifact("4.1.1").source.as_deref(), Some(public.as_str()));
+        assert!(source_cache_path(&mirror, &dir.join("sources")).exists());
+
+        // Earlier sources win.
+        let sources = [public.clone(), local.clone()];
+        update_from_sources(&test_client(), &sources, &dest, None)
+            .await
+            .unwrap();
+        let merged = godot::VersionList::from_yaml(&fs::read_to_string(&dest).unwrap()).unwrap();
+        assert_eq!(merged.len(godot::Platform::Linux64), 2);
+
+        // Nothing reachable: the previous list stays.
+        let before = fs::read_to_string(&dest).unwrap();
+        let err = update_from_sources(
+            &test_client(),
+            &[
+                broken,
+                dir.join("missing.yml").to_string_lossy().into_owned(),
+            ],
+            &dest,
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("could not fetch any version list source"));
+        assert_eq!(fs::read_to_string(&dest).unwrap(), before);
+    }
+}
@@ -0,0 +1,44 @@
+use std::{env, fs, process::Command};
+
+/// With `--progress json` everything godotup writes to stderr has to be a
+/// JSON event, including the warnings from loading the config and the
+/// version list.
+#[test]
+fn test_json_progress_stderr() {
+    let home = env::temp_dir().join(format!("godotup-test-json-stderr-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&home);
+    let config = home.join(".config/godotup");
+    fs::create_dir_all(&config).unwrap();
+    fs::create_dir_all(home.join(".local/share")).unwrap();
+    fs::write(
+        config.join("default-config.toml"),
+        "version_list_proxy_url = \"https://example.com/versions.yml\"\n\
+         progress_chars = \"x\"\n\
+         danger_accept_invalid_certs = true\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_godotup"))
+        .args(["--progress", "json", "export-list", "--format", "json"])
+        .env("HOME", &home)
+        .env_remove("XDG_CONFIG_HOME")
+        .env_remove("XDG_DATA_HOME")
+        .env_remove("GODOTUP_LOG")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let events: Vec<serde_json::Value> = stderr
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|_| panic!("not JSON: {}", line)))
+        .collect();
+    let warnings = events
+        .iter()
+        .filter(|event| event["event"] == "warning")
+        .count();
+    // The deprecated key, the progress bar characters, the unchecked
+    // certificates and the missing version list.
+    assert_eq!(warnings, 4, "{}", stderr);
+    let _ = fs::remove_dir_all(&home);
+}